@@ -0,0 +1,7 @@
+-- Store the UTC time anchor of each log so timestamps can be reported as wall-clock time
+
+ALTER TABLE log_files ADD COLUMN IF NOT EXISTS time_anchor_source VARCHAR(20); -- time_of_day, gps
+ALTER TABLE log_files ADD COLUMN IF NOT EXISTS log_start_utc TIMESTAMPTZ; -- UTC time of timestamp 0
+ALTER TABLE log_files ADD COLUMN IF NOT EXISTS time_anchor_offset DOUBLE PRECISION; -- Seconds between the anchor and time_of_day
+
+CREATE INDEX IF NOT EXISTS idx_log_files_log_start_utc ON log_files(log_start_utc);
@@ -0,0 +1,33 @@
+-- User-supplied schema files (messages.xml, airframe, flight plan) that override what a .log embeds
+
+CREATE TABLE schema_artifacts (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    kind VARCHAR(20) NOT NULL, -- messages, airframe, flight_plan
+    name VARCHAR(255) NOT NULL,
+    version INTEGER NOT NULL, -- Increments for each new content uploaded under the same name
+    content_hash VARCHAR(64) NOT NULL, -- blake3 hash of the content
+    file_size BIGINT NOT NULL,
+    storage_path VARCHAR(500) NOT NULL,
+    summary TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (user_id, kind, content_hash),
+    UNIQUE (user_id, kind, name, version)
+);
+
+CREATE INDEX idx_schema_artifacts_user_kind ON schema_artifacts(user_id, kind);
+CREATE INDEX idx_schema_artifacts_hash ON schema_artifacts(content_hash);
+
+-- Artifacts attached to a .log/.data file pair
+CREATE TABLE file_pair_artifacts (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    file_pair_id UUID NOT NULL,
+    artifact_id UUID NOT NULL REFERENCES schema_artifacts(id) ON DELETE CASCADE,
+    kind VARCHAR(20) NOT NULL,
+    sender_id SMALLINT, -- Aircraft an airframe or flight plan applies to, NULL for messages
+    attached_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- One artifact of each kind per aircraft of a pair
+CREATE UNIQUE INDEX idx_file_pair_artifacts_unique ON file_pair_artifacts(file_pair_id, kind, COALESCE(sender_id, -1));
+CREATE INDEX idx_file_pair_artifacts_artifact ON file_pair_artifacts(artifact_id);
@@ -0,0 +1,7 @@
+-- Record which compressed upload or archive bundle a log file was expanded from
+
+ALTER TABLE log_files ADD COLUMN IF NOT EXISTS source_archive_name VARCHAR(255); -- File name of the uploaded archive
+ALTER TABLE log_files ADD COLUMN IF NOT EXISTS source_archive_hash VARCHAR(64); -- blake3 hash of the uploaded archive
+ALTER TABLE log_files ADD COLUMN IF NOT EXISTS archive_member_path TEXT; -- Path of the file inside the archive
+
+CREATE INDEX IF NOT EXISTS idx_log_files_source_archive_hash ON log_files(source_archive_hash);
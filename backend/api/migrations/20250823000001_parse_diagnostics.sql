@@ -0,0 +1,19 @@
+-- Diagnostics of the lines of a .data file that could not be decoded
+
+CREATE TABLE IF NOT EXISTS parse_diagnostics (
+    id BIGSERIAL PRIMARY KEY,
+    file_id UUID NOT NULL REFERENCES log_files(id) ON DELETE CASCADE,
+    line_number BIGINT NOT NULL,
+    byte_offset BIGINT NOT NULL,
+    kind VARCHAR(30) NOT NULL, -- unknown_message, arity_mismatch, bad_number, truncated_line, non_monotonic_time, checksum_error
+    message TEXT NOT NULL,
+    snippet TEXT NOT NULL,
+    suggestion TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_parse_diagnostics_file_id ON parse_diagnostics(file_id, line_number);
+CREATE INDEX IF NOT EXISTS idx_parse_diagnostics_kind ON parse_diagnostics(file_id, kind);
+
+-- Count per kind, including the diagnostics past the stored ones
+ALTER TABLE log_files ADD COLUMN IF NOT EXISTS diagnostic_counts JSONB;
+ALTER TABLE log_files ADD COLUMN IF NOT EXISTS diagnostics_updated_at TIMESTAMPTZ;
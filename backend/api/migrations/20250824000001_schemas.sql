@@ -0,0 +1,13 @@
+-- Registry of the distinct message protocols log files were decoded with
+
+CREATE TABLE IF NOT EXISTS schemas (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    schema_hash VARCHAR(64) NOT NULL UNIQUE, -- blake3 hash of the normalized <protocol> section
+    message_count INTEGER NOT NULL,
+    protocol JSONB NOT NULL, -- Message definitions, for comparing schemas
+    first_seen_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE log_files ADD COLUMN IF NOT EXISTS schema_id UUID REFERENCES schemas(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_log_files_schema_id ON log_files(schema_id);
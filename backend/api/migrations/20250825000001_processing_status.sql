@@ -0,0 +1,4 @@
+-- Timestamps of the background parsing of each file
+
+ALTER TABLE log_files ADD COLUMN IF NOT EXISTS processing_started_at TIMESTAMPTZ;
+ALTER TABLE log_files ADD COLUMN IF NOT EXISTS processing_completed_at TIMESTAMPTZ;
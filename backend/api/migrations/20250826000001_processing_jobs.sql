@@ -0,0 +1,34 @@
+-- Durable queue of file pairs to parse, shared by every backend replica
+
+CREATE TABLE IF NOT EXISTS processing_jobs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    file_id UUID NOT NULL REFERENCES log_files(id) ON DELETE CASCADE,
+    file_pair_id UUID,
+    status VARCHAR(20) NOT NULL DEFAULT 'queued', -- queued, running, completed, dead, cancelled
+    priority INTEGER NOT NULL DEFAULT 0, -- Higher runs first
+    attempts INTEGER NOT NULL DEFAULT 0,
+    max_attempts INTEGER NOT NULL DEFAULT 5,
+    run_at TIMESTAMPTZ NOT NULL DEFAULT NOW(), -- Not claimed before, pushed back after a failure
+    locked_by VARCHAR(100), -- Worker holding the lease
+    locked_until TIMESTAMPTZ, -- Lease expiry; an expired running job is claimed again
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    started_at TIMESTAMPTZ,
+    completed_at TIMESTAMPTZ
+);
+
+-- At most one queued job per file pair
+CREATE UNIQUE INDEX IF NOT EXISTS idx_processing_jobs_queued_pair
+    ON processing_jobs ((COALESCE(file_pair_id, file_id))) WHERE status = 'queued';
+CREATE INDEX IF NOT EXISTS idx_processing_jobs_claim ON processing_jobs(priority DESC, run_at) WHERE status IN ('queued', 'running');
+CREATE INDEX IF NOT EXISTS idx_processing_jobs_file_id ON processing_jobs(file_id);
+CREATE INDEX IF NOT EXISTS idx_processing_jobs_status ON processing_jobs(status, updated_at);
+
+-- Files uploaded before the queue existed
+INSERT INTO processing_jobs (file_id, file_pair_id)
+SELECT DISTINCT ON (COALESCE(file_pair_id, id)) id, file_pair_id
+FROM log_files
+WHERE processing_status IN ('pending', 'processing')
+ORDER BY COALESCE(file_pair_id, id), file_extension = 'data' DESC
+ON CONFLICT DO NOTHING;
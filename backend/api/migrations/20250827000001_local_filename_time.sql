@@ -0,0 +1,26 @@
+-- The time in a log file name is the ground station local time, its UTC offset is unknown
+
+DROP VIEW IF EXISTS file_pairs;
+
+ALTER TABLE log_files ALTER COLUMN extracted_timestamp TYPE TIMESTAMP USING extracted_timestamp AT TIME ZONE 'UTC';
+COMMENT ON COLUMN log_files.extracted_timestamp IS 'Local time in the file name, UTC offset unknown';
+
+CREATE OR REPLACE VIEW file_pairs AS
+SELECT 
+    fp.file_pair_id,
+    fp.base_filename,
+    fp.extracted_timestamp,
+    COUNT(*) as file_count,
+    STRING_AGG(fp.file_extension, ', ' ORDER BY fp.file_extension) as extensions,
+    STRING_AGG(fp.id::text, ', ' ORDER BY fp.file_extension) as file_ids,
+    STRING_AGG(fp.original_filename, ', ' ORDER BY fp.file_extension) as filenames,
+    SUM(fp.file_size) as total_size,
+    MIN(fp.upload_timestamp) as first_upload,
+    MAX(fp.upload_timestamp) as last_upload,
+    fp.user_id
+FROM log_files fp 
+WHERE fp.file_pair_id IS NOT NULL
+GROUP BY fp.file_pair_id, fp.base_filename, fp.extracted_timestamp, fp.user_id
+ORDER BY fp.extracted_timestamp DESC;
+
+COMMENT ON VIEW file_pairs IS 'View showing paired PaparazziUAV log/data files for easy management';
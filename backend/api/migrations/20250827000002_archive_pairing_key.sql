@@ -0,0 +1,26 @@
+-- Files expanded from an archive pair on their directory in the archive and their base name,
+-- which can be longer than a file name
+
+DROP VIEW IF EXISTS file_pairs;
+
+ALTER TABLE log_files ALTER COLUMN base_filename TYPE TEXT;
+
+CREATE OR REPLACE VIEW file_pairs AS
+SELECT 
+    fp.file_pair_id,
+    fp.base_filename,
+    fp.extracted_timestamp,
+    COUNT(*) as file_count,
+    STRING_AGG(fp.file_extension, ', ' ORDER BY fp.file_extension) as extensions,
+    STRING_AGG(fp.id::text, ', ' ORDER BY fp.file_extension) as file_ids,
+    STRING_AGG(fp.original_filename, ', ' ORDER BY fp.file_extension) as filenames,
+    SUM(fp.file_size) as total_size,
+    MIN(fp.upload_timestamp) as first_upload,
+    MAX(fp.upload_timestamp) as last_upload,
+    fp.user_id
+FROM log_files fp 
+WHERE fp.file_pair_id IS NOT NULL
+GROUP BY fp.file_pair_id, fp.base_filename, fp.extracted_timestamp, fp.user_id
+ORDER BY fp.extracted_timestamp DESC;
+
+COMMENT ON VIEW file_pairs IS 'View showing paired PaparazziUAV log/data files for easy management';
@@ -0,0 +1,10 @@
+-- Single-use tickets opening an event stream, for EventSource clients that cannot send an Authorization header
+
+CREATE TABLE IF NOT EXISTS event_tickets (
+    ticket_hash VARCHAR(64) PRIMARY KEY, -- blake3 of the ticket; the ticket itself is never stored
+    session_id UUID NOT NULL REFERENCES user_sessions(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_event_tickets_expires_at ON event_tickets(expires_at);
@@ -0,0 +1,3 @@
+-- Stages of a job that succeeded (analysis, ingestion, columnar), skipped when the job is retried
+
+ALTER TABLE processing_jobs ADD COLUMN IF NOT EXISTS stages_done TEXT[] NOT NULL DEFAULT '{}';
@@ -1,9 +1,10 @@
 use axum::{
     extract::{Request, State},
-    http::{header::AUTHORIZATION, StatusCode},
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
     middleware::Next,
     response::Response,
 };
+use sqlx::PgPool;
 use std::sync::Arc;
 use uuid::Uuid;
 
@@ -16,8 +17,6 @@ pub async fn auth_middleware(
     mut request: Request,
     next: Next,
 ) -> Result<Response, StatusCode> {
-    let auth_service = AuthService::new();
-    
     let auth_header = request
         .headers()
         .get(AUTHORIZATION)
@@ -34,31 +33,7 @@ pub async fn auth_middleware(
         Some(token) => token,
         None => return Err(StatusCode::UNAUTHORIZED),
     };
-
-    let claims = match auth_service.verify_token(token) {
-        Ok(claims) => claims,
-        Err(AuthError::TokenExpired) => return Err(StatusCode::UNAUTHORIZED),
-        Err(AuthError::InvalidToken) => return Err(StatusCode::UNAUTHORIZED),
-        Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
-    };
-
-    // Verify session is still active in database
-    let session_id = match Uuid::parse_str(&claims.jti) {
-        Ok(id) => id,
-        Err(_) => return Err(StatusCode::UNAUTHORIZED),
-    };
-
-    let session_exists = sqlx::query!(
-        "SELECT id FROM user_sessions WHERE id = $1 AND is_active = true AND expires_at > NOW()",
-        session_id
-    )
-    .fetch_optional(&state.db)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    if session_exists.is_none() {
-        return Err(StatusCode::UNAUTHORIZED);
-    }
+    let claims = authenticate(&state.db, token).await?;
 
     // Add user information to request extensions
     request.extensions_mut().insert(claims);
@@ -72,8 +47,6 @@ pub async fn optional_auth_middleware(
     mut request: Request,
     next: Next,
 ) -> Result<Response, StatusCode> {
-    let auth_service = AuthService::new();
-    
     let auth_header = request
         .headers()
         .get(AUTHORIZATION)
@@ -86,27 +59,53 @@ pub async fn optional_auth_middleware(
             }
         });
 
-    if let Some(token) = auth_header {
-        if let Ok(claims) = auth_service.verify_token(token) {
-            // Verify session is still active in database
-            if let Ok(session_id) = Uuid::parse_str(&claims.jti) {
-                let session_exists = sqlx::query!(
-                    "SELECT id FROM user_sessions WHERE id = $1 AND is_active = true AND expires_at > NOW()",
-                    session_id
-                )
-                .fetch_optional(&state.db)
-                .await;
-
-                if let Ok(Some(_)) = session_exists {
-                    request.extensions_mut().insert(claims);
-                }
-            }
-        }
+    if let Some(token) = auth_header
+        && let Ok(claims) = authenticate(&state.db, token).await
+    {
+        request.extensions_mut().insert(claims);
     }
     
     Ok(next.run(request).await)
 }
 
+/// Claims of a token whose session is still active in the database
+pub async fn authenticate(db: &PgPool, token: &str) -> Result<Claims, StatusCode> {
+    let claims = match AuthService::new().verify_token(token) {
+        Ok(claims) => claims,
+        Err(AuthError::TokenExpired) => return Err(StatusCode::UNAUTHORIZED),
+        Err(AuthError::InvalidToken) => return Err(StatusCode::UNAUTHORIZED),
+        Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+    };
+
+    let session_id = match Uuid::parse_str(&claims.jti) {
+        Ok(id) => id,
+        Err(_) => return Err(StatusCode::UNAUTHORIZED),
+    };
+
+    let session_exists = sqlx::query!(
+        "SELECT id FROM user_sessions WHERE id = $1 AND is_active = true AND expires_at > NOW()",
+        session_id
+    )
+    .fetch_optional(db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if session_exists.is_none() {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    Ok(claims)
+}
+
+/// Claims of the `Authorization: Bearer` token of a request
+pub async fn authenticate_headers(db: &PgPool, headers: &HeaderMap) -> Result<Claims, StatusCode> {
+    let token = headers
+        .get(AUTHORIZATION)
+        .and_then(|header| header.to_str().ok())
+        .and_then(|header| header.strip_prefix("Bearer "))
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    authenticate(db, token).await
+}
+
 /// Extract user claims from request extensions
 pub fn get_current_user(request: &Request) -> Option<&Claims> {
     request.extensions().get::<Claims>()
@@ -10,9 +10,11 @@ use uuid::Uuid;
 
 pub mod middleware;
 pub mod service;
+pub mod ticket;
 
 pub use middleware::*;
 pub use service::*;
+pub use ticket::*;
 
 /// JWT Claims structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -0,0 +1,56 @@
+//! Short-lived single-use tickets opening an event stream, for `EventSource` clients
+//! that cannot send an `Authorization` header
+
+use axum::http::StatusCode;
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::{AuthService, Claims};
+
+/// Seconds a ticket can be redeemed after it was issued
+pub const TICKET_LIFETIME_SECONDS: i64 = 30;
+
+/// Issue a ticket for the session of `claims`. Only its hash is stored.
+pub async fn issue_ticket(db: &PgPool, claims: &Claims) -> Result<String, StatusCode> {
+    let session_id = Uuid::parse_str(&claims.jti).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let ticket = AuthService::new().generate_random_token();
+
+    sqlx::query!("DELETE FROM event_tickets WHERE expires_at <= NOW()")
+        .execute(db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    sqlx::query!(
+        "INSERT INTO event_tickets (ticket_hash, session_id, user_id, expires_at) VALUES ($1, $2, $3, $4)",
+        ticket_hash(&ticket),
+        session_id,
+        user_id,
+        Utc::now() + Duration::seconds(TICKET_LIFETIME_SECONDS)
+    )
+    .execute(db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(ticket)
+}
+
+/// User a ticket was issued to. The ticket is consumed; it fails once used, expired, or once its session ended.
+pub async fn redeem_ticket(db: &PgPool, ticket: &str) -> Result<Uuid, StatusCode> {
+    let user_id = sqlx::query_scalar!(
+        r#"
+        DELETE FROM event_tickets t USING user_sessions s
+        WHERE t.ticket_hash = $1 AND t.expires_at > NOW()
+          AND s.id = t.session_id AND s.is_active = true AND s.expires_at > NOW()
+        RETURNING t.user_id
+        "#,
+        ticket_hash(ticket)
+    )
+    .fetch_optional(db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    user_id.ok_or(StatusCode::UNAUTHORIZED)
+}
+
+fn ticket_hash(ticket: &str) -> String {
+    blake3::hash(ticket.as_bytes()).to_hex().to_string()
+}
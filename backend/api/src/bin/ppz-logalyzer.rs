@@ -1,6 +1,7 @@
-use ppz_logalyzer_api::{db, routes, schema::SchemaManager};
+use ppz_logalyzer_api::{db, processing::{EventBus, FileProcessor, ProcessingConfig}, routes, schema::SchemaManager};
 use std::env;
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
 use tower_http::cors::CorsLayer;
 use axum::extract::DefaultBodyLimit;
@@ -49,12 +50,24 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     info!("Schema manager initialized");
 
+    // Background parsing of uploaded file pairs, with live progress events
+    let file_processor = Arc::new(FileProcessor::new(db_pool.clone(), ProcessingConfig::from_env()));
+    let events = Arc::new(EventBus::new(db_pool.clone()));
+    events.listen();
+
+    // Uploaded files, schema artifacts and columnar caches
+    let upload_dir = PathBuf::from(env::var("UPLOAD_DIR").unwrap_or_else(|_| "uploads".to_string()));
+    info!("Storing uploads in {:?}", upload_dir);
+
     // Create application state
     let app_state = Arc::new(routes::AppState { 
         db: db_pool.clone(),
         schema_manager,
-        // file_processor,
+        file_processor,
+        events,
+        upload_dir,
     });
+    routes::start_processing(&app_state);
 
     // Build our application with routes
     let app = routes::router()
@@ -4,9 +4,10 @@ pub mod analysis;
 pub mod auth;
 pub mod db;
 pub mod models;
-// pub mod processing; // File processing pipeline
+pub mod processing; // Background parsing of uploaded files
 pub mod routes;
 pub mod schema; // Schema detection and parsing
 pub mod telemetry; // placeholder for parsing, monitoring logic
+pub mod uploads; // Compressed uploads and archive bundles
 
 pub use routes::router;
@@ -0,0 +1,228 @@
+//! Live processing events: parser progress, status transitions, warnings and diagnostics.
+//!
+//! Events go through Postgres `NOTIFY`, so a client connected to any replica sees the
+//! progress of a file parsed by another one.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use sqlx::postgres::PgListener;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, watch};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::ProcessingStatus;
+use crate::schema::{DataProgress, Diagnostic};
+
+/// Postgres channel the events are published on
+const EVENT_CHANNEL: &str = "processing_events";
+
+/// Postgres rejects `NOTIFY` payloads of 8000 bytes or more; larger events stay on this replica
+const MAX_NOTIFY_PAYLOAD: usize = 7900;
+
+/// Events buffered per subscriber before it lags
+const EVENT_BUFFER: usize = 1024;
+
+/// Shortest time between two progress events of a file
+const PROGRESS_PERIOD: Duration = Duration::from_millis(500);
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Owner and files an event is about
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventScope {
+    /// Owner of the files; only they receive the event
+    pub user_id: Uuid,
+    /// .data file of the pair
+    pub file_id: Uuid,
+    pub file_pair_id: Option<Uuid>,
+    /// Processing job, unless the file was parsed on request
+    pub job_id: Option<Uuid>,
+}
+
+impl EventScope {
+    pub fn event(&self, kind: EventKind) -> ProcessingEvent {
+        ProcessingEvent {
+            scope: self.clone(),
+            timestamp: Utc::now(),
+            kind,
+        }
+    }
+
+    /// Pair key of the files, `COALESCE(file_pair_id, id)` in `log_files`
+    pub fn pair_key(&self) -> Uuid {
+        self.file_pair_id.unwrap_or(self.file_id)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventKind {
+    /// Position of the parser in the .data file
+    Progress {
+        bytes_read: u64,
+        total_bytes: Option<u64>,
+        fraction: Option<f64>,
+        messages_decoded: usize,
+        lines_skipped: usize,
+    },
+    Status {
+        status: ProcessingStatus,
+        /// Attempt of the job, starting at 1
+        attempt: Option<i32>,
+        error: Option<String>,
+    },
+    Warning { message: String },
+    Diagnostic { diagnostic: Diagnostic },
+}
+
+impl EventKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EventKind::Progress { .. } => "progress",
+            EventKind::Status { .. } => "status",
+            EventKind::Warning { .. } => "warning",
+            EventKind::Diagnostic { .. } => "diagnostic",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProcessingEvent {
+    #[serde(flatten)]
+    pub scope: EventScope,
+    pub timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+/// Fan-out of processing events to the clients connected to this replica
+pub struct EventBus {
+    db: PgPool,
+    sender: broadcast::Sender<ProcessingEvent>,
+}
+
+impl EventBus {
+    pub fn new(db: PgPool) -> Self {
+        Self {
+            db,
+            sender: broadcast::channel(EVENT_BUFFER).0,
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ProcessingEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Publish an event to every replica; it reaches local subscribers through `listen`
+    pub async fn publish(&self, event: ProcessingEvent) {
+        let payload = match serde_json::to_string(&event) {
+            Ok(payload) => payload,
+            Err(e) => {
+                warn!("Failed to serialize processing event: {}", e);
+                return;
+            }
+        };
+        if payload.len() >= MAX_NOTIFY_PAYLOAD {
+            let _ = self.sender.send(event);
+            return;
+        }
+        if let Err(e) = sqlx::query!("SELECT pg_notify($1, $2)", EVENT_CHANNEL, payload).execute(&self.db).await {
+            warn!("Failed to publish processing event: {}", e);
+            let _ = self.sender.send(event);
+        }
+    }
+
+    /// Forward the events published by every replica to the subscribers of this one
+    pub fn listen(self: &Arc<Self>) {
+        let bus = self.clone();
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = bus.forward_notifications().await {
+                    warn!("Processing event listener stopped: {}", e);
+                }
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        });
+    }
+
+    async fn forward_notifications(&self) -> Result<(), sqlx::Error> {
+        let mut listener = PgListener::connect_with(&self.db).await?;
+        listener.listen(EVENT_CHANNEL).await?;
+        info!("Listening for processing events");
+        loop {
+            let notification = listener.recv().await?;
+            match serde_json::from_str::<ProcessingEvent>(notification.payload()) {
+                Ok(event) => {
+                    let _ = self.sender.send(event);
+                }
+                Err(e) => warn!("Ignoring malformed processing event: {}", e),
+            }
+        }
+    }
+
+    /// Publish the progress and new diagnostics of a .data reader until it finishes
+    pub fn forward_progress(self: &Arc<Self>, scope: EventScope, mut progress: watch::Receiver<DataProgress>) {
+        let bus = self.clone();
+        tokio::spawn(async move {
+            let mut diagnostics_sent = 0;
+            let mut last_progress: Option<Instant> = None;
+            while progress.changed().await.is_ok() {
+                let (kind, diagnostics) = {
+                    let current = progress.borrow_and_update();
+                    let due = current.finished || last_progress.is_none_or(|last| last.elapsed() >= PROGRESS_PERIOD);
+                    let kind = due.then(|| EventKind::Progress {
+                        bytes_read: current.bytes_read,
+                        total_bytes: current.total_bytes,
+                        fraction: current.fraction(),
+                        messages_decoded: current.messages_decoded,
+                        lines_skipped: current.lines_skipped,
+                    });
+                    let entries = &current.diagnostics.entries;
+                    let diagnostics = entries.get(diagnostics_sent..).unwrap_or_default().to_vec();
+                    diagnostics_sent = entries.len();
+                    (kind, diagnostics)
+                };
+                for diagnostic in diagnostics {
+                    bus.publish(scope.event(EventKind::Diagnostic { diagnostic })).await;
+                }
+                if let Some(kind) = kind {
+                    last_progress = Some(Instant::now());
+                    bus.publish(scope.event(kind)).await;
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_format() {
+        let scope = EventScope {
+            user_id: Uuid::nil(),
+            file_id: Uuid::from_u128(1),
+            file_pair_id: None,
+            job_id: Some(Uuid::from_u128(2)),
+        };
+        let event = scope.event(EventKind::Progress {
+            bytes_read: 512,
+            total_bytes: Some(1024),
+            fraction: Some(0.5),
+            messages_decoded: 10,
+            lines_skipped: 1,
+        });
+
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "progress");
+        assert_eq!(json["fraction"], 0.5);
+        assert_eq!(json["file_id"], Uuid::from_u128(1).to_string());
+        assert_eq!(serde_json::from_value::<ProcessingEvent>(json).unwrap(), event);
+        assert_eq!(event.kind.name(), "progress");
+        assert_eq!(scope.pair_key(), scope.file_id);
+    }
+}
@@ -0,0 +1,299 @@
+//! Bulk ingestion of decoded telemetry into `parsed_data` with `COPY`
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{PgConnection, PgPool, Postgres, Transaction};
+use std::fmt::Write;
+use std::time::Instant;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::schema::{TelemetryMessage, TimeAnchor};
+
+/// Rows sent in one `COPY` statement
+pub const INGEST_BATCH_ROWS: usize = 10_000;
+
+const COPY_PARSED_DATA: &str =
+    "COPY parsed_data (file_id, data_type, timestamp_field, raw_data, indexed_fields, sequence_number) FROM STDIN";
+
+/// Origin of the `timestamp_field` of ingested rows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimestampBase {
+    /// The UTC start of the log given by its time anchor
+    #[default]
+    Anchor,
+    /// No UTC start is known: the unix epoch, 1970-01-01T00:00:00Z, stands for the start of the log,
+    /// so that `timestamp_field` holds the time elapsed since it
+    LogStart,
+}
+
+impl TimestampBase {
+    fn of(start: Option<f64>) -> Self {
+        match start {
+            Some(_) => Self::Anchor,
+            None => Self::LogStart,
+        }
+    }
+}
+
+/// Outcome of an ingestion, stored under `ingestion` in `log_files.metadata`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IngestionStats {
+    pub rows: u64,
+    pub batches: u32,
+    /// Rows of a previous ingestion of the file that were replaced
+    pub replaced_rows: u64,
+    pub duration_ms: u64,
+    pub rows_per_second: f64,
+    pub ingested_at: DateTime<Utc>,
+    #[serde(default)]
+    pub timestamp_base: TimestampBase,
+}
+
+/// Ingestion of the decoded messages of a .data file into `parsed_data`, replacing the ones of a previous run.
+///
+/// Messages are fed one at a time as the file is decoded and copied with the UTC start known when
+/// the ingestion begins, if any. Everything happens in one transaction, so a failed ingestion leaves
+/// the previous rows in place, and concurrent ingestions of the same file wait for each other on an
+/// advisory lock rather than on the row of the file.
+pub struct Ingestion {
+    tx: Transaction<'static, Postgres>,
+    file_id: Uuid,
+    start: Option<f64>,
+    file_pair_id: Option<Uuid>,
+    batch: String,
+    batch_rows: usize,
+    rows: u64,
+    batches: u32,
+    replaced_rows: u64,
+    started: Instant,
+}
+
+impl Ingestion {
+    pub async fn begin(pool: &PgPool, file_id: Uuid, file_pair_id: Option<Uuid>, start: Option<f64>) -> Result<Self> {
+        let mut tx = pool.begin().await?;
+        sqlx::query!("SELECT pg_advisory_xact_lock(hashtextextended($1::text, 0))", file_id.to_string())
+            .fetch_one(&mut *tx).await?;
+        let replaced_rows = sqlx::query!("DELETE FROM parsed_data WHERE file_id = $1", file_id)
+            .execute(&mut *tx).await?
+            .rows_affected();
+        Ok(Self {
+            tx,
+            file_id,
+            start,
+            replaced_rows,
+            file_pair_id,
+            batch: String::new(),
+            batch_rows: 0,
+            rows: 0,
+            batches: 0,
+            started: Instant::now(),
+        })
+    }
+
+    pub async fn add(&mut self, message: &TelemetryMessage) -> Result<()> {
+        write_copy_row(&mut self.batch, self.file_id, self.start, self.rows as i64, message);
+        self.rows += 1;
+        self.batch_rows += 1;
+        if self.batch_rows == INGEST_BATCH_ROWS {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        if self.batch_rows > 0 {
+            copy_batch(&mut self.tx, &self.batch).await?;
+            self.batches += 1;
+            self.batch.clear();
+            self.batch_rows = 0;
+        }
+        Ok(())
+    }
+
+    /// Commit the rows, rebasing their timestamps once if the anchor found by the pass over the file
+    /// gives another UTC start than the one they were copied with
+    pub async fn finish(mut self, anchor: Option<&TimeAnchor>) -> Result<IngestionStats> {
+        self.flush().await?;
+        let start = anchor.and_then(|anchor| anchor.start);
+        if start != self.start && self.rows > 0 {
+            sqlx::query!(
+                "UPDATE parsed_data SET timestamp_field = to_timestamp($2 + (indexed_fields->>'timestamp')::float8)
+                 WHERE file_id = $1",
+                self.file_id,
+                start.unwrap_or(0.0)
+            ).execute(&mut *self.tx).await?;
+        }
+
+        let elapsed = self.started.elapsed();
+        let stats = IngestionStats {
+            rows: self.rows,
+            batches: self.batches,
+            replaced_rows: self.replaced_rows,
+            duration_ms: elapsed.as_millis() as u64,
+            rows_per_second: self.rows as f64 / elapsed.as_secs_f64().max(1e-3),
+            ingested_at: Utc::now(),
+            timestamp_base: TimestampBase::of(start),
+        };
+        sqlx::query!(
+            "UPDATE log_files SET metadata = COALESCE(metadata, '{}'::jsonb) || jsonb_build_object('ingestion', $1::jsonb)
+             WHERE id = $2 OR file_pair_id = $3",
+            serde_json::to_value(&stats)?,
+            self.file_id,
+            self.file_pair_id
+        ).execute(&mut *self.tx).await?;
+        self.tx.commit().await?;
+
+        info!("Ingested {} rows of {} in {} batch(es), {:?} ({:.0} rows/s)",
+            stats.rows, self.file_id, stats.batches, elapsed, stats.rows_per_second);
+        Ok(stats)
+    }
+}
+
+async fn copy_batch(connection: &mut PgConnection, rows: &str) -> Result<u64, sqlx::Error> {
+    let mut copy = connection.copy_in_raw(COPY_PARSED_DATA).await?;
+    copy.send(rows.as_bytes()).await?;
+    copy.finish().await
+}
+
+/// Append a message as a line of `COPY` text format: `data_type` is the message name,
+/// `timestamp_field` its timestamp counted from `start`, or from the unix epoch without one,
+/// `raw_data` its fields and `indexed_fields` its header
+fn write_copy_row(out: &mut String, file_id: Uuid, start: Option<f64>, sequence: i64, message: &TelemetryMessage) {
+    let fields: serde_json::Map<_, _> = message.fields.iter()
+        .map(|(name, value)| (name.clone(), value.to_json()))
+        .collect();
+    let header = json!({
+        "timestamp": message.timestamp,
+        "sender_id": message.sender_id,
+        "message_id": message.message_id,
+        "class": message.msg_class,
+    });
+
+    // Rounded like `TimeAnchor::utc`; `\N` is NULL for a time out of range
+    let micros = ((start.unwrap_or(0.0) + message.timestamp) * 1e6).round() as i64;
+    let time = DateTime::from_timestamp_micros(micros).map_or_else(|| "\\N".to_string(), |time| time.to_rfc3339());
+
+    let _ = write!(out, "{}\t", file_id);
+    write_copy_text(out, &message.message_name);
+    let _ = write!(out, "\t{}\t", time);
+    write_copy_text(out, &serde_json::Value::Object(fields).to_string());
+    out.push('\t');
+    write_copy_text(out, &header.to_string());
+    let _ = writeln!(out, "\t{}", sequence);
+}
+
+/// Escape a value for the `COPY` text format
+fn write_copy_text(out: &mut String, value: &str) {
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{FieldValue, MessageClass};
+
+    #[test]
+    fn test_copy_rows() {
+        let message = TelemetryMessage {
+            timestamp: 1.5,
+            sender_id: 7,
+            message_id: 12,
+            msg_class: MessageClass::Telemetry,
+            message_name: "INFO_MSG".to_string(),
+            fields: [
+                ("msg".to_string(), FieldValue::String("a\tb\\c".to_string())),
+                ("values".to_string(), FieldValue::Array(vec![FieldValue::Float(0.1), FieldValue::Int16(-3)])),
+            ].into(),
+        };
+        let file_id = Uuid::new_v4();
+        let mut rows = String::new();
+        write_copy_row(&mut rows, file_id, None, 0, &message);
+        write_copy_row(&mut rows, file_id, Some(1_752_068_334.0), 1, &message);
+
+        let lines: Vec<_> = rows.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let columns: Vec<_> = lines[0].split('\t').collect();
+        assert_eq!(columns.len(), 6);
+        assert_eq!(&columns[..3], [file_id.to_string().as_str(), "INFO_MSG", "1970-01-01T00:00:01.500+00:00"]);
+        let raw: serde_json::Value = serde_json::from_str(&columns[3].replace("\\\\", "\\")).unwrap();
+        assert_eq!(raw, json!({ "msg": "a\tb\\c", "values": [0.1, -3] }));
+        let header: serde_json::Value = serde_json::from_str(columns[4]).unwrap();
+        assert_eq!(header, json!({ "timestamp": 1.5, "sender_id": 7, "message_id": 12, "class": "telemetry" }));
+        assert_eq!(columns[5], "0");
+        let columns: Vec<_> = lines[1].split('\t').collect();
+        assert_eq!((columns[2], columns[5]), ("2025-07-09T13:38:55.500+00:00", "1"));
+    }
+
+    async fn create_file(db: &PgPool) -> Uuid {
+        sqlx::query_scalar!(
+            r#"
+            WITH owner AS (INSERT INTO users (username, email, password_hash) VALUES ('pilot', 'pilot@example.com', '') RETURNING id)
+            INSERT INTO log_files (user_id, original_filename, file_hash, file_size, storage_path)
+            SELECT id, 'flight.data', '', 0, '' FROM owner RETURNING id
+            "#
+        ).fetch_one(db).await.unwrap()
+    }
+
+    fn message(timestamp: f64) -> TelemetryMessage {
+        TelemetryMessage {
+            timestamp,
+            sender_id: 7,
+            message_id: 12,
+            msg_class: MessageClass::Telemetry,
+            message_name: "INFO_MSG".to_string(),
+            fields: [("msg".to_string(), FieldValue::String("a\tb\\c".to_string()))].into(),
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_ingest_with_anchor(db: PgPool) {
+        let file_id = create_file(&db).await;
+        let anchor = TimeAnchor::from_time_of_day(1_752_068_334.0);
+        // Rebased once the pass finds the anchor, then copied with the one already known
+        for (run, start) in [(0, None), (1, anchor.start)] {
+            let mut ingestion = Ingestion::begin(&db, file_id, None, start).await.unwrap();
+            for timestamp in [1.5, 2.25] {
+                ingestion.add(&message(timestamp)).await.unwrap();
+            }
+            let stats = ingestion.finish(Some(&anchor)).await.unwrap();
+            assert_eq!((stats.rows, stats.batches, stats.replaced_rows), (2, 1, run * 2));
+            assert_eq!(stats.timestamp_base, TimestampBase::Anchor);
+        }
+
+        let rows = sqlx::query!(
+            "SELECT timestamp_field, raw_data FROM parsed_data WHERE file_id = $1 ORDER BY sequence_number",
+            file_id
+        ).fetch_all(&db).await.unwrap();
+        let timestamps: Vec<_> = rows.iter().map(|row| row.timestamp_field).collect();
+        assert_eq!(timestamps, [anchor.utc(1.5), anchor.utc(2.25)]);
+        assert_eq!(rows[0].raw_data, json!({ "msg": "a\tb\\c" }));
+    }
+
+    #[sqlx::test]
+    async fn test_ingest_without_anchor(db: PgPool) {
+        let file_id = create_file(&db).await;
+        let mut ingestion = Ingestion::begin(&db, file_id, None, None).await.unwrap();
+        ingestion.add(&message(1.5)).await.unwrap();
+        let stats = ingestion.finish(Some(&TimeAnchor::from_local_start(Default::default()))).await.unwrap();
+        assert_eq!(stats.timestamp_base, TimestampBase::LogStart);
+
+        let timestamp = sqlx::query_scalar!("SELECT timestamp_field FROM parsed_data WHERE file_id = $1", file_id)
+            .fetch_one(&db).await.unwrap();
+        assert_eq!(timestamp, DateTime::from_timestamp_millis(1_500));
+        let stored = sqlx::query_scalar!("SELECT metadata->'ingestion' FROM log_files WHERE id = $1", file_id)
+            .fetch_one(&db).await.unwrap();
+        assert_eq!(stored.unwrap()["timestamp_base"], "log_start");
+    }
+}
@@ -1,560 +1,716 @@
-//! File processing pipeline for PaparazziUAV log files
+//! Background processing of uploaded log file pairs
 //!
-//! This module handles:
-//! - Processing uploaded log file pairs (.log/.data)
-//! - Schema-aware parsing using detected schemas
-//! - File validation and error recovery
-//! - Processing status tracking and notifications
+//! Each file pair is parsed after upload by a pool of workers draining the `processing_jobs` table;
+//! `log_files.processing_status` moves from `pending` to `processing`, then `completed` or `failed`
+//! with `processing_error` set.
 
-use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::path::{Path, PathBuf};
-use tokio::fs;
-use tracing::{debug, info, warn, error};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tracing::{error, info, warn};
 use uuid::Uuid;
-use chrono::{DateTime, Utc};
 
-use crate::schema::{SchemaManager, PaparazziSchema, SchemaDetectionResult};
+pub mod events;
+pub mod ingest;
 
-/// File processing pipeline manager
-#[derive(Debug)]
-pub struct FileProcessor {
-    schema_manager: SchemaManager,
-    processing_queue: Vec<ProcessingTask>,
-}
+pub use events::*;
+pub use ingest::*;
 
-/// Represents a file processing task
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ProcessingTask {
-    pub id: Uuid,
-    pub log_file_path: PathBuf,
-    pub data_file_path: Option<PathBuf>,
-    pub status: ProcessingStatus,
-    pub schema_hash: Option<String>,
-    pub created_at: DateTime<Utc>,
-    pub started_at: Option<DateTime<Utc>>,
-    pub completed_at: Option<DateTime<Utc>>,
-    pub error_message: Option<String>,
-    pub progress: f64,
-}
+/// Files parsed at the same time unless `PROCESSING_CONCURRENCY` says otherwise
+pub const DEFAULT_CONCURRENCY: usize = 5;
 
-/// Processing status for tracking
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+/// Processing state of a file, as stored in `log_files.processing_status`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ProcessingStatus {
-    Queued,
+    Pending,
     Processing,
     Completed,
     Failed,
-    SchemaError,
-    ValidationError,
 }
 
-/// Result of file processing
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ProcessingResult {
-    pub task_id: Uuid,
-    pub status: ProcessingStatus,
-    pub parsed_data: Option<ParsedLogData>,
-    pub schema_used: Option<String>, // Schema hash
-    pub warnings: Vec<String>,
-    pub errors: Vec<String>,
-    pub processing_time_ms: u64,
-    pub records_processed: usize,
+impl ProcessingStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProcessingStatus::Pending => "pending",
+            ProcessingStatus::Processing => "processing",
+            ProcessingStatus::Completed => "completed",
+            ProcessingStatus::Failed => "failed",
+        }
+    }
 }
 
-/// Parsed log data structure
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ParsedLogData {
-    pub metadata: LogFileMetadata,
-    pub messages: Vec<ParsedMessage>,
-    pub statistics: ProcessingStatistics,
+impl fmt::Display for ProcessingStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
-/// Metadata extracted from log files
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct LogFileMetadata {
-    pub aircraft_name: Option<String>,
-    pub aircraft_id: Option<u32>,
-    pub flight_time: Option<DateTime<Utc>>,
-    pub duration_seconds: Option<f64>,
-    pub paparazzi_version: Option<String>,
-    pub airframe_config: Option<String>,
-    pub flight_plan: Option<String>,
+impl FromStr for ProcessingStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "pending" => Ok(ProcessingStatus::Pending),
+            "processing" => Ok(ProcessingStatus::Processing),
+            "completed" => Ok(ProcessingStatus::Completed),
+            "failed" => Ok(ProcessingStatus::Failed),
+            other => Err(anyhow::anyhow!("Unknown processing status: {}", other)),
+        }
+    }
 }
 
-/// Individual parsed message
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ParsedMessage {
-    pub timestamp: f64,
-    pub message_id: u32,
-    pub message_name: String,
-    pub fields: std::collections::HashMap<String, FieldValue>,
-    pub raw_data: Option<Vec<u8>>,
+/// State of a job in `processing_jobs.status`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "varchar", rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    /// Failed `max_attempts` times; only retried on request
+    Dead,
+    Cancelled,
 }
 
-/// Field value with type information
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum FieldValue {
-    Uint8(u8),
-    Int8(i8),
-    Uint16(u16),
-    Int16(i16),
-    Uint32(u32),
-    Int32(i32),
-    Uint64(u64),
-    Int64(i64),
-    Float(f32),
-    Double(f64),
-    String(String),
-    Array(Vec<FieldValue>),
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Completed => "completed",
+            JobStatus::Dead => "dead",
+            JobStatus::Cancelled => "cancelled",
+        }
+    }
 }
 
-/// Processing statistics
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ProcessingStatistics {
-    pub total_messages: usize,
-    pub message_type_counts: std::collections::HashMap<String, usize>,
-    pub processing_time_ms: u64,
-    pub bytes_processed: u64,
-    pub parse_errors: usize,
-    pub warnings: usize,
+impl fmt::Display for JobStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
-impl FileProcessor {
-    /// Create a new file processor
-    pub fn new() -> Self {
-        Self {
-            schema_manager: SchemaManager::new(),
-            processing_queue: Vec::new(),
+impl FromStr for JobStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "queued" => Ok(JobStatus::Queued),
+            "running" => Ok(JobStatus::Running),
+            "completed" => Ok(JobStatus::Completed),
+            "dead" => Ok(JobStatus::Dead),
+            "cancelled" => Ok(JobStatus::Cancelled),
+            other => Err(anyhow::anyhow!("Unknown job status: {}", other)),
         }
     }
+}
 
-    /// Add a file pair to the processing queue
-    pub async fn queue_file_pair(
-        &mut self,
-        log_path: PathBuf,
-        data_path: Option<PathBuf>,
-    ) -> Result<Uuid> {
-        // Validate files exist
-        if !log_path.exists() {
-            return Err(anyhow!("Log file does not exist: {}", log_path.display()));
-        }
+/// Stage of the processing of a pair, all fed from one pass over its .data file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStage {
+    /// Statistics, diagnostics, schema and UTC anchor
+    Analysis,
+    /// Rows of `parsed_data`
+    Ingestion,
+    /// Columnar cache of the time series
+    Columnar,
+}
 
-        if let Some(ref data_path) = data_path {
-            if !data_path.exists() {
-                return Err(anyhow!("Data file does not exist: {}", data_path.display()));
-            }
+impl JobStage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStage::Analysis => "analysis",
+            JobStage::Ingestion => "ingestion",
+            JobStage::Columnar => "columnar",
         }
+    }
+}
 
-        let task_id = Uuid::new_v4();
-        let task = ProcessingTask {
-            id: task_id,
-            log_file_path: log_path.clone(),
-            data_file_path: data_path,
-            status: ProcessingStatus::Queued,
-            schema_hash: None,
-            created_at: Utc::now(),
-            started_at: None,
-            completed_at: None,
-            error_message: None,
-            progress: 0.0,
-        };
-
-        self.processing_queue.push(task);
-        info!("Queued file processing task: {} for file: {}", 
-              task_id, log_path.display());
+/// A row of `processing_jobs`
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessingJob {
+    pub id: Uuid,
+    /// File the job was queued for, either half of its pair
+    pub file_id: Uuid,
+    pub file_pair_id: Option<Uuid>,
+    pub status: JobStatus,
+    pub priority: i32,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub run_at: DateTime<Utc>,
+    pub locked_by: Option<String>,
+    pub locked_until: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+    /// Stages that succeeded in an earlier attempt, skipped by a retry
+    pub stages_done: Vec<String>,
+}
 
-        Ok(task_id)
+impl ProcessingJob {
+    pub fn stage_done(&self, stage: JobStage) -> bool {
+        self.stages_done.iter().any(|done| done == stage.as_str())
     }
 
-    /// Process the next file in the queue
-    pub async fn process_next(&mut self) -> Result<Option<ProcessingResult>> {
-        let task_index = self.processing_queue.iter()
-            .position(|t| t.status == ProcessingStatus::Queued);
-
-        let task_index = match task_index {
-            Some(idx) => idx,
-            None => return Ok(None), // No queued tasks
-        };
+    /// Whether a failure of the current attempt dead-letters the job
+    pub fn is_last_attempt(&self) -> bool {
+        self.attempts >= self.max_attempts
+    }
+}
 
-        // Update task status
-        self.processing_queue[task_index].status = ProcessingStatus::Processing;
-        self.processing_queue[task_index].started_at = Some(Utc::now());
-
-        let task = self.processing_queue[task_index].clone();
-        let result = self.process_file_task(&task).await;
-
-        // Update task with result
-        match &result {
-            Ok(processing_result) => {
-                self.processing_queue[task_index].status = processing_result.status.clone();
-                self.processing_queue[task_index].completed_at = Some(Utc::now());
-                if processing_result.status == ProcessingStatus::Failed {
-                    self.processing_queue[task_index].error_message = 
-                        Some(processing_result.errors.join("; "));
-                }
-            }
-            Err(e) => {
-                self.processing_queue[task_index].status = ProcessingStatus::Failed;
-                self.processing_queue[task_index].error_message = Some(e.to_string());
-                self.processing_queue[task_index].completed_at = Some(Utc::now());
-            }
-        }
+/// Error of an attempt that would fail the same way on every retry; the job is dead-lettered at once
+#[derive(Debug)]
+pub struct Unrecoverable(pub String);
 
-        result.map(Some)
+impl fmt::Display for Unrecoverable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
     }
+}
 
-    /// Process a specific file task
-    async fn process_file_task(&mut self, task: &ProcessingTask) -> Result<ProcessingResult> {
-        let start_time = std::time::Instant::now();
-        info!("Processing file task: {}", task.id);
-
-        // Step 1: Detect schema
-        let schema_result = self.schema_manager
-            .detect_schema_from_log(&task.log_file_path).await?;
+impl std::error::Error for Unrecoverable {}
 
-        let schema = match schema_result.schema {
-            Some(schema) => schema,
-            None => {
-                warn!("No schema detected for file: {}", task.log_file_path.display());
-                return Ok(ProcessingResult {
-                    task_id: task.id,
-                    status: ProcessingStatus::SchemaError,
-                    parsed_data: None,
-                    schema_used: None,
-                    warnings: schema_result.warnings,
-                    errors: vec!["No schema could be detected or loaded".to_string()],
-                    processing_time_ms: start_time.elapsed().as_millis() as u64,
-                    records_processed: 0,
-                });
-            }
-        };
+/// Worker pool settings
+#[derive(Debug, Clone)]
+pub struct ProcessingConfig {
+    /// Files parsed at the same time by this replica
+    pub concurrency: usize,
+    /// Attempts before a job is dead-lettered
+    pub max_attempts: i32,
+    /// How long a claimed job stays locked without a heartbeat
+    pub lease: Duration,
+    /// Heartbeat period, which is also how fast a cancellation stops a running job
+    pub heartbeat: Duration,
+    /// Delay before looking for jobs again when the queue is empty
+    pub poll_interval: Duration,
+    /// Delay before the first retry, doubled at each further attempt
+    pub retry_delay: Duration,
+}
 
-        // Step 2: Parse log file using detected schema
-        let parsed_data = match self.parse_log_file(&task.log_file_path, &schema).await {
-            Ok(data) => data,
-            Err(e) => {
-                error!("Failed to parse log file: {}", e);
-                return Ok(ProcessingResult {
-                    task_id: task.id,
-                    status: ProcessingStatus::Failed,
-                    parsed_data: None,
-                    schema_used: Some(self.schema_manager.get_schema_hash(&schema)),
-                    warnings: schema_result.warnings,
-                    errors: vec![format!("Parse error: {}", e)],
-                    processing_time_ms: start_time.elapsed().as_millis() as u64,
-                    records_processed: 0,
-                });
-            }
-        };
+impl Default for ProcessingConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: DEFAULT_CONCURRENCY,
+            max_attempts: 5,
+            lease: Duration::from_secs(120),
+            heartbeat: Duration::from_secs(10),
+            poll_interval: Duration::from_secs(2),
+            retry_delay: Duration::from_secs(30),
+        }
+    }
+}
 
-        // Step 3: Validate parsed data
-        let validation_result = self.validate_parsed_data(&parsed_data, &schema);
+impl ProcessingConfig {
+    /// Settings from `PROCESSING_CONCURRENCY` and `PROCESSING_MAX_ATTEMPTS`, defaults otherwise
+    pub fn from_env() -> Self {
+        let positive = |name: &str| std::env::var(name).ok()
+            .and_then(|value| value.parse::<usize>().ok())
+            .filter(|&value| value > 0);
+        let defaults = Self::default();
+        Self {
+            concurrency: positive("PROCESSING_CONCURRENCY").unwrap_or(defaults.concurrency),
+            max_attempts: positive("PROCESSING_MAX_ATTEMPTS").map(|n| n as i32).unwrap_or(defaults.max_attempts),
+            ..defaults
+        }
+    }
 
-        let final_status = if validation_result.is_empty() {
-            ProcessingStatus::Completed
-        } else {
-            ProcessingStatus::ValidationError
-        };
+    /// Delay before the retry that follows attempt number `attempts`
+    pub fn backoff(&self, attempts: i32) -> Duration {
+        let exponent = attempts.saturating_sub(1).clamp(0, 16) as u32;
+        (self.retry_delay * 2u32.pow(exponent)).min(MAX_RETRY_DELAY)
+    }
+}
 
-        let processing_time = start_time.elapsed().as_millis() as u64;
-
-        Ok(ProcessingResult {
-            task_id: task.id,
-            status: final_status,
-            parsed_data: Some(parsed_data.clone()),
-            schema_used: Some(self.schema_manager.get_schema_hash(&schema)),
-            warnings: [schema_result.warnings, validation_result].concat(),
-            errors: vec![],
-            processing_time_ms: processing_time,
-            records_processed: parsed_data.messages.len(),
-        })
-    }
-
-    /// Parse log file using the provided schema
-    async fn parse_log_file(
-        &self,
-        log_path: &Path,
-        schema: &PaparazziSchema,
-    ) -> Result<ParsedLogData> {
-        info!("Parsing log file: {} with schema", log_path.display());
-
-        // Read log file content
-        let content = fs::read_to_string(log_path).await
-            .map_err(|e| anyhow!("Cannot read log file: {}", e))?;
-
-        let mut messages = Vec::new();
-        let mut metadata = LogFileMetadata {
-            aircraft_name: schema.aircraft.as_ref().map(|a| a.name.clone()),
-            aircraft_id: schema.aircraft.as_ref().map(|a| a.ac_id),
-            flight_time: None,
-            duration_seconds: None,
-            paparazzi_version: None,
-            airframe_config: None,
-            flight_plan: None,
-        };
+/// Longest delay between two attempts of a job
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(3600);
 
-        let mut line_count = 0;
-        let mut parse_errors = 0;
-        let mut message_type_counts = std::collections::HashMap::new();
-
-        // Parse each line of the log file
-        for (line_no, line) in content.lines().enumerate() {
-            line_count += 1;
-            
-            // Skip empty lines and comments
-            if line.trim().is_empty() || line.starts_with('#') {
-                continue;
-            }
+/// Workers draining the `processing_jobs` queue. Jobs are leased with `FOR UPDATE SKIP LOCKED`,
+/// so any number of replicas can share the queue.
+pub struct FileProcessor {
+    db: PgPool,
+    config: ProcessingConfig,
+    /// Identifies this replica in `processing_jobs.locked_by`
+    worker_id: String,
+    /// Wakes idle workers when this replica queues a job
+    notify: Notify,
+    started: AtomicBool,
+}
 
-            // Try to parse the line as a message
-            match self.parse_log_line(line, schema) {
-                Ok(Some(message)) => {
-                    *message_type_counts.entry(message.message_name.clone())
-                        .or_insert(0) += 1;
-                    messages.push(message);
-                }
-                Ok(None) => {
-                    // Line was parsed but didn't contain a message (header info)
-                    if let Some(meta_update) = self.extract_metadata_from_line(line) {
-                        self.update_metadata(&mut metadata, meta_update);
-                    }
-                }
-                Err(e) => {
-                    parse_errors += 1;
-                    debug!("Parse error on line {}: {}", line_no + 1, e);
-                }
-            }
+impl FileProcessor {
+    pub fn new(db: PgPool, config: ProcessingConfig) -> Self {
+        let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "backend".to_string());
+        Self {
+            db,
+            config,
+            worker_id: format!("{}-{}-{}", host, std::process::id(), &Uuid::new_v4().simple().to_string()[..8]),
+            notify: Notify::new(),
+            started: AtomicBool::new(false),
         }
+    }
+
+    pub fn concurrency(&self) -> usize {
+        self.config.concurrency
+    }
 
-        // Calculate duration from first and last message timestamps
-        if let (Some(first), Some(last)) = (messages.first(), messages.last()) {
-            metadata.duration_seconds = Some(last.timestamp - first.timestamp);
+    /// Queue the pair of a file for parsing. A pair already queued keeps its job, raised to `priority`,
+    /// with all its stages to run again.
+    ///
+    /// Returns the job, or `None` if the file does not exist.
+    pub async fn enqueue(&self, file_id: Uuid, priority: i32) -> Result<Option<ProcessingJob>, sqlx::Error> {
+        let job = sqlx::query_as!(
+            ProcessingJob,
+            r#"
+            INSERT INTO processing_jobs (file_id, file_pair_id, priority, max_attempts)
+            SELECT id, file_pair_id, $2, $3 FROM log_files WHERE id = $1
+            ON CONFLICT ((COALESCE(file_pair_id, file_id))) WHERE status = 'queued'
+            DO UPDATE SET priority = GREATEST(processing_jobs.priority, EXCLUDED.priority), stages_done = '{}', updated_at = NOW()
+            RETURNING id, file_id, file_pair_id, status AS "status: JobStatus", priority, attempts, max_attempts, run_at,
+                locked_by, locked_until, last_error, created_at, updated_at, started_at, completed_at, stages_done
+            "#,
+            file_id,
+            priority,
+            self.config.max_attempts
+        ).fetch_optional(&self.db).await?;
+
+        if job.is_some() {
+            self.notify.notify_one();
         }
+        Ok(job)
+    }
 
-        let statistics = ProcessingStatistics {
-            total_messages: messages.len(),
-            message_type_counts,
-            processing_time_ms: 0, // Will be filled by caller
-            bytes_processed: content.len() as u64,
-            parse_errors,
-            warnings: 0, // Will be filled by validation
-        };
+    /// Lock a job while this worker still holds its lease. A cancellation waits for the returned
+    /// transaction, so writes made in it cannot overwrite a cancelled job's files.
+    ///
+    /// `None` once the job was cancelled or taken over by another worker.
+    pub async fn lock_lease(&self, job_id: Uuid) -> Result<Option<Transaction<'static, Postgres>>, sqlx::Error> {
+        let mut tx = self.db.begin().await?;
+        let held = sqlx::query_scalar!(
+            "SELECT id FROM processing_jobs WHERE id = $1 AND locked_by = $2 AND status = 'running' FOR UPDATE",
+            job_id,
+            self.worker_id
+        ).fetch_optional(&mut *tx).await?;
+        Ok(held.map(|_| tx))
+    }
 
-        Ok(ParsedLogData {
-            metadata,
-            messages,
-            statistics,
-        })
-    }
-
-    /// Parse a single log line into a message
-    fn parse_log_line(
-        &self,
-        line: &str,
-        schema: &PaparazziSchema,
-    ) -> Result<Option<ParsedMessage>> {
-        // Simplified parsing logic - real implementation would be more complex
-        // Expected format: timestamp message_id field1=value1 field2=value2 ...
-        
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() < 2 {
-            return Ok(None); // Not a message line
-        }
+    /// Queued or running job of the pair of a file
+    pub async fn active_job(&self, file_id: Uuid) -> Result<Option<ProcessingJob>, sqlx::Error> {
+        sqlx::query_as!(
+            ProcessingJob,
+            r#"
+            SELECT j.id, j.file_id, j.file_pair_id, j.status AS "status: JobStatus", j.priority, j.attempts, j.max_attempts,
+                j.run_at, j.locked_by, j.locked_until, j.last_error, j.created_at, j.updated_at, j.started_at,
+                j.completed_at, j.stages_done
+            FROM processing_jobs j
+            JOIN log_files f ON COALESCE(f.file_pair_id, f.id) = COALESCE(j.file_pair_id, j.file_id)
+            WHERE f.id = $1 AND j.status IN ('queued', 'running')
+            ORDER BY j.status = 'running' DESC, j.created_at DESC
+            LIMIT 1
+            "#,
+            file_id
+        ).fetch_optional(&self.db).await
+    }
 
-        // Parse timestamp
-        let timestamp = parts[0].parse::<f64>()
-            .map_err(|_| anyhow!("Invalid timestamp: {}", parts[0]))?;
-
-        // Parse message ID or name
-        let message_info = if let Ok(msg_id) = parts[1].parse::<u32>() {
-            // Message ID provided
-            schema.messages.values()
-                .find(|m| m.id == msg_id)
-                .ok_or_else(|| anyhow!("Unknown message ID: {}", msg_id))?
-        } else {
-            // Message name provided
-            schema.messages.get(parts[1])
-                .ok_or_else(|| anyhow!("Unknown message name: {}", parts[1]))?
-        };
+    /// Jobs of the files of `user_id`, most recently updated first
+    pub async fn list_jobs(&self, user_id: Uuid, status: Option<JobStatus>, limit: i64) -> Result<Vec<ProcessingJob>, sqlx::Error> {
+        sqlx::query_as!(
+            ProcessingJob,
+            r#"
+            SELECT j.id, j.file_id, j.file_pair_id, j.status AS "status: JobStatus", j.priority, j.attempts, j.max_attempts,
+                j.run_at, j.locked_by, j.locked_until, j.last_error, j.created_at, j.updated_at, j.started_at,
+                j.completed_at, j.stages_done
+            FROM processing_jobs j
+            JOIN log_files f ON f.id = j.file_id
+            WHERE f.user_id = $1 AND ($2::text IS NULL OR j.status = $2)
+            ORDER BY j.updated_at DESC
+            LIMIT $3
+            "#,
+            user_id,
+            status.map(|status| status.as_str()),
+            limit
+        ).fetch_all(&self.db).await
+    }
 
-        // Parse field values
-        let mut fields = std::collections::HashMap::new();
-        for part in parts.iter().skip(2) {
-            if let Some((field_name, value_str)) = part.split_once('=') {
-                if let Some(field_schema) = message_info.fields.iter()
-                    .find(|f| f.name == field_name) {
-                    
-                    let field_value = self.parse_field_value(value_str, &field_schema.r#type)?;
-                    fields.insert(field_name.to_string(), field_value);
-                }
-            }
-        }
+    /// Cancel a queued or running job of the files of `user_id`; a running one stops at its next heartbeat
+    pub async fn cancel(&self, job_id: Uuid, user_id: Uuid) -> Result<Option<ProcessingJob>, sqlx::Error> {
+        sqlx::query_as!(
+            ProcessingJob,
+            r#"
+            UPDATE processing_jobs SET status = 'cancelled', locked_by = NULL, locked_until = NULL,
+                completed_at = NOW(), updated_at = NOW()
+            WHERE id = $1 AND status IN ('queued', 'running')
+              AND file_id IN (SELECT id FROM log_files WHERE user_id = $2)
+            RETURNING id, file_id, file_pair_id, status AS "status: JobStatus", priority, attempts, max_attempts, run_at,
+                locked_by, locked_until, last_error, created_at, updated_at, started_at, completed_at, stages_done
+            "#,
+            job_id,
+            user_id
+        ).fetch_optional(&self.db).await
+    }
 
-        Ok(Some(ParsedMessage {
-            timestamp,
-            message_id: message_info.id,
-            message_name: message_info.name.clone(),
-            fields,
-            raw_data: None,
-        }))
-    }
-
-    /// Parse a field value according to its type
-    fn parse_field_value(
-        &self,
-        value_str: &str,
-        field_type: &crate::schema::FieldType,
-    ) -> Result<FieldValue> {
-        use crate::schema::FieldType;
-        
-        match field_type {
-            FieldType::Uint8 => Ok(FieldValue::Uint8(value_str.parse()?)),
-            FieldType::Int8 => Ok(FieldValue::Int8(value_str.parse()?)),
-            FieldType::Uint16 => Ok(FieldValue::Uint16(value_str.parse()?)),
-            FieldType::Int16 => Ok(FieldValue::Int16(value_str.parse()?)),
-            FieldType::Uint32 => Ok(FieldValue::Uint32(value_str.parse()?)),
-            FieldType::Int32 => Ok(FieldValue::Int32(value_str.parse()?)),
-            FieldType::Uint64 => Ok(FieldValue::Uint64(value_str.parse()?)),
-            FieldType::Int64 => Ok(FieldValue::Int64(value_str.parse()?)),
-            FieldType::Float => Ok(FieldValue::Float(value_str.parse()?)),
-            FieldType::Double => Ok(FieldValue::Double(value_str.parse()?)),
-            FieldType::String => Ok(FieldValue::String(value_str.to_string())),
-            FieldType::Array { element_type: _, size: _ } => {
-                // Simplified array parsing - split by commas
-                let elements: Result<Vec<_>> = value_str.split(',')
-                    .map(|s| self.parse_field_value(s.trim(), field_type))
-                    .collect();
-                Ok(FieldValue::Array(elements?))
-            }
+    /// Queue a dead or cancelled job of the files of `user_id` again with a fresh set of attempts
+    pub async fn retry(&self, job_id: Uuid, user_id: Uuid) -> Result<Option<ProcessingJob>, sqlx::Error> {
+        let job = sqlx::query_as!(
+            ProcessingJob,
+            r#"
+            UPDATE processing_jobs SET status = 'queued', attempts = 0, run_at = NOW(), completed_at = NULL, updated_at = NOW()
+            WHERE id = $1 AND status IN ('dead', 'cancelled')
+              AND file_id IN (SELECT id FROM log_files WHERE user_id = $2)
+              AND NOT EXISTS (
+                  SELECT 1 FROM processing_jobs q
+                  WHERE q.status = 'queued'
+                    AND COALESCE(q.file_pair_id, q.file_id) = COALESCE(processing_jobs.file_pair_id, processing_jobs.file_id)
+              )
+            RETURNING id, file_id, file_pair_id, status AS "status: JobStatus", priority, attempts, max_attempts, run_at,
+                locked_by, locked_until, last_error, created_at, updated_at, started_at, completed_at, stages_done
+            "#,
+            job_id,
+            user_id
+        ).fetch_optional(&self.db).await?;
+
+        if job.is_some() {
+            self.notify.notify_one();
         }
+        Ok(job)
     }
 
-    /// Extract metadata from a log line
-    fn extract_metadata_from_line(&self, line: &str) -> Option<MetadataUpdate> {
-        if line.contains("AIRCRAFT:") {
-            if let Some(name) = line.split("AIRCRAFT:").nth(1) {
-                return Some(MetadataUpdate::AircraftName(name.trim().to_string()));
-            }
+    /// Start `concurrency` workers running `work` on each claimed job. Only the first call starts workers.
+    ///
+    /// A job whose `work` fails is retried with exponential backoff, then dead-lettered;
+    /// at once when the error is [`Unrecoverable`].
+    pub fn start<F, Fut>(self: &Arc<Self>, work: F)
+    where
+        F: Fn(ProcessingJob) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        if self.started.swap(true, Ordering::SeqCst) {
+            warn!("File processor already started");
+            return;
         }
-        
-        if line.contains("VERSION:") {
-            if let Some(version) = line.split("VERSION:").nth(1) {
-                return Some(MetadataUpdate::Version(version.trim().to_string()));
-            }
+        info!("File processor {} started with {} worker(s)", self.worker_id, self.config.concurrency);
+
+        let work = Arc::new(work);
+        for _ in 0..self.config.concurrency {
+            let (processor, work) = (self.clone(), work.clone());
+            tokio::spawn(async move {
+                loop {
+                    match processor.claim().await {
+                        Ok(Some(job)) => processor.run(job, work.as_ref()).await,
+                        Ok(None) => {
+                            tokio::select! {
+                                _ = processor.notify.notified() => {}
+                                _ = tokio::time::sleep(processor.config.poll_interval) => {}
+                            }
+                        }
+                        Err(e) => {
+                            warn!("Failed to claim a processing job: {}", e);
+                            tokio::time::sleep(processor.config.poll_interval).await;
+                        }
+                    }
+                }
+            });
         }
-        
-        None
     }
 
-    /// Update metadata with extracted information
-    fn update_metadata(&self, metadata: &mut LogFileMetadata, update: MetadataUpdate) {
-        match update {
-            MetadataUpdate::AircraftName(name) => metadata.aircraft_name = Some(name),
-            MetadataUpdate::Version(version) => metadata.paparazzi_version = Some(version),
-            MetadataUpdate::FlightTime(time) => metadata.flight_time = Some(time),
+    /// Lease the next job due: highest priority first, skipping jobs leased by other workers
+    /// and pairs another worker is already parsing. An expired lease is taken over.
+    async fn claim(&self) -> Result<Option<ProcessingJob>, sqlx::Error> {
+        let job = sqlx::query_as!(
+            ProcessingJob,
+            r#"
+            UPDATE processing_jobs SET status = 'running', attempts = attempts + 1, locked_by = $1,
+                locked_until = NOW() + make_interval(secs => $2), started_at = NOW(), updated_at = NOW()
+            WHERE id = (
+                SELECT j.id FROM processing_jobs j
+                WHERE ((j.status = 'queued' AND j.run_at <= NOW()) OR (j.status = 'running' AND j.locked_until < NOW()))
+                  AND NOT EXISTS (
+                      SELECT 1 FROM processing_jobs r
+                      WHERE r.status = 'running' AND r.locked_until >= NOW() AND r.id <> j.id
+                        AND COALESCE(r.file_pair_id, r.file_id) = COALESCE(j.file_pair_id, j.file_id)
+                  )
+                ORDER BY j.priority DESC, j.run_at
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, file_id, file_pair_id, status AS "status: JobStatus", priority, attempts, max_attempts, run_at,
+                locked_by, locked_until, last_error, created_at, updated_at, started_at, completed_at, stages_done
+            "#,
+            self.worker_id,
+            self.config.lease.as_secs_f64()
+        ).fetch_optional(&self.db).await?;
+
+        match job {
+            // Taken over from a worker that died on its last attempt
+            Some(job) if job.attempts > job.max_attempts => {
+                self.fail(&job, "Worker stopped during the last attempt", false).await?;
+                Ok(None)
+            }
+            job => Ok(job),
         }
     }
 
-    /// Validate parsed data against schema
-    fn validate_parsed_data(
-        &self,
-        data: &ParsedLogData,
-        schema: &PaparazziSchema,
-    ) -> Vec<String> {
-        let mut warnings = Vec::new();
-
-        // Check if all message types in data are defined in schema
-        let schema_messages: std::collections::HashSet<_> = 
-            schema.messages.keys().collect();
-
-        for message in &data.messages {
-            if !schema_messages.contains(&message.message_name) {
-                warnings.push(format!("Unknown message type: {}", message.message_name));
+    async fn run<F, Fut>(&self, job: ProcessingJob, work: &F)
+    where
+        F: Fn(ProcessingJob) -> Fut,
+        Fut: Future<Output = anyhow::Result<()>>,
+    {
+        let job_id = job.id;
+        let heartbeat = async {
+            loop {
+                tokio::time::sleep(self.config.heartbeat).await;
+                match self.heartbeat(job_id).await {
+                    Ok(true) => {}
+                    Ok(false) => break,
+                    Err(e) => warn!("Failed to extend the lease of job {}: {}", job_id, e),
+                }
             }
-        }
+        };
 
-        // Check for missing required fields
-        for message in &data.messages {
-            if let Some(msg_schema) = schema.messages.get(&message.message_name) {
-                for field_schema in &msg_schema.fields {
-                    if !message.fields.contains_key(&field_schema.name) {
-                        warnings.push(format!(
-                            "Missing field '{}' in message '{}'",
-                            field_schema.name,
-                            message.message_name
-                        ));
-                    }
+        let outcome = tokio::select! {
+            result = work(job.clone()) => Some(result),
+            _ = heartbeat => None,
+        };
+        let recorded = match outcome {
+            Some(Ok(())) => self.complete(job_id).await,
+            Some(Err(e)) => self.fail(&job, &e.to_string(), e.is::<Unrecoverable>()).await.map(|status| {
+                if status == JobStatus::Dead {
+                    warn!("Job {} dead after {} attempt(s): {}", job_id, job.attempts, e);
                 }
+            }),
+            None => {
+                info!("Job {} was cancelled or lost its lease, stopped", job_id);
+                Ok(())
             }
+        };
+        if let Err(e) = recorded {
+            error!("Failed to record the outcome of job {}: {}", job_id, e);
         }
-
-        warnings
     }
 
-    /// Get processing queue status
-    pub fn get_queue_status(&self) -> Vec<ProcessingTask> {
-        self.processing_queue.clone()
+    /// Extend the lease of a running job; false once it was cancelled or taken over
+    async fn heartbeat(&self, job_id: Uuid) -> Result<bool, sqlx::Error> {
+        let extended = sqlx::query!(
+            r#"
+            UPDATE processing_jobs SET locked_until = NOW() + make_interval(secs => $3), updated_at = NOW()
+            WHERE id = $1 AND locked_by = $2 AND status = 'running'
+            "#,
+            job_id,
+            self.worker_id,
+            self.config.lease.as_secs_f64()
+        ).execute(&self.db).await?;
+        Ok(extended.rows_affected() > 0)
     }
 
-    /// Get task by ID
-    pub fn get_task(&self, task_id: &Uuid) -> Option<&ProcessingTask> {
-        self.processing_queue.iter().find(|t| t.id == *task_id)
+    /// Record a stage of a running job as done, so that a retry skips it
+    pub async fn complete_stage(&self, job_id: Uuid, stage: JobStage) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE processing_jobs SET stages_done = array_append(stages_done, $3), updated_at = NOW()
+            WHERE id = $1 AND locked_by = $2 AND status = 'running' AND NOT ($3 = ANY(stages_done))
+            "#,
+            job_id,
+            self.worker_id,
+            stage.as_str()
+        ).execute(&self.db).await?;
+        Ok(())
     }
 
-    /// Remove completed tasks older than specified duration
-    pub fn cleanup_completed_tasks(&mut self, max_age_hours: u64) {
-        let cutoff = Utc::now() - chrono::Duration::hours(max_age_hours as i64);
-        
-        self.processing_queue.retain(|task| {
-            if matches!(task.status, ProcessingStatus::Completed | ProcessingStatus::Failed) {
-                if let Some(completed_at) = task.completed_at {
-                    completed_at > cutoff
-                } else {
-                    true // Keep tasks without completion time
-                }
-            } else {
-                true // Keep non-completed tasks
-            }
-        });
+    async fn complete(&self, job_id: Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE processing_jobs SET status = 'completed', locked_by = NULL, locked_until = NULL, last_error = NULL,
+                completed_at = NOW(), updated_at = NOW()
+            WHERE id = $1 AND locked_by = $2 AND status = 'running'
+            "#,
+            job_id,
+            self.worker_id
+        ).execute(&self.db).await?;
+        Ok(())
     }
-}
 
-/// Metadata update types
-#[derive(Debug, Clone)]
-enum MetadataUpdate {
-    AircraftName(String),
-    Version(String),
-    FlightTime(DateTime<Utc>),
+    /// Schedule a retry of a failed job after its backoff, or dead-letter it after its last attempt
+    /// or when `unrecoverable`
+    async fn fail(&self, job: &ProcessingJob, error: &str, unrecoverable: bool) -> Result<JobStatus, sqlx::Error> {
+        let status = if unrecoverable || job.is_last_attempt() { JobStatus::Dead } else { JobStatus::Queued };
+        sqlx::query!(
+            r#"
+            UPDATE processing_jobs SET status = $3::varchar, last_error = $4, locked_by = NULL, locked_until = NULL,
+                run_at = NOW() + make_interval(secs => $5),
+                completed_at = CASE WHEN $3::varchar = 'dead' THEN NOW() END,
+                updated_at = NOW()
+            WHERE id = $1 AND locked_by = $2 AND status = 'running'
+            "#,
+            job.id,
+            self.worker_id,
+            status.as_str(),
+            error,
+            self.config.backoff(job.attempts).as_secs_f64()
+        ).execute(&self.db).await?;
+        Ok(status)
+    }
 }
 
-impl Default for FileProcessor {
-    fn default() -> Self {
-        Self::new()
-    }
+/// Move both files of a pair to `status`, recording the error of a failure
+pub async fn set_status(
+    executor: impl sqlx::PgExecutor<'_>,
+    data_file_id: Uuid,
+    file_pair_id: Option<Uuid>,
+    status: ProcessingStatus,
+    error: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE log_files SET
+            processing_status = $1::varchar,
+            processing_error = $2,
+            is_processed = ($1::varchar = 'completed'),
+            processing_started_at = CASE WHEN $1::varchar = 'processing' THEN NOW() ELSE processing_started_at END,
+            processing_completed_at = CASE WHEN $1::varchar IN ('completed', 'failed') THEN NOW() ELSE NULL END,
+            updated_at = NOW()
+        WHERE id = $3 OR file_pair_id = $4
+        "#,
+        status.as_str(),
+        error,
+        data_file_id,
+        file_pair_id
+    ).execute(executor).await?;
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[tokio::test]
-    async fn test_file_processor_creation() {
-        let processor = FileProcessor::new();
-        assert!(processor.processing_queue.is_empty());
+    #[test]
+    fn test_retry_backoff() {
+        let config = ProcessingConfig::default();
+        assert_eq!(config.concurrency, 5);
+        let delays: Vec<u64> = (1..=4).map(|attempts| config.backoff(attempts).as_secs()).collect();
+        assert_eq!(delays, [30, 60, 120, 240]);
+        assert_eq!(config.backoff(40), MAX_RETRY_DELAY);
+        assert_eq!("dead".parse::<JobStatus>().unwrap(), JobStatus::Dead);
     }
 
-    #[test]
-    fn test_field_value_parsing() {
-        let processor = FileProcessor::new();
-        
-        let result = processor.parse_field_value("42", &crate::schema::FieldType::Uint32);
-        assert!(result.is_ok());
-        
-        if let Ok(FieldValue::Uint32(value)) = result {
-            assert_eq!(value, 42);
-        } else {
-            panic!("Expected Uint32 value");
-        }
+    fn processor(db: &PgPool, lease: Duration) -> FileProcessor {
+        let config = ProcessingConfig { max_attempts: 2, lease, retry_delay: Duration::ZERO, ..Default::default() };
+        FileProcessor::new(db.clone(), config)
+    }
+
+    /// A queued job for a new file of its own pair
+    async fn queue_file(processor: &FileProcessor) -> ProcessingJob {
+        let name = Uuid::new_v4().simple().to_string();
+        let user_id = sqlx::query_scalar!(
+            "INSERT INTO users (username, email, password_hash) VALUES ($1, $2, '') RETURNING id",
+            &name[..20],
+            format!("{}@example.com", name)
+        ).fetch_one(&processor.db).await.unwrap();
+        let file_id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO log_files (user_id, original_filename, file_hash, file_size, storage_path, file_pair_id)
+            VALUES ($1, 'flight.data', '', 0, '', gen_random_uuid()) RETURNING id
+            "#,
+            user_id
+        ).fetch_one(&processor.db).await.unwrap();
+        processor.enqueue(file_id, 0).await.unwrap().unwrap()
+    }
+
+    async fn owner(db: &PgPool, job: &ProcessingJob) -> Uuid {
+        sqlx::query_scalar!("SELECT user_id FROM log_files WHERE id = $1", job.file_id)
+            .fetch_one(db).await.unwrap()
+    }
+
+    #[sqlx::test]
+    async fn test_claim_skips_locked_jobs(db: PgPool) {
+        let (a, b) = (processor(&db, Duration::from_secs(60)), processor(&db, Duration::from_secs(60)));
+        let (first, second) = (queue_file(&a).await, queue_file(&a).await);
+
+        let (claimed_a, claimed_b) = tokio::join!(a.claim(), b.claim());
+        let (claimed_a, claimed_b) = (claimed_a.unwrap().unwrap(), claimed_b.unwrap().unwrap());
+        assert_ne!(claimed_a.id, claimed_b.id);
+        let mut claimed = [claimed_a.id, claimed_b.id];
+        claimed.sort();
+        let mut queued = [first.id, second.id];
+        queued.sort();
+        assert_eq!(claimed, queued);
+        assert_eq!(claimed_a.locked_by.as_deref(), Some(a.worker_id.as_str()));
+        assert!(b.claim().await.unwrap().is_none());
+    }
+
+    #[sqlx::test]
+    async fn test_expired_lease_taken_over(db: PgPool) {
+        let (a, b) = (processor(&db, Duration::from_millis(200)), processor(&db, Duration::from_secs(60)));
+        let job = queue_file(&a).await;
+        assert_eq!(a.claim().await.unwrap().unwrap().id, job.id);
+        assert!(b.claim().await.unwrap().is_none());
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let taken = b.claim().await.unwrap().unwrap();
+        assert_eq!((taken.id, taken.attempts), (job.id, 2));
+        assert_eq!(taken.locked_by.as_deref(), Some(b.worker_id.as_str()));
+
+        // The first worker lost its lease and can no longer record anything
+        assert!(!a.heartbeat(job.id).await.unwrap());
+        assert!(a.lock_lease(job.id).await.unwrap().is_none());
+        a.complete(job.id).await.unwrap();
+        assert_eq!(b.list_jobs(owner(&db, &job).await, Some(JobStatus::Running), 10).await.unwrap()[0].id, job.id);
+    }
+
+    #[sqlx::test]
+    async fn test_dead_letter_after_max_attempts(db: PgPool) {
+        let worker = processor(&db, Duration::from_secs(60));
+        let job = queue_file(&worker).await;
+        let claimed = worker.claim().await.unwrap().unwrap();
+        worker.complete_stage(claimed.id, JobStage::Analysis).await.unwrap();
+        assert_eq!(worker.fail(&claimed, "first", false).await.unwrap(), JobStatus::Queued);
+        // The retry skips the stage that succeeded
+        let claimed = worker.claim().await.unwrap().unwrap();
+        assert!(claimed.is_last_attempt());
+        assert!(claimed.stage_done(JobStage::Analysis) && !claimed.stage_done(JobStage::Ingestion));
+        assert_eq!(worker.fail(&claimed, "second", false).await.unwrap(), JobStatus::Dead);
+        assert!(worker.claim().await.unwrap().is_none());
+        let dead = worker.list_jobs(owner(&db, &job).await, Some(JobStatus::Dead), 10).await.unwrap();
+        assert_eq!((dead[0].id, dead[0].last_error.as_deref()), (job.id, Some("second")));
+
+        // An unrecoverable error skips the remaining attempts
+        let job = queue_file(&worker).await;
+        let claimed = worker.claim().await.unwrap().unwrap();
+        assert_eq!(claimed.id, job.id);
+        assert_eq!(worker.fail(&claimed, "missing pair", true).await.unwrap(), JobStatus::Dead);
+    }
+
+    #[sqlx::test]
+    async fn test_cancel_and_retry(db: PgPool) {
+        let worker = processor(&db, Duration::from_secs(60));
+        let job = queue_file(&worker).await;
+        let other = queue_file(&worker).await;
+        let (user_id, other_user) = (owner(&db, &job).await, owner(&db, &other).await);
+        assert_eq!(worker.claim().await.unwrap().unwrap().id, job.id);
+        assert!(worker.retry(job.id, user_id).await.unwrap().is_none());
+
+        // Only the owner of the files sees and cancels the job
+        assert!(worker.list_jobs(other_user, None, 10).await.unwrap().iter().all(|other| other.id != job.id));
+        assert!(worker.cancel(job.id, other_user).await.unwrap().is_none());
+        let cancelled = worker.cancel(job.id, user_id).await.unwrap().unwrap();
+        assert_eq!(cancelled.status, JobStatus::Cancelled);
+        // The worker finishing afterwards leaves the job cancelled
+        assert!(worker.lock_lease(job.id).await.unwrap().is_none());
+        worker.complete(job.id).await.unwrap();
+        assert!(worker.cancel(job.id, user_id).await.unwrap().is_none());
+
+        assert!(worker.retry(job.id, other_user).await.unwrap().is_none());
+        assert!(worker.cancel(other.id, other_user).await.unwrap().is_some());
+        let retried = worker.retry(job.id, user_id).await.unwrap().unwrap();
+        assert_eq!((retried.status, retried.attempts), (JobStatus::Queued, 0));
+        assert_eq!(worker.claim().await.unwrap().unwrap().id, job.id);
     }
 }
@@ -1,39 +1,47 @@
 use axum::{
-    extract::{Multipart, State, Path, Request, ConnectInfo},
-    http::StatusCode,
-    response::Json,
+    extract::{Multipart, State, Path, Query, Request, ConnectInfo},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response, sse::{Event, KeepAlive, Sse}},
     routing::{get, post},
-    Router, body::to_bytes,
+    Router, body::{to_bytes, Body},
 };
-use chrono::{DateTime, Utc, NaiveDate};
+use chrono::{NaiveDate, NaiveDateTime, SecondsFormat};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
-use std::{path::Path as StdPath, sync::Arc, net::SocketAddr};
-use tokio::fs;
+use std::{collections::BTreeMap, path::{Path as StdPath, PathBuf}, sync::Arc, net::SocketAddr};
+use tokio::{fs, io::AsyncWriteExt};
+use tokio_stream::{Stream, StreamExt};
+use tokio_stream::wrappers::{BroadcastStream, errors::BroadcastStreamRecvError};
 use tracing::{error, info, warn};
 use uuid::Uuid;
 use ipnetwork::IpNetwork;
 
-use crate::auth::{AuthError, UserService, get_current_user};
+use crate::auth::{AuthError, TICKET_LIFETIME_SECONDS, UserService, authenticate_headers, get_current_user, issue_ticket, redeem_ticket};
 use crate::analysis::{AnalysisService, AnalysisError};
 use crate::models::{CreateUserRequest, LoginRequest, UserResponse, SessionResponse};
 use crate::models::analysis::{CreateAnalysisSessionRequest, UpdateAnalysisSessionRequest, AnalysisSessionResponse, CreateTemplateRequest, TemplateResponse};
-use crate::schema::{SchemaManager};
-// use crate::processing::{FileProcessor, ProcessingResult, ProcessingStatus};
+use crate::schema::{AircraftInfo, ArtifactAttachment, ArtifactKind, AircraftStatistics, AirframeConfig, AliveMd5s, BuildProvenance, ChannelRef, ChannelSamples, ColumnarCache, ColumnarWriterTask, ConfigCheck, ConfigCheckStatus, DataFormat, DataProgress, Diagnostic, DiagnosticKind, Diagnostics, DictionaryMismatches, DictionarySource, FlightPlan, LogConfiguration, LogStatistics, MessageDefinition, MessageDictionary, PaparazziVersion, ProtocolDiff, SchemaManager, SettingChange, SettingChangeTracker, TelemetryMessage, TelemetryStream, TimeAnchor, TimeAnchorBuilder, TimeSource, UnitSystem, bundled_dictionary, default_dictionary};
+use crate::uploads::{DATA_EXTENSIONS, UploadError, expand_upload};
+use crate::processing::{EventBus, EventKind, EventScope, FileProcessor, Ingestion, IngestionStats, JobStage, JobStatus, ProcessingJob, ProcessingStatus, Unrecoverable, set_status};
 
 // App state
 #[derive(Clone)]
 pub struct AppState {
     pub db: PgPool,
     pub schema_manager: Arc<tokio::sync::Mutex<SchemaManager>>,
-    // pub file_processor: Arc<tokio::sync::Mutex<FileProcessor>>,
+    pub file_processor: Arc<FileProcessor>,
+    pub events: Arc<EventBus>,
+    /// Directory uploads, schema artifacts and columnar caches are stored in
+    pub upload_dir: PathBuf,
 }
 
 // Helper functions for file pairing and timestamp extraction
 
 /// Extract timestamp from PaparazziUAV filename format: DD_MM_YY__HH_MM_SS.ext
-fn extract_timestamp_from_filename(filename: &str) -> Option<DateTime<Utc>> {
+///
+/// The ground station names logs with its local time, the UTC offset is unknown.
+fn extract_timestamp_from_filename(filename: &str) -> Option<NaiveDateTime> {
     let re = Regex::new(r"(\d{2})_(\d{2})_(\d{2})__(\d{2})_(\d{2})_(\d{2})").ok()?;
     let caps = re.captures(filename)?;
     
@@ -45,9 +53,7 @@ fn extract_timestamp_from_filename(filename: &str) -> Option<DateTime<Utc>> {
     let second: u32 = caps.get(6)?.as_str().parse().ok()?;
     
     NaiveDate::from_ymd_opt(year, month, day)?
-        .and_hms_opt(hour, minute, second)?
-        .and_utc()
-        .into()
+        .and_hms_opt(hour, minute, second)
 }
 
 /// Parse filename to extract base name and extension
@@ -67,15 +73,17 @@ fn parse_filename(filename: &str) -> (String, String) {
 
 /// Get or create a file pair ID for files with the same base filename
 async fn get_or_create_pair_id(
-    pool: &PgPool,
+    executor: impl sqlx::PgExecutor<'_>,
+    user_id: Uuid,
     base_filename: &str,
 ) -> Result<Uuid, sqlx::Error> {
-    // First try to find existing pair
+    // First try to find an existing pair among the files of the same owner
     if let Ok(record) = sqlx::query!(
-        "SELECT file_pair_id FROM log_files WHERE base_filename = $1 LIMIT 1",
-        base_filename
+        "SELECT file_pair_id FROM log_files WHERE base_filename = $1 AND user_id = $2 LIMIT 1",
+        base_filename,
+        user_id
     )
-    .fetch_one(pool)
+    .fetch_one(executor)
     .await
     {
         return Ok(record.file_pair_id.unwrap_or_else(Uuid::new_v4));
@@ -85,6 +93,395 @@ async fn get_or_create_pair_id(
     Ok(Uuid::new_v4())
 }
 
+/// Storage paths of a .log/.data file pair
+struct FilePairPaths {
+    /// Owner of the files
+    user_id: Uuid,
+    file_pair_id: Option<Uuid>,
+    /// log_files row of the .data half
+    data_file_id: Uuid,
+    original_filename: String,
+    /// `None` when only the .data file was uploaded
+    log_path: Option<PathBuf>,
+    data_path: PathBuf,
+}
+
+impl FilePairPaths {
+    /// Columnar cache of the pair, kept under the id of its .data file and built by the processing job
+    fn columnar_cache(&self, upload_dir: &StdPath) -> ColumnarCache {
+        ColumnarCache::new(upload_dir.join("columnar").join(self.data_file_id.to_string()))
+    }
+
+    fn event_scope(&self, job_id: Option<Uuid>) -> EventScope {
+        EventScope {
+            user_id: self.user_id,
+            file_id: self.data_file_id,
+            file_pair_id: self.file_pair_id,
+            job_id,
+        }
+    }
+}
+
+/// Why a file pair could not be resolved
+enum FilePairError {
+    FileNotFound,
+    MissingPair(String),
+    Database(sqlx::Error),
+}
+
+impl FilePairError {
+    fn message(&self) -> String {
+        match self {
+            FilePairError::FileNotFound => "File not found".to_string(),
+            FilePairError::MissingPair(extension) => format!("Matching .{} file not found for pair", extension),
+            FilePairError::Database(e) => format!("Database error: {}", e),
+        }
+    }
+}
+
+/// Resolve the .log and .data storage paths for either half of a file pair.
+///
+/// A .data file without its .log resolves; it is decoded with the bundled dictionary.
+/// The data half may also be a binary pprzlink capture (.tlm), detected when it is read.
+async fn resolve_file_pair(pool: &PgPool, file_id: Uuid) -> Result<FilePairPaths, FilePairError> {
+    let file_info = sqlx::query!(
+        "SELECT user_id, storage_path, original_filename, file_pair_id, file_extension FROM log_files WHERE id = $1",
+        file_id
+    ).fetch_optional(pool).await
+     .map_err(FilePairError::Database)?
+     .ok_or(FilePairError::FileNotFound)?;
+
+    // Find the matching file with the same file_pair_id: any telemetry format for a .log, the .log otherwise
+    let is_log = file_info.file_extension.as_deref() == Some("log");
+    let target_extensions: &[&str] = if is_log { &DATA_EXTENSIONS } else { &["log"] };
+    let paired_file_info = sqlx::query!(
+        "SELECT id, storage_path FROM log_files WHERE file_pair_id = $1 AND file_extension = ANY($2) AND id != $3
+         ORDER BY array_position($2, file_extension::text) LIMIT 1",
+        file_info.file_pair_id,
+        target_extensions as &[&str],
+        file_id
+    ).fetch_optional(pool).await
+     .map_err(FilePairError::Database)?;
+
+    let storage_path = PathBuf::from(file_info.storage_path);
+    let paired = paired_file_info.map(|paired| (paired.id, PathBuf::from(paired.storage_path)));
+    let (log_path, data_file_id, data_path) = if is_log {
+        let (data_file_id, data_path) = paired.ok_or_else(|| FilePairError::MissingPair(target_extensions.join(" or .")))?;
+        (Some(storage_path), data_file_id, data_path)
+    } else {
+        (paired.map(|(_, log_path)| log_path), file_id, storage_path)
+    };
+
+    Ok(FilePairPaths {
+        user_id: file_info.user_id,
+        file_pair_id: file_info.file_pair_id,
+        data_file_id,
+        original_filename: file_info.original_filename,
+        log_path,
+        data_path,
+    })
+}
+
+/// Resolve the file pair of a file owned by `user_id`; the files of other users are not found
+async fn resolve_owned_file_pair(pool: &PgPool, file_id: Uuid, user_id: Uuid) -> Result<FilePairPaths, FilePairError> {
+    let paths = resolve_file_pair(pool, file_id).await?;
+    if paths.user_id != user_id {
+        return Err(FilePairError::FileNotFound);
+    }
+    Ok(paths)
+}
+
+/// A file to record in log_files, uploaded as is or expanded from an archive
+struct NewLogFile {
+    id: Uuid,
+    original_filename: String,
+    storage_path: String,
+    file_size: i64,
+    file_hash: String,
+    content_type: String,
+    archive: Option<ArchiveSource>,
+}
+
+/// Upload a file was expanded from, kept for provenance
+struct ArchiveSource {
+    name: String,
+    /// blake3 hash of the upload as received
+    hash: String,
+    member_path: String,
+    /// Directories of the member inside the archive
+    directory: String,
+}
+
+impl NewLogFile {
+    /// Base name the file pairs on, and its extension. Members of an archive only
+    /// pair with files of the same archive directory.
+    fn pairing_key(&self) -> (String, String) {
+        let (base_filename, extension) = parse_filename(&self.original_filename);
+        match &self.archive {
+            Some(archive) if !archive.directory.is_empty() => (format!("{}/{}", archive.directory, base_filename), extension),
+            _ => (base_filename, extension),
+        }
+    }
+}
+
+/// Remove the stored files of an upload that could not be recorded
+async fn remove_new_files(new_files: &[NewLogFile]) {
+    for new_file in new_files {
+        let _ = fs::remove_file(&new_file.storage_path).await;
+    }
+}
+
+/// Write a multipart field to disk without buffering it, returning its size and blake3 hash
+async fn save_upload_field(
+    mut field: axum::extract::multipart::Field<'_>,
+    storage_path: &str,
+) -> anyhow::Result<(i64, String)> {
+    let mut file = fs::File::create(storage_path).await?;
+    let mut hasher = blake3::Hasher::new();
+    let mut file_size = 0i64;
+
+    while let Some(chunk) = field.chunk().await? {
+        hasher.update(&chunk);
+        file.write_all(&chunk).await?;
+        file_size += chunk.len() as i64;
+    }
+    file.flush().await?;
+
+    Ok((file_size, hasher.finalize().to_hex().to_string()))
+}
+
+/// Replace the stored diagnostics of a .data file, in the transaction holding the lease of the job that decoded it
+async fn store_diagnostics(tx: &mut sqlx::PgConnection, data_file_id: Uuid, diagnostics: &Diagnostics) -> Result<(), sqlx::Error> {
+    sqlx::query!("DELETE FROM parse_diagnostics WHERE file_id = $1", data_file_id)
+        .execute(&mut *tx).await?;
+
+    let entries = &diagnostics.entries;
+    sqlx::query!(
+        r#"
+        INSERT INTO parse_diagnostics (file_id, line_number, byte_offset, kind, message, snippet, suggestion)
+        SELECT $1, * FROM UNNEST($2::bigint[], $3::bigint[], $4::varchar[], $5::text[], $6::text[], $7::text[])
+        "#,
+        data_file_id,
+        &entries.iter().map(|d| d.line as i64).collect::<Vec<_>>(),
+        &entries.iter().map(|d| d.byte_offset as i64).collect::<Vec<_>>(),
+        &entries.iter().map(|d| d.kind.as_str().to_string()).collect::<Vec<_>>(),
+        &entries.iter().map(|d| d.message.clone()).collect::<Vec<_>>(),
+        &entries.iter().map(|d| d.snippet.clone()).collect::<Vec<_>>(),
+        &entries.iter().map(|d| d.suggestion.clone()).collect::<Vec<_>>()
+    ).execute(&mut *tx).await?;
+
+    sqlx::query!(
+        "UPDATE log_files SET diagnostic_counts = $1, diagnostics_updated_at = NOW() WHERE id = $2",
+        serde_json::to_value(&diagnostics.counts).unwrap_or_default(),
+        data_file_id
+    ).execute(&mut *tx).await?;
+    Ok(())
+}
+
+/// Drop the stored diagnostics of a pair when the artifacts it decodes with change
+async fn invalidate_diagnostics(conn: &mut sqlx::PgConnection, file_pair_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "DELETE FROM parse_diagnostics WHERE file_id IN (SELECT id FROM log_files WHERE file_pair_id = $1)",
+        file_pair_id
+    ).execute(&mut *conn).await?;
+    sqlx::query!(
+        "UPDATE log_files SET diagnostic_counts = NULL, diagnostics_updated_at = NULL WHERE file_pair_id = $1",
+        file_pair_id
+    ).execute(&mut *conn).await?;
+    Ok(())
+}
+
+/// Schema artifacts attached to a file pair, messages.xml first
+async fn pair_artifacts(pool: &PgPool, file_pair_id: Option<Uuid>) -> Result<Vec<ArtifactAttachment>, sqlx::Error> {
+    let records = sqlx::query!(
+        r#"
+        SELECT a.id, a.kind, a.name, a.version, a.content_hash, a.storage_path, fa.sender_id
+        FROM file_pair_artifacts fa
+        JOIN schema_artifacts a ON a.id = fa.artifact_id
+        WHERE fa.file_pair_id = $1
+        ORDER BY fa.kind = 'messages' DESC, fa.attached_at
+        "#,
+        file_pair_id
+    ).fetch_all(pool).await?;
+
+    Ok(records.into_iter()
+        .filter_map(|record| Some(ArtifactAttachment {
+            artifact_id: record.id,
+            kind: record.kind.parse().ok()?,
+            name: record.name,
+            version: record.version,
+            content_hash: record.content_hash,
+            storage_path: PathBuf::from(record.storage_path),
+            sender_id: record.sender_id.and_then(|id| u8::try_from(id).ok()),
+        }))
+        .collect())
+}
+
+/// Load the .log configuration of a file pair, or the bundled one when there is no .log,
+/// then apply the artifacts attached to the pair.
+///
+/// Files are read before taking the schema manager lock, which only guards parsing.
+async fn load_configuration(
+    state: &AppState,
+    paths: &FilePairPaths,
+) -> anyhow::Result<LogConfiguration> {
+    let mut artifacts = Vec::new();
+    for attachment in pair_artifacts(&state.db, paths.file_pair_id).await? {
+        let content = fs::read(&attachment.storage_path).await
+            .map_err(|e| anyhow::anyhow!("Failed to read artifact {:?}: {}", attachment.storage_path, e))?;
+        artifacts.push((attachment, content));
+    }
+
+    let mut configuration = match &paths.log_path {
+        Some(log_path) => {
+            let log_content = fs::read_to_string(log_path).await
+                .map_err(|e| anyhow::anyhow!("Failed to read log file {:?}: {}", log_path, e))?;
+            state.schema_manager.lock().await.parse_log_configuration(&log_content)?
+        }
+        None => {
+            let format = detect_data_format(&paths.data_path).await?;
+            let local_start = extract_timestamp_from_filename(&paths.original_filename);
+            // Without a bundled dictionary for its transport version, an attached messages.xml is the only one that fits
+            let attached = artifacts.iter().any(|(attachment, _)| attachment.kind == ArtifactKind::Messages);
+            let bundled = match bundled_dictionary(format) {
+                Some(bundled) => bundled,
+                None if attached => default_dictionary(),
+                None => anyhow::bail!("No {:?} dictionary is bundled: upload the .log of {} or attach its messages.xml",
+                    format, paths.original_filename),
+            };
+            state.schema_manager.lock().await.bundled_configuration(&paths.original_filename, bundled, local_start)?
+        }
+    };
+    state.schema_manager.lock().await.apply_artifacts(&mut configuration, &artifacts)?;
+    Ok(configuration)
+}
+
+/// Format of a .data file uploaded without its .log, to pick the bundled dictionary
+async fn detect_data_format(data_path: &StdPath) -> anyhow::Result<DataFormat> {
+    let data_path = data_path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let protocol = default_dictionary().load()?;
+        DataFormat::detect_file(&mut std::fs::File::open(&data_path)?, &protocol)
+    }).await?
+}
+
+/// Load the configuration of a file pair and start streaming its .data file
+async fn open_telemetry_stream(
+    state: &AppState,
+    paths: &FilePairPaths,
+) -> anyhow::Result<(LogConfiguration, TelemetryStream)> {
+    let configuration = load_configuration(state, paths).await?;
+    let stream = state.schema_manager.lock().await
+        .stream_telemetry_data(&paths.data_path, &configuration)?;
+    Ok((configuration, stream))
+}
+
+/// UTC anchor of a file pair, as estimated by the processing job.
+///
+/// Until the job has run, the log's `time_of_day` is used; this never reads the .data file.
+async fn load_time_anchor(
+    state: &AppState,
+    paths: &FilePairPaths,
+    configuration: &LogConfiguration,
+) -> anyhow::Result<TimeAnchor> {
+    let stored = sqlx::query!(
+        "SELECT time_anchor_source, log_start_utc, time_anchor_offset FROM log_files WHERE id = $1",
+        paths.data_file_id
+    ).fetch_optional(&state.db).await?;
+
+    if let Some(record) = stored
+        && let (Some(source), Some(start), Some(offset)) = (record.time_anchor_source, record.log_start_utc, record.time_anchor_offset)
+    {
+        return Ok(TimeAnchor {
+            source: source.parse()?,
+            start: Some(start.timestamp_micros() as f64 / 1e6),
+            local_start: configuration.local_start,
+            offset,
+            samples: 0,
+        });
+    }
+    Ok(TimeAnchor::from_configuration(configuration))
+}
+
+/// Store the UTC anchor estimated by the processing job on both files of the pair
+async fn store_time_anchor(pool: &PgPool, paths: &FilePairPaths, anchor: &TimeAnchor) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE log_files SET time_anchor_source = $1, log_start_utc = $2, time_anchor_offset = $3
+         WHERE id = $4 OR file_pair_id = $5",
+        anchor.source.as_str(),
+        anchor.utc(0.0),
+        anchor.offset,
+        paths.data_file_id,
+        paths.file_pair_id
+    ).execute(pool).await?;
+
+    info!("Anchored {} on {} time ({} fixes, {:+.3}s from time_of_day)",
+        paths.original_filename, anchor.source.as_str(), anchor.samples, anchor.offset);
+    Ok(())
+}
+
+/// Register the protocol a file pair decodes with and link both files to it, returning the schema id
+async fn link_schema(
+    pool: &PgPool,
+    paths: &FilePairPaths,
+    configuration: &LogConfiguration,
+    schema_hash: &str,
+) -> Result<Uuid, sqlx::Error> {
+    let schema_id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO schemas (schema_hash, message_count, protocol)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (schema_hash) DO UPDATE SET schema_hash = EXCLUDED.schema_hash
+        RETURNING id
+        "#,
+        schema_hash,
+        configuration.protocol.len() as i32,
+        serde_json::to_value(&configuration.protocol.messages).unwrap_or_default()
+    ).fetch_one(pool).await?;
+
+    sqlx::query!(
+        "UPDATE log_files SET schema_id = $1 WHERE id = $2 OR file_pair_id = $3",
+        schema_id,
+        paths.data_file_id,
+        paths.file_pair_id
+    ).execute(pool).await?;
+
+    Ok(schema_id)
+}
+
+/// Keep the paparazzi versions of a file pair under `metadata.paparazzi` of both files
+async fn store_provenance(pool: &PgPool, paths: &FilePairPaths, provenance: &BuildProvenance) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE log_files SET metadata = COALESCE(metadata, '{}'::jsonb) || jsonb_build_object('paparazzi', $1::jsonb)
+         WHERE id = $2 OR file_pair_id = $3",
+        serde_json::to_value(provenance).unwrap_or_default(),
+        paths.data_file_id,
+        paths.file_pair_id
+    ).execute(pool).await?;
+    Ok(())
+}
+
+/// Keep the schema detection result of a file pair under `metadata.schema_detection` of both files
+async fn store_schema_detection(pool: &PgPool, paths: &FilePairPaths, detection: &SchemaDetectionResponse) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE log_files SET metadata = COALESCE(metadata, '{}'::jsonb) || jsonb_build_object('schema_detection', $1::jsonb)
+         WHERE id = $2 OR file_pair_id = $3",
+        serde_json::to_value(detection).unwrap_or_default(),
+        paths.data_file_id,
+        paths.file_pair_id
+    ).execute(pool).await?;
+    Ok(())
+}
+
+/// Quote a CSV value when it contains separators or quotes
+fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
 // Response types
 #[derive(Serialize)]
 pub struct ApiResponse<T> {
@@ -100,6 +497,8 @@ pub struct FileUploadResponse {
     pub file_size: u64,
     pub upload_timestamp: chrono::DateTime<chrono::Utc>,
     pub content_type: String,
+    /// Compressed file or archive the file was expanded from
+    pub source_archive: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -113,7 +512,7 @@ pub struct LogFileInfo {
     pub is_processed: bool,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 pub struct SchemaDetectionResponse {
     pub success: bool,
     pub schema_found: bool,
@@ -121,23 +520,44 @@ pub struct SchemaDetectionResponse {
     pub source: String,
     pub warnings: Vec<String>,
     pub schema_hash: Option<String>,
+    /// UTC time of the start of the log and where it comes from
+    pub time_anchor: Option<TimeAnchor>,
+    /// Message dictionary the .data file was decoded with
+    pub dictionary: Option<DictionarySource>,
+    /// Messages of the .data file the dictionary does not match
+    pub mismatches: Option<DictionaryMismatches>,
+    /// Text .data file or binary pprzlink capture
+    pub data_format: Option<DataFormat>,
+    /// `ALIVE` md5sum of each aircraft compared with its airframe
+    pub config_checks: Option<Vec<ConfigCheck>>,
 }
 
 #[derive(Serialize)]
 pub struct ProcessingStatusResponse {
-    pub task_id: Uuid,
-    pub status: String,
-    pub progress: f64,
-    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub file_id: Uuid,
+    pub status: ProcessingStatus,
+    /// Queued or running job of the pair
+    pub job: Option<ProcessingJob>,
+    /// Rows stored in `parsed_data` by the last successful processing
+    pub ingestion: Option<IngestionStats>,
+    pub uploaded_at: chrono::DateTime<chrono::Utc>,
     pub started_at: Option<chrono::DateTime<chrono::Utc>>,
     pub completed_at: Option<chrono::DateTime<chrono::Utc>>,
     pub error_message: Option<String>,
-    pub records_processed: Option<usize>,
 }
 
 #[derive(Deserialize)]
 pub struct ProcessFileRequest {
     pub file_id: Uuid,
+    /// Higher runs first, 0 by default
+    pub priority: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct ProcessingJobsQuery {
+    /// Jobs in this state only
+    pub status: Option<JobStatus>,
+    pub limit: Option<i64>,
 }
 
 async fn healthz() -> &'static str {
@@ -194,18 +614,18 @@ async fn root() -> Json<serde_json::Value> {
 
 async fn upload_log_files(
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
     mut multipart: Multipart,
 ) -> Result<Json<ApiResponse<Vec<FileUploadResponse>>>, StatusCode> {
-    // For now, we'll use a fixed user ID for testing
-    // TODO: Extract from authentication token
-    let user_id = Uuid::parse_str("00000000-0000-0000-0000-000000000001").unwrap();
+    let claims = authenticate_headers(&state.db, &headers).await?;
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
     
     info!("Upload request received for user: {}", user_id);
     let mut uploaded_files = Vec::new();
     let mut file_pairs: std::collections::HashMap<String, Vec<Uuid>> = std::collections::HashMap::new();
     
     // Create uploads directory if it doesn't exist
-    if let Err(e) = fs::create_dir_all("uploads").await {
+    if let Err(e) = fs::create_dir_all(&state.upload_dir).await {
         error!("Failed to create uploads directory: {}", e);
         return Err(StatusCode::INTERNAL_SERVER_ERROR);
     }
@@ -220,93 +640,160 @@ async fn upload_log_files(
         
         info!("Processing field: name={}, filename={}, content_type={}", field_name, original_filename, content_type);
         
-        // Read file data
-        let data = field.bytes().await.map_err(|e| {
-            error!("Failed to read field bytes: {}", e);
-            StatusCode::BAD_REQUEST
-        })?;
-        let file_size = data.len() as i64;
-        
-        info!("File size: {} bytes", file_size);
-        
         // Generate unique file ID and save path
         let file_id = Uuid::new_v4();
-        let storage_path = format!("uploads/{}", file_id);
-        
-        // Save file to disk
-        if let Err(e) = fs::write(&storage_path, &data).await {
-            error!("Failed to save file {}: {}", original_filename, e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
-
-        // Calculate file hash for deduplication
-        let file_hash = blake3::hash(&data).to_hex().to_string();
-        
-        // Extract timestamp and parse filename for pairing
-        let extracted_timestamp = extract_timestamp_from_filename(&original_filename);
-        let (base_filename, file_extension) = parse_filename(&original_filename);
+        let storage_path = state.upload_dir.join(file_id.to_string()).to_string_lossy().to_string();
         
-        // Get or create a file pair ID for files with the same base filename
-        let file_pair_id = match get_or_create_pair_id(&state.db, &base_filename).await {
-            Ok(pair_id) => Some(pair_id),
+        // Stream the file to disk chunk by chunk, hashing it for deduplication on the way
+        let (file_size, file_hash) = match save_upload_field(field, &storage_path).await {
+            Ok(saved) => saved,
             Err(e) => {
-                warn!("Failed to get/create pair ID for {}: {}", base_filename, e);
-                None
+                error!("Failed to save file {}: {}", original_filename, e);
+                let _ = fs::remove_file(&storage_path).await;
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
             }
         };
         
-        // Track files by their base filename for session creation
-        file_pairs.entry(base_filename.clone()).or_insert_with(Vec::new);
-        
-        // Ensure test user exists (for development only)
-        let _ = sqlx::query!(
-            "INSERT INTO users (id, username, email, password_hash) VALUES ($1, 'test_user', 'test@example.com', 'dummy_hash') ON CONFLICT (id) DO NOTHING",
-            user_id
-        ).execute(&state.db).await;
-        
-        // Insert into database with enhanced file pairing info
-        let upload_timestamp = chrono::Utc::now();
-        let result = sqlx::query!(
-            r#"
-            INSERT INTO log_files (id, user_id, original_filename, storage_path, file_size, file_hash, content_type, upload_timestamp, file_pair_id, base_filename, extracted_timestamp, file_extension)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
-            RETURNING id, original_filename, file_size, upload_timestamp, content_type
-            "#,
-            file_id,
-            user_id,  // Use authenticated user
-            original_filename,
-            storage_path,
-            file_size,
-            file_hash,
-            content_type,
-            upload_timestamp,
-            file_pair_id,
-            base_filename,
-            extracted_timestamp,
-            file_extension
-        ).fetch_one(&state.db).await;
-
-        match result {
-            Ok(record) => {
-                info!("Successfully uploaded file: {} ({})", original_filename, file_id);
-                
-                // Track file for session creation
-                file_pairs.entry(base_filename.clone()).or_insert_with(Vec::new).push(record.id);
-                
-                uploaded_files.push(FileUploadResponse {
-                    file_id: record.id,
-                    original_filename: record.original_filename,
-                    file_size: record.file_size as u64,
-                    upload_timestamp: record.upload_timestamp,
-                    content_type: record.content_type.unwrap_or_default(),
+        info!("File size: {} bytes", file_size);
+
+        // Compressed files and archive bundles are replaced by the log files they contain
+        let upload_path = PathBuf::from(&storage_path);
+        let upload_name = original_filename.clone();
+        let upload_dir = state.upload_dir.clone();
+        let expanded = tokio::task::spawn_blocking(move || expand_upload(&upload_path, &upload_name, &upload_dir)).await;
+        let new_files = match expanded {
+            Ok(Ok(None)) => vec![NewLogFile {
+                id: file_id,
+                original_filename: original_filename.clone(),
+                storage_path: storage_path.clone(),
+                file_size,
+                file_hash: file_hash.clone(),
+                content_type: content_type.clone(),
+                archive: None,
+            }],
+            Ok(Ok(Some(expanded))) => {
+                let _ = fs::remove_file(&storage_path).await;
+                if !expanded.skipped.is_empty() {
+                    info!("Skipped {} member(s) of {}: {:?}", expanded.skipped.len(), original_filename, expanded.skipped);
+                }
+                expanded.files.into_iter()
+                    .map(|extracted| NewLogFile {
+                        id: extracted.id,
+                        original_filename: extracted.filename,
+                        storage_path: extracted.storage_path.to_string_lossy().to_string(),
+                        file_size: extracted.file_size,
+                        file_hash: extracted.file_hash,
+                        content_type: "application/octet-stream".to_string(),
+                        archive: Some(ArchiveSource {
+                            name: original_filename.clone(),
+                            hash: file_hash.clone(),
+                            member_path: extracted.member_path,
+                            directory: extracted.directory,
+                        }),
+                    })
+                    .collect()
+            }
+            Ok(Err(e)) => {
+                warn!("Failed to expand {}: {}", original_filename, e);
+                let _ = fs::remove_file(&storage_path).await;
+                return Err(match e {
+                    UploadError::TooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+                    UploadError::Zip(_) => StatusCode::BAD_REQUEST,
+                    // Corrupt or truncated compressed data
+                    UploadError::Io(ref io) if matches!(io.kind(),
+                        std::io::ErrorKind::InvalidData | std::io::ErrorKind::InvalidInput | std::io::ErrorKind::UnexpectedEof) => StatusCode::BAD_REQUEST,
+                    UploadError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
                 });
             }
             Err(e) => {
-                error!("Database error for file {}: {}", original_filename, e);
-                // Clean up file on database error
+                error!("Upload expansion task failed for {}: {}", original_filename, e);
                 let _ = fs::remove_file(&storage_path).await;
                 return Err(StatusCode::INTERNAL_SERVER_ERROR);
             }
+        };
+
+        // Record the files of this upload together: on an error none is kept, on disk or in the database
+        let mut tx = match state.db.begin().await {
+            Ok(tx) => tx,
+            Err(e) => {
+                error!("Failed to start a transaction for {}: {}", original_filename, e);
+                remove_new_files(&new_files).await;
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        };
+        let mut recorded = Vec::with_capacity(new_files.len());
+        for new_file in &new_files {
+            // Extract timestamp and parse filename for pairing
+            let extracted_timestamp = extract_timestamp_from_filename(&new_file.original_filename);
+            let (base_filename, file_extension) = new_file.pairing_key();
+            
+            // Get or create a file pair ID for files with the same base filename
+            let file_pair_id = match get_or_create_pair_id(&mut *tx, user_id, &base_filename).await {
+                Ok(pair_id) => Some(pair_id),
+                Err(e) => {
+                    warn!("Failed to get/create pair ID for {}: {}", base_filename, e);
+                    None
+                }
+            };
+            
+            // Insert into database with enhanced file pairing info
+            let upload_timestamp = chrono::Utc::now();
+            let archive = new_file.archive.as_ref();
+            let result = sqlx::query!(
+                r#"
+                INSERT INTO log_files (id, user_id, original_filename, storage_path, file_size, file_hash, content_type, upload_timestamp, file_pair_id, base_filename, extracted_timestamp, file_extension,
+                                       source_archive_name, source_archive_hash, archive_member_path)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
+                RETURNING id, original_filename, file_size, upload_timestamp, content_type, source_archive_name
+                "#,
+                new_file.id,
+                user_id,  // Use authenticated user
+                new_file.original_filename,
+                new_file.storage_path,
+                new_file.file_size,
+                new_file.file_hash,
+                new_file.content_type,
+                upload_timestamp,
+                file_pair_id,
+                base_filename,
+                extracted_timestamp,
+                file_extension,
+                archive.map(|a| a.name.clone()),
+                archive.map(|a| a.hash.clone()),
+                archive.map(|a| a.member_path.clone())
+            ).fetch_one(&mut *tx).await;
+
+            match result {
+                Ok(record) => recorded.push((base_filename, record)),
+                Err(e) => {
+                    error!("Database error for file {}: {}", new_file.original_filename, e);
+                    // Clean up every file of the upload on database error
+                    drop(tx);
+                    remove_new_files(&new_files).await;
+                    return Err(StatusCode::INTERNAL_SERVER_ERROR);
+                }
+            }
+        }
+        if let Err(e) = tx.commit().await {
+            error!("Failed to record the files of {}: {}", original_filename, e);
+            remove_new_files(&new_files).await;
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+
+        for (base_filename, record) in recorded {
+            info!("Successfully uploaded file: {} ({})", record.original_filename, record.id);
+
+            // Track file for session creation
+            file_pairs.entry(base_filename).or_insert_with(Vec::new).push(record.id);
+
+            uploaded_files.push(FileUploadResponse {
+                file_id: record.id,
+                original_filename: record.original_filename,
+                file_size: record.file_size as u64,
+                upload_timestamp: record.upload_timestamp,
+                content_type: record.content_type.unwrap_or_default(),
+                source_archive: record.source_archive_name,
+            });
         }
     }
 
@@ -318,6 +805,15 @@ async fn upload_log_files(
         }));
     }
 
+    // Parse each pair in the background; a lone .log waits for its .data
+    for file_ids in file_pairs.values() {
+        if let Some(&file_id) = file_ids.first()
+            && let Err(e) = state.file_processor.enqueue(file_id, 0).await
+        {
+            warn!("Failed to queue {} for processing: {}", file_id, e);
+        }
+    }
+
     // Automatically create analysis sessions for file pairs
     let analysis_service = AnalysisService::new();
     let mut created_sessions = Vec::new();
@@ -371,242 +867,2012 @@ async fn upload_log_files(
 
     Ok(Json(ApiResponse {
         success: true,
-        data: Some(uploaded_files),
-        message,
+        data: Some(uploaded_files),
+        message,
+    }))
+}
+
+async fn list_log_files(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<Vec<LogFileInfo>>>, StatusCode> {
+    let claims = authenticate_headers(&state.db, &headers).await?;
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let result = sqlx::query_as!(
+        LogFileInfo,
+        r#"
+        SELECT 
+            id,
+            original_filename,
+            file_size,
+            upload_timestamp,
+            COALESCE(processing_status, 'pending') as "processing_status!",
+            COALESCE(content_type, 'application/octet-stream') as "content_type!",
+            is_processed
+        FROM log_files
+        WHERE user_id = $1
+        ORDER BY upload_timestamp DESC
+        "#,
+        user_id
+    ).fetch_all(&state.db).await;
+
+    match result {
+        Ok(files) => {
+            Ok(Json(ApiResponse {
+                success: true,
+                data: Some(files),
+                message: "Files retrieved successfully".to_string(),
+            }))
+        }
+        Err(e) => {
+            error!("Database error retrieving files: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn get_log_file(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    axum::extract::Path(file_id): axum::extract::Path<Uuid>,
+) -> Result<Json<ApiResponse<LogFileInfo>>, StatusCode> {
+    let claims = authenticate_headers(&state.db, &headers).await?;
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let result = sqlx::query_as!(
+        LogFileInfo,
+        r#"
+        SELECT 
+            id,
+            original_filename,
+            file_size,
+            upload_timestamp,
+            COALESCE(processing_status, 'pending') as "processing_status!",
+            COALESCE(content_type, 'application/octet-stream') as "content_type!",
+            is_processed
+        FROM log_files
+        WHERE id = $1 AND user_id = $2
+        "#,
+        file_id,
+        user_id
+    ).fetch_optional(&state.db).await;
+
+    match result {
+        Ok(Some(file)) => {
+            Ok(Json(ApiResponse {
+                success: true,
+                data: Some(file),
+                message: "File retrieved successfully".to_string(),
+            }))
+        }
+        Ok(None) => {
+            Ok(Json(ApiResponse {
+                success: false,
+                data: None,
+                message: "File not found".to_string(),
+            }))
+        }
+        Err(e) => {
+            error!("Database error retrieving file {}: {}", file_id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn delete_log_file(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    axum::extract::Path(file_id): axum::extract::Path<Uuid>,
+) -> Result<Json<ApiResponse<()>>, StatusCode> {
+    let claims = authenticate_headers(&state.db, &headers).await?;
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    // Get file info first to delete from disk
+    let file_info = sqlx::query!(
+        "SELECT storage_path FROM log_files WHERE id = $1 AND user_id = $2",
+        file_id,
+        user_id
+    ).fetch_optional(&state.db).await
+     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let storage_path = match file_info {
+        Some(info) => info.storage_path,
+        None => {
+            return Ok(Json(ApiResponse {
+                success: false,
+                data: None,
+                message: "File not found".to_string(),
+            }));
+        }
+    };
+    // The cache of the pair is kept under the id of its .data file, whichever half is deleted
+    let pair = resolve_file_pair(&state.db, file_id).await.ok();
+
+    // Delete from database
+    let result = sqlx::query!(
+        "DELETE FROM log_files WHERE id = $1 AND user_id = $2",
+        file_id,
+        user_id
+    ).execute(&state.db).await;
+
+    match result {
+        Ok(rows) if rows.rows_affected() > 0 => {
+            // Delete file from disk
+            if let Err(e) = fs::remove_file(&storage_path).await {
+                error!("Failed to delete file from disk: {}", e);
+            }
+            if let Some(paths) = &pair {
+                let _ = fs::remove_dir_all(paths.columnar_cache(&state.upload_dir).dir()).await;
+                // The .data file left is decoded with the bundled dictionary from now on
+                if paths.data_file_id != file_id
+                    && let Err(e) = state.file_processor.enqueue(paths.data_file_id, 0).await
+                {
+                    error!("Failed to queue {} after deleting its .log: {}", paths.data_file_id, e);
+                }
+            }
+            
+            info!("Successfully deleted file: {}", file_id);
+            Ok(Json(ApiResponse {
+                success: true,
+                data: Some(()),
+                message: "File deleted successfully".to_string(),
+            }))
+        }
+        Ok(_) => {
+            Ok(Json(ApiResponse {
+                success: false,
+                data: None,
+                message: "File not found".to_string(),
+            }))
+        }
+        Err(e) => {
+            error!("Database error deleting file {}: {}", file_id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Schema detection result of a file, as stored by the last processing of its pair.
+///
+/// Nothing is decoded here: 202 while the pair is queued or being processed, 404 when
+/// it has not been processed yet.
+async fn detect_schema(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(file_id): Path<Uuid>,
+) -> Result<(StatusCode, Json<ApiResponse<SchemaDetectionResponse>>), StatusCode> {
+    let claims = authenticate_headers(&state.db, &headers).await?;
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let record = sqlx::query!(
+        r#"
+        SELECT COALESCE(processing_status, 'pending') AS "status!", processing_error,
+               metadata->'schema_detection' AS detection
+        FROM log_files WHERE id = $1 AND user_id = $2
+        "#,
+        file_id,
+        user_id
+    ).fetch_optional(&state.db).await.map_err(|e| {
+        error!("Database error reading the schema detection of {}: {}", file_id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?.ok_or(StatusCode::NOT_FOUND)?;
+
+    if let Some(detection) = record.detection.and_then(|detection| serde_json::from_value(detection).ok()) {
+        return Ok((StatusCode::OK, Json(ApiResponse {
+            success: true,
+            data: Some(detection),
+            message: "Schema detected by the last processing of the file".to_string(),
+        })));
+    }
+
+    let job = state.file_processor.active_job(file_id).await.map_err(|e| {
+        error!("Database error reading the processing job of {}: {}", file_id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if let Some(job) = job {
+        return Ok((StatusCode::ACCEPTED, Json(ApiResponse {
+            success: false,
+            data: None,
+            message: format!("Schema detection is {} in job {}", job.status, job.id),
+        })));
+    }
+    match record.status.parse() {
+        Ok(ProcessingStatus::Failed) => {
+            let error = record.processing_error.unwrap_or_default();
+            let response = SchemaDetectionResponse {
+                success: false,
+                schema_found: false,
+                confidence: 0.0,
+                source: "Failed to parse".to_string(),
+                warnings: vec![format!("Parse error: {}", error)],
+                schema_hash: None,
+                time_anchor: None,
+                dictionary: None,
+                mismatches: None,
+                data_format: None,
+                config_checks: None,
+            };
+            Ok((StatusCode::OK, Json(ApiResponse {
+                success: false,
+                data: Some(response),
+                message: format!("Failed to parse log file: {}", error),
+            })))
+        }
+        _ => Err(StatusCode::NOT_FOUND),
+    }
+}
+
+/// ALIVE md5sums and UTC anchor of a pair, gathered in its pass over the .data file
+struct PairAnalysis {
+    alive: AliveMd5s,
+    anchor: TimeAnchorBuilder,
+}
+
+impl PairAnalysis {
+    fn new(configuration: &LogConfiguration) -> Self {
+        Self {
+            alive: AliveMd5s::default(),
+            anchor: TimeAnchorBuilder::for_configuration(configuration),
+        }
+    }
+
+    fn add(&mut self, message: &TelemetryMessage) {
+        self.alive.add(message);
+        self.anchor.add(message);
+    }
+}
+
+/// Store what the pass over a pair learnt (diagnostics, schema, versions, time anchor) and publish its warnings.
+///
+/// The detection result is kept for `GET /api/files/{id}/schema`.
+async fn record_analysis(
+    state: &AppState,
+    paths: &FilePairPaths,
+    job: &ProcessingJob,
+    configuration: &LogConfiguration,
+    mut progress: DataProgress,
+    alive: &AliveMd5s,
+    time_anchor: TimeAnchor,
+) {
+    let scope = paths.event_scope(Some(job.id));
+    let config_checks = alive.check(configuration);
+
+    let mut warnings = Vec::new();
+    if let DictionarySource::Bundled { name, version } = &configuration.dictionary {
+        warnings.push(format!("No .log file, decoded with the bundled {} v{} dictionary", name, version));
+    }
+    let mismatches = &progress.mismatches;
+    if !mismatches.unknown_messages.is_empty() {
+        warnings.push(format!("{} message(s) not in the dictionary: {}",
+            mismatches.unknown_messages.len(),
+            mismatches.unknown_messages.keys().cloned().collect::<Vec<_>>().join(", ")));
+    }
+    for (message, mismatch) in &mismatches.field_counts {
+        warnings.push(format!("{}: dictionary declares {} field(s), {} line(s) have {:?}",
+            message, mismatch.expected, mismatch.lines, mismatch.found));
+    }
+    if progress.checksum_errors > 0 || progress.resyncs > 0 {
+        warnings.push(format!("Binary capture: {} frame(s) failed their checksum, lost sync {} time(s)",
+            progress.checksum_errors, progress.resyncs));
+    }
+    for check in config_checks.iter().filter(|check| check.status == ConfigCheckStatus::Unverifiable) {
+        warnings.push(format!("Sender {}: ALIVE md5sum {} cannot be checked against the airframe embedded in the .log; \
+            attach the airframe file to verify it", check.sender_id, check.alive_md5));
+    }
+    for check in config_checks.iter().filter(|check| check.status == ConfigCheckStatus::Mismatch) {
+        warnings.push(format!("Sender {} flew another configuration: ALIVE md5sum {} does not match the airframe",
+            check.sender_id, check.alive_md5));
+        if let Some(diagnostic) = check.diagnostic() {
+            state.events.publish(scope.event(EventKind::Diagnostic { diagnostic: diagnostic.clone() })).await;
+            progress.diagnostics.push(diagnostic);
+        }
+    }
+    for diagnostic in &configuration.diagnostics {
+        warnings.push(diagnostic.message.clone());
+        state.events.publish(scope.event(EventKind::Diagnostic { diagnostic: diagnostic.clone() })).await;
+        progress.diagnostics.push(diagnostic.clone());
+    }
+    if !progress.diagnostics.is_empty() {
+        warnings.push(format!("{} diagnostic(s), see /api/files/{}/diagnostics",
+            progress.diagnostics.total(), paths.data_file_id));
+    }
+    // Only the job holding the lease writes them, so a cancelled or taken over job cannot overwrite its successor's
+    let stored = async {
+        let Some(mut tx) = state.file_processor.lock_lease(job.id).await? else {
+            info!("Job {} no longer holds its lease, diagnostics of {} not stored", job.id, paths.data_file_id);
+            return Ok(());
+        };
+        store_diagnostics(&mut tx, paths.data_file_id, &progress.diagnostics).await?;
+        tx.commit().await
+    }.await;
+    if let Err(e) = stored {
+        warn!("Failed to store diagnostics of {}: {}", paths.data_file_id, e);
+    }
+    let provenance = configuration.build_provenance();
+    if provenance.mismatch() {
+        warnings.push(format!("Logged with paparazzi {} but the firmware was built from {}",
+            provenance.runtime.as_ref().map(|v| v.raw.as_str()).unwrap_or_default(),
+            provenance.build.as_ref().map(|v| v.raw.as_str()).unwrap_or_default()));
+    }
+    if provenance.dirty() {
+        warnings.push("Firmware built from a tree with uncommitted changes".to_string());
+    }
+    if let Err(e) = store_provenance(&state.db, paths, &provenance).await {
+        warn!("Failed to store the paparazzi versions of {}: {}", paths.data_file_id, e);
+    }
+    let schema_hash = configuration.protocol.schema_hash();
+    if let Err(e) = link_schema(&state.db, paths, configuration, &schema_hash).await {
+        warn!("Failed to register schema {} of {}: {}", schema_hash, paths.data_file_id, e);
+    }
+    // Share of the lines the dictionary could decode
+    let confidence = match progress.messages_decoded + progress.lines_skipped {
+        0 => 1.0,
+        total => progress.messages_decoded as f64 / total as f64,
+    };
+    if time_anchor.source == TimeSource::LocalFilename {
+        warnings.push("No .log file and no GPS fix: no UTC time, only the local time of the file name".to_string());
+    }
+    if let Err(e) = store_time_anchor(&state.db, paths, &time_anchor).await {
+        warnings.push(format!("Could not store the UTC anchor: {}", e));
+    }
+
+    for warning in &warnings {
+        state.events.publish(scope.event(EventKind::Warning { message: warning.clone() })).await;
+    }
+
+    let response = SchemaDetectionResponse {
+        success: true,
+        schema_found: true,
+        confidence,
+        source: format!("Parsed from {} with {} messages", paths.original_filename, progress.messages_decoded),
+        warnings,
+        schema_hash: Some(schema_hash),
+        time_anchor: Some(time_anchor),
+        dictionary: Some(configuration.dictionary.clone()),
+        mismatches: Some(progress.mismatches),
+        data_format: Some(progress.format),
+        config_checks: Some(config_checks),
+    };
+    if let Err(e) = store_schema_detection(&state.db, paths, &response).await {
+        warn!("Failed to store the schema detection of {}: {}", paths.data_file_id, e);
+    }
+}
+
+/// Background job of the file processor: parse the pair a file belongs to and record the outcome.
+///
+/// A failed attempt leaves the pair `pending` until the job runs out of retries; a .log
+/// without its telemetry fails at once. Nothing is recorded once the job was cancelled.
+async fn process_file_pair(state: Arc<AppState>, job: ProcessingJob) -> anyhow::Result<()> {
+    let paths = match resolve_file_pair(&state.db, job.file_id).await {
+        Ok(paths) => paths,
+        // Nothing to retry: the pair is queued again once the other half is uploaded
+        Err(e @ FilePairError::MissingPair(_)) => {
+            let message = e.message();
+            let user_id = sqlx::query_scalar!("SELECT user_id FROM log_files WHERE id = $1", job.file_id)
+                .fetch_one(&state.db).await?;
+            let scope = EventScope { user_id, file_id: job.file_id, file_pair_id: job.file_pair_id, job_id: Some(job.id) };
+            update_status(&state, &scope, &job, ProcessingStatus::Failed, Some(&message)).await?;
+            return Err(Unrecoverable(message).into());
+        }
+        Err(e) => return Err(anyhow::anyhow!(e.message())),
+    };
+    let scope = paths.event_scope(Some(job.id));
+    if !update_status(&state, &scope, &job, ProcessingStatus::Processing, None).await? {
+        return Ok(());
+    }
+
+    match run_stages(&state, &paths, &job).await {
+        Ok(_) => {
+            update_status(&state, &scope, &job, ProcessingStatus::Completed, None).await?;
+            Ok(())
+        }
+        Err(e) => {
+            warn!("Attempt {}/{} to process {} failed: {}", job.attempts, job.max_attempts, paths.original_filename, e);
+            let status = if job.is_last_attempt() { ProcessingStatus::Failed } else { ProcessingStatus::Pending };
+            update_status(&state, &scope, &job, status, Some(&e.to_string())).await?;
+            Err(e)
+        }
+    }
+}
+
+/// Decode a pair once, feeding every stage its job has not completed yet: analysis, ingestion into
+/// `parsed_data` and the columnar cache. A stage that fails stops being fed while the others complete;
+/// each stage that succeeds is recorded on the job, so that a retry only runs the ones that failed.
+async fn run_stages(state: &AppState, paths: &FilePairPaths, job: &ProcessingJob) -> anyhow::Result<()> {
+    let started = std::time::Instant::now();
+    let (configuration, mut stream) = open_telemetry_stream(state, paths).await?;
+    let scope = paths.event_scope(Some(job.id));
+    state.events.forward_progress(scope.clone(), stream.subscribe_progress());
+
+    let schema_hash = configuration.protocol.schema_hash();
+    let cache = paths.columnar_cache(&state.upload_dir);
+    // The anchor stored by the attempt that completed the analysis, known before the pass
+    let stored_anchor = match job.stage_done(JobStage::Analysis) {
+        true => Some(load_time_anchor(state, paths, &configuration).await?),
+        false => None,
+    };
+    let mut analysis = stored_anchor.is_none().then(|| PairAnalysis::new(&configuration));
+    let mut ingestion = match job.stage_done(JobStage::Ingestion) {
+        true => None,
+        false => {
+            let start = stored_anchor.as_ref().and_then(|anchor| anchor.start);
+            Some(Ingestion::begin(&state.db, paths.data_file_id, paths.file_pair_id, start).await)
+        }
+    };
+    let mut columnar = match job.stage_done(JobStage::Columnar) || cache.current(&schema_hash).is_some() {
+        true => None,
+        false => Some(cache.writer(&configuration.protocol, &schema_hash).map(ColumnarWriterTask::spawn)),
+    };
+
+    while let Some(message) = stream.next().await {
+        let message = message?;
+        if let Some(analysis) = &mut analysis {
+            analysis.add(&message);
+        }
+        if let Some(Ok(sink)) = &mut ingestion
+            && let Err(e) = sink.add(&message).await
+        {
+            ingestion = Some(Err(e));
+        }
+        if let Some(Ok(writer)) = &mut columnar {
+            writer.add(message).await;
+        }
+    }
+    let progress = stream.progress();
+    info!("Decoded {} ({} messages) in {:?}", paths.original_filename, progress.messages_decoded, started.elapsed());
+
+    let (alive, time_anchor) = match analysis {
+        Some(analysis) => (Some(analysis.alive), analysis.anchor.finish()),
+        None => (None, stored_anchor.unwrap_or_else(|| TimeAnchor::from_configuration(&configuration))),
+    };
+    let mut failures = Vec::new();
+    if let Some(ingestion) = ingestion {
+        let ingested = async { ingestion?.finish(Some(&time_anchor)).await }.await;
+        complete_stage(state, job, JobStage::Ingestion, ingested.map(|_| ()), &mut failures).await?;
+    }
+    if let Some(alive) = alive {
+        record_analysis(state, paths, job, &configuration, progress, &alive, time_anchor).await;
+        complete_stage(state, job, JobStage::Analysis, Ok(()), &mut failures).await?;
+    }
+    if let Some(columnar) = columnar {
+        let built = async { columnar?.finish().await }.await;
+        complete_stage(state, job, JobStage::Columnar, built.map(|_| ()), &mut failures).await?;
+    }
+    match failures.is_empty() {
+        true => Ok(()),
+        false => Err(anyhow::anyhow!(failures.join("; "))),
+    }
+}
+
+/// Record a stage that succeeded on its job, or its error in `failures`
+async fn complete_stage(
+    state: &AppState,
+    job: &ProcessingJob,
+    stage: JobStage,
+    outcome: anyhow::Result<()>,
+    failures: &mut Vec<String>,
+) -> Result<(), sqlx::Error> {
+    match outcome {
+        Ok(()) => state.file_processor.complete_stage(job.id, stage).await,
+        Err(e) => {
+            failures.push(format!("{} failed: {}", stage.as_str(), e));
+            Ok(())
+        }
+    }
+}
+
+/// Samples of channels read from the columnar cache of a pair, one table read per message.
+///
+/// Fails while the processing job has not built the cache for the current dictionary.
+async fn read_columnar_channels(
+    state: &AppState,
+    paths: &FilePairPaths,
+    configuration: &LogConfiguration,
+    channels: &[ChannelRef],
+    query: &TimeSeriesQuery,
+) -> anyhow::Result<Vec<ChannelSamples>> {
+    let cache = paths.columnar_cache(&state.upload_dir);
+    let schema_hash = configuration.protocol.schema_hash();
+    let channels = channels.to_vec();
+    let (start, end, sender_id) = (query.start, query.end, query.sender_id);
+
+    tokio::task::spawn_blocking(move || {
+        let manifest = cache.current(&schema_hash)
+            .ok_or_else(|| anyhow::anyhow!("not built for dictionary {} yet", schema_hash))?;
+        let mut by_message: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        for (index, channel) in channels.iter().enumerate() {
+            by_message.entry(channel.message.as_str()).or_default().push(index);
+        }
+        let mut samples = vec![ChannelSamples::default(); channels.len()];
+        for (message, indices) in by_message {
+            let message_channels: Vec<_> = indices.iter().map(|&index| channels[index].clone()).collect();
+            let read = cache.read_channels(&manifest, message, &message_channels, start, end, sender_id)?;
+            for (index, channel_samples) in indices.into_iter().zip(read) {
+                samples[index] = channel_samples;
+            }
+        }
+        Ok(samples)
+    }).await?
+}
+
+/// Record the processing status of a pair and publish it to the owner, unless the job was
+/// cancelled or taken over meanwhile. Returns whether the status was recorded.
+async fn update_status(
+    state: &AppState,
+    scope: &EventScope,
+    job: &ProcessingJob,
+    status: ProcessingStatus,
+    error: Option<&str>,
+) -> Result<bool, sqlx::Error> {
+    let Some(mut tx) = state.file_processor.lock_lease(job.id).await? else {
+        info!("Job {} no longer holds its lease, {} status not recorded", job.id, status);
+        return Ok(false);
+    };
+    set_status(&mut *tx, scope.file_id, scope.file_pair_id, status, error).await?;
+    tx.commit().await?;
+    let event = EventKind::Status { status, attempt: Some(job.attempts), error: error.map(str::to_string) };
+    state.events.publish(scope.event(event)).await;
+    Ok(true)
+}
+
+/// Start the background workers on the `processing_jobs` queue
+pub fn start_processing(state: &Arc<AppState>) {
+    let job_state = state.clone();
+    state.file_processor.start(move |job| process_file_pair(job_state.clone(), job));
+}
+
+/// Queue a file pair for parsing again
+async fn process_file(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(request): Json<ProcessFileRequest>,
+) -> Result<Json<ApiResponse<ProcessingStatusResponse>>, StatusCode> {
+    let claims = authenticate_headers(&state.db, &headers).await?;
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let paths = match resolve_owned_file_pair(&state.db, request.file_id, user_id).await {
+        Ok(paths) => paths,
+        Err(FilePairError::Database(_)) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+        Err(e) => {
+            return Ok(Json(ApiResponse {
+                success: false,
+                data: None,
+                message: e.message(),
+            }));
+        }
+    };
+
+    set_status(&state.db, paths.data_file_id, paths.file_pair_id, ProcessingStatus::Pending, None).await
+        .map_err(|e| {
+            error!("Database error queuing {}: {}", request.file_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    state.file_processor.enqueue(paths.data_file_id, request.priority.unwrap_or(0)).await
+        .map_err(|e| {
+            error!("Database error queuing {}: {}", request.file_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    get_processing_status(State(state), headers, Path(paths.data_file_id)).await
+}
+
+/// Processing state of a file
+async fn get_processing_status(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(file_id): Path<Uuid>,
+) -> Result<Json<ApiResponse<ProcessingStatusResponse>>, StatusCode> {
+    let claims = authenticate_headers(&state.db, &headers).await?;
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let record = sqlx::query!(
+        r#"
+        SELECT COALESCE(processing_status, 'pending') AS "status!", processing_error, upload_timestamp,
+               processing_started_at, processing_completed_at, metadata->'ingestion' AS ingestion
+        FROM log_files WHERE id = $1 AND user_id = $2
+        "#,
+        file_id,
+        user_id
+    ).fetch_optional(&state.db).await.map_err(|e| {
+        error!("Database error reading processing status of {}: {}", file_id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let Some(record) = record else {
+        return Ok(Json(ApiResponse {
+            success: false,
+            data: None,
+            message: "File not found".to_string(),
+        }));
+    };
+    let job = state.file_processor.active_job(file_id).await.map_err(|e| {
+        error!("Database error reading the processing job of {}: {}", file_id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let status = record.status.parse().unwrap_or(ProcessingStatus::Pending);
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("File is {}", status),
+        data: Some(ProcessingStatusResponse {
+            file_id,
+            status,
+            job,
+            ingestion: record.ingestion.and_then(|ingestion| serde_json::from_value(ingestion).ok()),
+            uploaded_at: record.upload_timestamp,
+            started_at: record.processing_started_at,
+            completed_at: record.processing_completed_at,
+            error_message: record.processing_error,
+        }),
+    }))
+}
+
+/// Processing jobs of the files of the authenticated user, most recently updated first
+async fn list_processing_jobs(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<ProcessingJobsQuery>,
+) -> Result<Json<ApiResponse<Vec<ProcessingJob>>>, StatusCode> {
+    let claims = authenticate_headers(&state.db, &headers).await?;
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let jobs = state.file_processor.list_jobs(user_id, query.status, query.limit.unwrap_or(100).clamp(1, 1000)).await
+        .map_err(|e| {
+            error!("Database error listing processing jobs: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("Found {} job(s)", jobs.len()),
+        data: Some(jobs),
+    }))
+}
+
+/// Cancel a queued or running job of the authenticated user; its files go back to `pending`
+async fn cancel_processing_job(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(job_id): Path<Uuid>,
+) -> Result<Json<ApiResponse<ProcessingJob>>, StatusCode> {
+    let claims = authenticate_headers(&state.db, &headers).await?;
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let job = state.file_processor.cancel(job_id, user_id).await.map_err(|e| {
+        error!("Database error cancelling job {}: {}", job_id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    update_job_files(&state, job, ProcessingStatus::Pending, "Job cancelled", "No queued or running job with this id").await
+}
+
+/// Queue a dead or cancelled job of the authenticated user again
+async fn retry_processing_job(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(job_id): Path<Uuid>,
+) -> Result<Json<ApiResponse<ProcessingJob>>, StatusCode> {
+    let claims = authenticate_headers(&state.db, &headers).await?;
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let job = state.file_processor.retry(job_id, user_id).await.map_err(|e| {
+        error!("Database error retrying job {}: {}", job_id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    update_job_files(&state, job, ProcessingStatus::Pending, "Job queued",
+        "No dead or cancelled job with this id, or its files are already queued").await
+}
+
+/// Move the files of a job that changed state to `status`
+async fn update_job_files(
+    state: &AppState,
+    job: Option<ProcessingJob>,
+    status: ProcessingStatus,
+    message: &str,
+    not_found: &str,
+) -> Result<Json<ApiResponse<ProcessingJob>>, StatusCode> {
+    let Some(job) = job else {
+        return Ok(Json(ApiResponse {
+            success: false,
+            data: None,
+            message: not_found.to_string(),
+        }));
+    };
+    set_status(&state.db, job.file_id, job.file_pair_id, status, None).await.map_err(|e| {
+        error!("Database error updating the files of job {}: {}", job.id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if let Ok(paths) = resolve_file_pair(&state.db, job.file_id).await {
+        let event = EventKind::Status { status, attempt: None, error: None };
+        state.events.publish(paths.event_scope(Some(job.id)).event(event)).await;
+    }
+    Ok(Json(ApiResponse {
+        success: true,
+        message: message.to_string(),
+        data: Some(job),
+    }))
+}
+
+#[derive(Serialize)]
+pub struct EventTicketResponse {
+    /// Single-use ticket to pass as `/api/events?ticket=`
+    pub ticket: String,
+    pub expires_in: i64,
+}
+
+/// Ticket opening one event stream, for clients such as `EventSource` that cannot set an `Authorization` header
+async fn issue_event_ticket(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<EventTicketResponse>>, StatusCode> {
+    let claims = authenticate_headers(&state.db, &headers).await?;
+    let ticket = issue_ticket(&state.db, &claims).await?;
+    Ok(Json(ApiResponse {
+        success: true,
+        message: "Event ticket issued".to_string(),
+        data: Some(EventTicketResponse { ticket, expires_in: TICKET_LIFETIME_SECONDS }),
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct EventsQuery {
+    /// Ticket from `/api/events/ticket`, when the request has no `Authorization` header
+    pub ticket: Option<String>,
+    /// Events of the pair of this file only
+    pub file_id: Option<Uuid>,
+}
+
+/// Live processing events of the files of the authenticated user, as server-sent events
+async fn processing_events(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<EventsQuery>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, StatusCode> {
+    let user_id = match query.ticket.as_deref() {
+        Some(ticket) if !headers.contains_key(header::AUTHORIZATION) => redeem_ticket(&state.db, ticket).await?,
+        _ => {
+            let claims = authenticate_headers(&state.db, &headers).await?;
+            Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?
+        }
+    };
+
+    let pair_key = match query.file_id {
+        Some(file_id) => {
+            let record = sqlx::query!(
+                r#"SELECT COALESCE(file_pair_id, id) AS "pair_key!" FROM log_files WHERE id = $1 AND user_id = $2"#,
+                file_id,
+                user_id
+            ).fetch_optional(&state.db).await.map_err(|e| {
+                error!("Database error subscribing to the events of {}: {}", file_id, e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+            Some(record.ok_or(StatusCode::NOT_FOUND)?.pair_key)
+        }
+        None => None,
+    };
+
+    let events = BroadcastStream::new(state.events.subscribe()).filter_map(move |event| match event {
+        Ok(event) if event.scope.user_id == user_id && pair_key.is_none_or(|key| key == event.scope.pair_key()) => {
+            Some(Event::default().event(event.kind.name()).json_data(&event))
+        }
+        Ok(_) => None,
+        // The client fell behind; it should reload the status of its files
+        Err(BroadcastStreamRecvError::Lagged(missed)) => Some(Ok(Event::default().event("lagged").data(missed.to_string()))),
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+#[derive(Deserialize)]
+pub struct TimeSeriesQuery {
+    /// Comma-separated channels, e.g. `GPS_INT.alt,IMU_GYRO_RAW.gp[0]`
+    pub channels: String,
+    pub start: Option<f64>,
+    pub end: Option<f64>,
+    /// Only return values sent by this aircraft
+    pub sender_id: Option<u8>,
+    /// Unit system of the returned values (`raw`, `alt` or `si`)
+    #[serde(default)]
+    pub units: UnitSystem,
+    /// Return timestamps as unix time (UTC) instead of seconds since the start of the log
+    #[serde(default)]
+    pub utc: bool,
+}
+
+#[derive(Serialize)]
+pub struct ChannelSeries {
+    pub channel: String,
+    pub unit: Option<String>,
+    pub timestamps: Vec<f64>,
+    pub values: Vec<f64>,
+}
+
+#[derive(Deserialize)]
+pub struct ExportQuery {
+    /// Comma-separated message names to export; all messages when omitted
+    pub messages: Option<String>,
+    pub start: Option<f64>,
+    pub end: Option<f64>,
+    /// Only export messages sent by this aircraft
+    pub sender_id: Option<u8>,
+    /// Unit system of the exported values (`raw`, `alt` or `si`)
+    #[serde(default)]
+    pub units: UnitSystem,
+}
+
+/// Get numeric time series for a set of channels of a parsed file pair
+async fn get_time_series(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(file_id): Path<Uuid>,
+    Query(query): Query<TimeSeriesQuery>,
+) -> Result<Json<ApiResponse<Vec<ChannelSeries>>>, StatusCode> {
+    let claims = authenticate_headers(&state.db, &headers).await?;
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let channels = match query.channels.split(',')
+        .map(|c| c.trim().parse::<ChannelRef>())
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(channels) => channels,
+        Err(e) => {
+            return Ok(Json(ApiResponse {
+                success: false,
+                data: None,
+                message: e.to_string(),
+            }));
+        }
+    };
+
+    let paths = match resolve_owned_file_pair(&state.db, file_id, user_id).await {
+        Ok(paths) => paths,
+        Err(FilePairError::Database(_)) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+        Err(e) => {
+            return Ok(Json(ApiResponse {
+                success: false,
+                data: None,
+                message: e.message(),
+            }));
+        }
+    };
+
+    let configuration = load_configuration(&state, &paths).await
+        .map_err(|e| {
+            error!("Failed to parse file pair for {}: {}", file_id, e);
+            StatusCode::UNPROCESSABLE_ENTITY
+        })?;
+
+    // Unix time of the log start, to shift the timestamps by
+    let utc_start = if query.utc {
+        let anchor = load_time_anchor(&state, &paths, &configuration).await.map_err(|e| {
+            error!("Failed to anchor timestamps of {}: {}", file_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        let Some(start) = anchor.start else {
+            return Ok(Json(ApiResponse {
+                success: false,
+                data: None,
+                message: "No UTC time for this log: no .log file and no GPS fix, only the local time of the file name".to_string(),
+            }));
+        };
+        Some(start)
+    } else {
+        None
+    };
+
+    let conversions: Vec<_> = channels.iter()
+        .map(|channel| configuration.protocol.get(&channel.message)
+            .and_then(|m| m.field(&channel.field))
+            .map(|f| f.conversion(query.units)))
+        .collect();
+
+    let mut series: Vec<ChannelSeries> = channels.iter()
+        .zip(&conversions)
+        .map(|(channel, conversion)| ChannelSeries {
+            channel: channel.to_string(),
+            unit: conversion.as_ref().and_then(|c| c.unit.clone()),
+            timestamps: Vec::new(),
+            values: Vec::new(),
+        })
+        .collect();
+
+    match read_columnar_channels(&state, &paths, &configuration, &channels, &query).await {
+        Ok(samples) => {
+            for ((entry, conversion), samples) in series.iter_mut().zip(&conversions).zip(samples) {
+                entry.timestamps = samples.timestamps.into_iter()
+                    .map(|timestamp| timestamp + utc_start.unwrap_or(0.0))
+                    .collect();
+                entry.values = samples.values.into_iter()
+                    .map(|value| conversion.as_ref().map_or(value, |c| c.apply(value)))
+                    .collect();
+            }
+        }
+        Err(e) => {
+            warn!("Columnar cache of {} unavailable, streaming the .data file: {}", file_id, e);
+            let mut stream = state.schema_manager.lock().await
+                .stream_telemetry_data(&paths.data_path, &configuration)
+                .map_err(|e| {
+                    error!("Failed to parse file pair for {}: {}", file_id, e);
+                    StatusCode::UNPROCESSABLE_ENTITY
+                })?;
+            while let Some(message) = stream.next().await {
+                let message = message.map_err(|e| {
+                    error!("Failed to read data file for {}: {}", file_id, e);
+                    StatusCode::INTERNAL_SERVER_ERROR
+                })?;
+                if query.start.is_some_and(|start| message.timestamp < start)
+                    || query.end.is_some_and(|end| message.timestamp > end)
+                    || query.sender_id.is_some_and(|sender_id| message.sender_id != sender_id)
+                {
+                    continue;
+                }
+                for ((channel, conversion), entry) in channels.iter().zip(&conversions).zip(series.iter_mut()) {
+                    if channel.message != message.message_name {
+                        continue;
+                    }
+                    if let Some(value) = channel.resolve(&message.fields).and_then(|v| v.as_f64()) {
+                        entry.timestamps.push(message.timestamp + utc_start.unwrap_or(0.0));
+                        entry.values.push(conversion.as_ref().map_or(value, |c| c.apply(value)));
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(Json(ApiResponse {
+        success: true,
+        data: Some(series),
+        message: format!("Retrieved {} channel(s) from {}", channels.len(), paths.original_filename),
+    }))
+}
+
+/// List the aircraft that sent messages in a file pair, with per-aircraft statistics
+async fn get_aircraft(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(file_id): Path<Uuid>,
+) -> Result<Json<ApiResponse<Vec<AircraftStatistics>>>, StatusCode> {
+    let claims = authenticate_headers(&state.db, &headers).await?;
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let paths = match resolve_owned_file_pair(&state.db, file_id, user_id).await {
+        Ok(paths) => paths,
+        Err(FilePairError::Database(_)) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+        Err(e) => {
+            return Ok(Json(ApiResponse {
+                success: false,
+                data: None,
+                message: e.message(),
+            }));
+        }
+    };
+
+    let (configuration, mut stream) = open_telemetry_stream(&state, &paths).await
+        .map_err(|e| {
+            error!("Failed to parse file pair for {}: {}", file_id, e);
+            StatusCode::UNPROCESSABLE_ENTITY
+        })?;
+
+    let (_, per_aircraft) = LogStatistics::from_stream_by_aircraft(&mut stream, &configuration).await
+        .map_err(|e| {
+            error!("Failed to read data file for {}: {}", file_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("Found {} aircraft in {}", per_aircraft.len(), paths.original_filename),
+        data: Some(per_aircraft),
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct AirframeQuery {
+    /// Airframe of this aircraft; all aircraft seen in the .data file when omitted
+    pub sender_id: Option<u8>,
+}
+
+#[derive(Serialize)]
+pub struct AircraftAirframe {
+    pub sender_id: u8,
+    pub aircraft: AircraftInfo,
+    pub airframe: AirframeConfig,
+}
+
+/// Load the configuration of a file pair with the aircraft a request applies to:
+/// the requested sender, or every sender seen in the .data file
+async fn resolve_senders(
+    state: &AppState,
+    paths: &FilePairPaths,
+    file_id: Uuid,
+    sender_id: Option<u8>,
+) -> Result<(LogConfiguration, Vec<u8>), StatusCode> {
+    match sender_id {
+        Some(sender_id) => {
+            let configuration = load_configuration(state, paths).await
+                .map_err(|e| {
+                    error!("Failed to parse log file for {}: {}", file_id, e);
+                    StatusCode::UNPROCESSABLE_ENTITY
+                })?;
+            Ok((configuration, vec![sender_id]))
+        }
+        None => {
+            let (configuration, mut stream) = open_telemetry_stream(state, paths).await
+                .map_err(|e| {
+                    error!("Failed to parse file pair for {}: {}", file_id, e);
+                    StatusCode::UNPROCESSABLE_ENTITY
+                })?;
+            let (_, per_aircraft) = LogStatistics::from_stream_by_aircraft(&mut stream, &configuration).await
+                .map_err(|e| {
+                    error!("Failed to read data file for {}: {}", file_id, e);
+                    StatusCode::INTERNAL_SERVER_ERROR
+                })?;
+            let senders = per_aircraft.iter().map(|a| a.sender_id).collect();
+            Ok((configuration, senders))
+        }
+    }
+}
+
+/// Get the airframe configuration (firmware, servos, defines by section) of the aircraft in a file pair
+async fn get_airframe(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(file_id): Path<Uuid>,
+    Query(query): Query<AirframeQuery>,
+) -> Result<Json<ApiResponse<Vec<AircraftAirframe>>>, StatusCode> {
+    let claims = authenticate_headers(&state.db, &headers).await?;
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let paths = match resolve_owned_file_pair(&state.db, file_id, user_id).await {
+        Ok(paths) => paths,
+        Err(FilePairError::Database(_)) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+        Err(e) => {
+            return Ok(Json(ApiResponse {
+                success: false,
+                data: None,
+                message: e.message(),
+            }));
+        }
+    };
+
+    let (configuration, senders) = resolve_senders(&state, &paths, file_id, query.sender_id).await?;
+
+    let airframes: Vec<AircraftAirframe> = senders.into_iter()
+        .filter_map(|sender_id| Some(AircraftAirframe {
+            sender_id,
+            aircraft: configuration.aircraft_for_sender(sender_id)?.clone(),
+            airframe: configuration.airframe_for_sender(sender_id)?.clone(),
+        }))
+        .collect();
+
+    if airframes.is_empty() {
+        return Ok(Json(ApiResponse {
+            success: false,
+            data: None,
+            message: "No airframe configuration found for the requested aircraft".to_string(),
+        }));
+    }
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("Retrieved {} airframe(s) from {}", airframes.len(), paths.original_filename),
+        data: Some(airframes),
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct FlightPlanQuery {
+    /// Flight plan of this aircraft; all aircraft seen in the .data file when omitted
+    pub sender_id: Option<u8>,
+    /// `geojson` for a FeatureCollection the map can draw directly
+    pub format: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct AircraftFlightPlan {
+    pub sender_id: u8,
+    pub aircraft: AircraftInfo,
+    pub flight_plan: FlightPlan,
+}
+
+/// Get the flight plan (origin, waypoints resolved to lat/lon, blocks) of the aircraft in a file pair
+async fn get_flight_plan(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(file_id): Path<Uuid>,
+    Query(query): Query<FlightPlanQuery>,
+) -> Result<Response, StatusCode> {
+    let claims = authenticate_headers(&state.db, &headers).await?;
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let paths = match resolve_owned_file_pair(&state.db, file_id, user_id).await {
+        Ok(paths) => paths,
+        Err(FilePairError::Database(_)) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+        Err(e) => {
+            return Ok(Json(ApiResponse::<()> {
+                success: false,
+                data: None,
+                message: e.message(),
+            }).into_response());
+        }
+    };
+
+    let (configuration, senders) = resolve_senders(&state, &paths, file_id, query.sender_id).await?;
+
+    let flight_plans: Vec<AircraftFlightPlan> = senders.into_iter()
+        .filter_map(|sender_id| Some(AircraftFlightPlan {
+            sender_id,
+            aircraft: configuration.aircraft_for_sender(sender_id)?.clone(),
+            flight_plan: configuration.flight_plan_for_sender(sender_id)?.clone(),
+        }))
+        .collect();
+
+    if flight_plans.is_empty() {
+        return Ok(Json(ApiResponse::<()> {
+            success: false,
+            data: None,
+            message: "No flight plan found for the requested aircraft".to_string(),
+        }).into_response());
+    }
+
+    if query.format.as_deref().is_some_and(|f| f.eq_ignore_ascii_case("geojson")) {
+        // One collection for all aircraft, each feature tagged with its sender
+        let mut features = Vec::new();
+        for entry in &flight_plans {
+            let serde_json::Value::Object(mut collection) = entry.flight_plan.to_geojson() else {
+                continue;
+            };
+            if let Some(serde_json::Value::Array(plan_features)) = collection.remove("features") {
+                for mut feature in plan_features {
+                    if let Some(properties) = feature.get_mut("properties").and_then(|p| p.as_object_mut()) {
+                        properties.insert("sender_id".to_string(), entry.sender_id.into());
+                    }
+                    features.push(feature);
+                }
+            }
+        }
+        let collection = serde_json::json!({
+            "type": "FeatureCollection",
+            "features": features,
+        });
+        return Ok((
+            [(header::CONTENT_TYPE, "application/geo+json")],
+            collection.to_string(),
+        ).into_response());
+    }
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("Retrieved {} flight plan(s) from {}", flight_plans.len(), paths.original_filename),
+        data: Some(flight_plans),
+    }).into_response())
+}
+
+#[derive(Deserialize)]
+pub struct SettingChangesQuery {
+    /// Changes of this aircraft only
+    pub sender_id: Option<u8>,
+}
+
+/// List every in-flight setting change of a file pair, with old and new value
+async fn get_setting_changes(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(file_id): Path<Uuid>,
+    Query(query): Query<SettingChangesQuery>,
+) -> Result<Json<ApiResponse<Vec<SettingChange>>>, StatusCode> {
+    let claims = authenticate_headers(&state.db, &headers).await?;
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let paths = match resolve_owned_file_pair(&state.db, file_id, user_id).await {
+        Ok(paths) => paths,
+        Err(FilePairError::Database(_)) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+        Err(e) => {
+            return Ok(Json(ApiResponse {
+                success: false,
+                data: None,
+                message: e.message(),
+            }));
+        }
+    };
+
+    let (configuration, mut stream) = open_telemetry_stream(&state, &paths).await
+        .map_err(|e| {
+            error!("Failed to parse file pair for {}: {}", file_id, e);
+            StatusCode::UNPROCESSABLE_ENTITY
+        })?;
+
+    let mut tracker = SettingChangeTracker::new(&configuration.settings);
+    while let Some(message) = stream.next().await {
+        let message = message.map_err(|e| {
+            error!("Failed to read data file for {}: {}", file_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        tracker.add(&message);
+    }
+
+    let changes: Vec<SettingChange> = tracker.finish().into_iter()
+        .filter(|change| query.sender_id.is_none_or(|sender_id| change.sender_id == sender_id))
+        .collect();
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("Found {} setting change(s) in {}", changes.len(), paths.original_filename),
+        data: Some(changes),
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct DiagnosticsQuery {
+    /// Diagnostics of this kind only
+    pub kind: Option<DiagnosticKind>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct FileDiagnosticsResponse {
+    /// The .data file the diagnostics belong to
+    pub file_id: Uuid,
+    pub total: usize,
+    pub counts: BTreeMap<DiagnosticKind, usize>,
+    /// Whether more diagnostics were counted than stored
+    pub truncated: bool,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Line-level diagnostics of the .data file of a pair, collected when it was last processed
+async fn get_file_diagnostics(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(file_id): Path<Uuid>,
+    Query(query): Query<DiagnosticsQuery>,
+) -> Result<Json<ApiResponse<FileDiagnosticsResponse>>, StatusCode> {
+    let claims = authenticate_headers(&state.db, &headers).await?;
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let paths = match resolve_owned_file_pair(&state.db, file_id, user_id).await {
+        Ok(paths) => paths,
+        Err(FilePairError::Database(_)) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+        Err(e) => {
+            return Ok(Json(ApiResponse {
+                success: false,
+                data: None,
+                message: e.message(),
+            }));
+        }
+    };
+
+    let stored = sqlx::query_scalar!(
+        "SELECT diagnostic_counts FROM log_files WHERE id = $1 AND diagnostics_updated_at IS NOT NULL",
+        paths.data_file_id
+    ).fetch_optional(&state.db).await
+     .map_err(|e| {
+         error!("Database error reading diagnostics of {}: {}", file_id, e);
+         StatusCode::INTERNAL_SERVER_ERROR
+     })?;
+
+    // Diagnostics are collected by the processing job; until it has run there are none to report
+    let Some(counts) = stored else {
+        return Ok(Json(ApiResponse {
+            success: false,
+            data: None,
+            message: format!("{} has not been processed yet", paths.original_filename),
+        }));
+    };
+    let counts: BTreeMap<DiagnosticKind, usize> = counts
+        .and_then(|counts| serde_json::from_value(counts).ok())
+        .unwrap_or_default();
+
+    let records = sqlx::query!(
+        r#"
+        SELECT line_number, byte_offset, kind, message, snippet, suggestion
+        FROM parse_diagnostics
+        WHERE file_id = $1 AND ($2::varchar IS NULL OR kind = $2)
+        ORDER BY line_number, id
+        LIMIT $3 OFFSET $4
+        "#,
+        paths.data_file_id,
+        query.kind.map(|kind| kind.as_str()),
+        query.limit.unwrap_or(100).clamp(1, 1000),
+        query.offset.unwrap_or(0).max(0)
+    ).fetch_all(&state.db).await
+     .map_err(|e| {
+         error!("Database error reading diagnostics of {}: {}", file_id, e);
+         StatusCode::INTERNAL_SERVER_ERROR
+     })?;
+    let stored_count = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!" FROM parse_diagnostics WHERE file_id = $1"#,
+        paths.data_file_id
+    ).fetch_one(&state.db).await
+     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let diagnostics: Vec<Diagnostic> = records.into_iter()
+        .filter_map(|record| Some(Diagnostic {
+            line: record.line_number as usize,
+            byte_offset: record.byte_offset as u64,
+            kind: record.kind.parse().ok()?,
+            message: record.message,
+            snippet: record.snippet,
+            suggestion: record.suggestion,
+        }))
+        .collect();
+    let total = counts.values().sum();
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("{} diagnostic(s) in {}", total, paths.original_filename),
+        data: Some(FileDiagnosticsResponse {
+            file_id: paths.data_file_id,
+            total,
+            truncated: (stored_count as usize) < total,
+            counts,
+            diagnostics,
+        }),
+    }))
+}
+
+/// Export decoded messages as CSV, one row per scalar channel value
+async fn export_csv(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(file_id): Path<Uuid>,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, StatusCode> {
+    let claims = authenticate_headers(&state.db, &headers).await?;
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let paths = match resolve_owned_file_pair(&state.db, file_id, user_id).await {
+        Ok(paths) => paths,
+        Err(FilePairError::Database(_)) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+        Err(_) => return Err(StatusCode::NOT_FOUND),
+    };
+
+    let (configuration, stream) = open_telemetry_stream(&state, &paths).await
+        .map_err(|e| {
+            error!("Failed to parse file pair for {}: {}", file_id, e);
+            StatusCode::UNPROCESSABLE_ENTITY
+        })?;
+
+    let anchor = load_time_anchor(&state, &paths, &configuration).await
+        .map_err(|e| {
+            error!("Failed to anchor timestamps of {}: {}", file_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let selected: Option<Vec<String>> = query.messages.as_deref()
+        .map(|m| m.split(',').map(|name| name.trim().to_string()).collect());
+    let protocol = configuration.protocol;
+    let units = query.units;
+
+    // Rows are rendered as messages are decoded, so the CSV is never held in memory
+    let rows = stream.filter_map(move |message| {
+        let message = match message {
+            Ok(message) => message,
+            Err(e) => return Some(Err(e)),
+        };
+        if query.start.is_some_and(|start| message.timestamp < start)
+            || query.end.is_some_and(|end| message.timestamp > end)
+            || query.sender_id.is_some_and(|sender_id| message.sender_id != sender_id)
+            || selected.as_ref().is_some_and(|s| !s.contains(&message.message_name))
+        {
+            return None;
+        }
+        let definition = protocol.get(&message.message_name)?;
+
+        let mut rows = String::new();
+        // Array fields are exported as indexed sub-channels (e.g. gp[0])
+        for (channel, field, value) in definition.channels(&message.fields) {
+            let conversion = field.conversion(units);
+            // Non-numeric values (strings) are exported unconverted
+            let value = match value.as_f64() {
+                Some(number) if !conversion.is_identity() => conversion.apply(number).to_string(),
+                _ => value.to_string(),
+            };
+            rows.push_str(&format!(
+                "{},{},{},{},{},{},{}\n",
+                message.timestamp,
+                anchor.utc(message.timestamp).map(|t| t.to_rfc3339_opts(SecondsFormat::Millis, true)).unwrap_or_default(),
+                message.sender_id,
+                message.message_name,
+                channel,
+                csv_escape(&value),
+                csv_escape(conversion.unit.as_deref().unwrap_or(""))
+            ));
+        }
+        Some(Ok(rows))
+    });
+    let csv = tokio_stream::once(Ok("timestamp,utc,sender_id,message,channel,value,unit\n".to_string()))
+        .chain(rows);
+
+    let filename = format!("{}.csv", StdPath::new(&paths.original_filename)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("export"));
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        Body::from_stream(csv),
+    ).into_response())
+}
+
+#[derive(Serialize)]
+pub struct SchemaArtifactInfo {
+    pub id: Uuid,
+    pub kind: String,
+    pub name: String,
+    pub version: i32,
+    pub content_hash: String,
+    pub file_size: i64,
+    pub summary: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Upload a messages.xml, airframe or flight plan file as a versioned schema artifact.
+///
+/// Multipart fields: `kind` (`messages`, `airframe`, `flight_plan`), optional `name`
+/// (defaults to the file name) and `file`. Uploading content that is already stored
+/// returns the existing artifact; new content under an existing name gets the next version.
+async fn upload_schema_artifact(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    mut multipart: Multipart,
+) -> Result<Json<ApiResponse<SchemaArtifactInfo>>, StatusCode> {
+    let claims = authenticate_headers(&state.db, &headers).await?;
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+    let mut kind = None;
+    let mut name = None;
+    let mut upload = None;
+    while let Some(field) = multipart.next_field().await.map_err(|e| {
+        error!("Failed to get next multipart field: {}", e);
+        StatusCode::BAD_REQUEST
+    })? {
+        match field.name().unwrap_or("") {
+            "kind" => kind = Some(field.text().await.map_err(|_| StatusCode::BAD_REQUEST)?),
+            "name" => name = Some(field.text().await.map_err(|_| StatusCode::BAD_REQUEST)?),
+            "file" => {
+                let filename = field.file_name().unwrap_or("unknown").to_string();
+                let content = field.bytes().await.map_err(|_| StatusCode::BAD_REQUEST)?;
+                upload = Some((filename, content));
+            }
+            _ => {}
+        }
+    }
+
+    let failure = |message: String| Ok(Json(ApiResponse {
+        success: false,
+        data: None,
+        message,
+    }));
+    let kind = match kind.as_deref().map(str::parse::<ArtifactKind>) {
+        Some(Ok(kind)) => kind,
+        Some(Err(e)) => return failure(e.to_string()),
+        None => return failure("Missing 'kind' field".to_string()),
+    };
+    let Some((filename, content)) = upload else {
+        return failure("Missing 'file' field".to_string());
+    };
+    let name = name.filter(|n| !n.trim().is_empty()).unwrap_or(filename);
+
+    // Parsing validates the upload and puts it in the registry
+    let (content_hash, summary) = match state.schema_manager.lock().await.register_artifact(kind, &content) {
+        Ok((hash, artifact)) => (hash, artifact.summary()),
+        Err(e) => return failure(format!("Invalid {} file: {}", kind, e)),
+    };
+
+    let existing = sqlx::query_as!(
+        SchemaArtifactInfo,
+        "SELECT id, kind, name, version, content_hash, file_size, summary, created_at FROM schema_artifacts
+         WHERE user_id = $1 AND kind = $2 AND content_hash = $3",
+        user_id,
+        kind.as_str(),
+        content_hash
+    ).fetch_optional(&state.db).await.map_err(|e| {
+        error!("Database error looking up artifact: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if let Some(artifact) = existing {
+        return Ok(Json(ApiResponse {
+            success: true,
+            message: format!("{} v{} already has this content", artifact.name, artifact.version),
+            data: Some(artifact),
+        }));
+    }
+
+    // Artifacts are stored by content, so identical uploads share a file
+    let artifact_dir = state.upload_dir.join("artifacts");
+    let storage_path = artifact_dir.join(format!("{}.xml", content_hash)).to_string_lossy().to_string();
+    let stored = async {
+        fs::create_dir_all(&artifact_dir).await?;
+        fs::write(&storage_path, &content).await
+    };
+    if let Err(e) = stored.await {
+        error!("Failed to store artifact {}: {}", name, e);
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    let artifact = sqlx::query_as!(
+        SchemaArtifactInfo,
+        r#"
+        INSERT INTO schema_artifacts (user_id, kind, name, version, content_hash, file_size, storage_path, summary)
+        VALUES ($1, $2::varchar, $3::varchar,
+            (SELECT COALESCE(MAX(version), 0) + 1 FROM schema_artifacts WHERE user_id = $1 AND kind = $2::varchar AND name = $3::varchar),
+            $4, $5, $6, $7)
+        RETURNING id, kind, name, version, content_hash, file_size, summary, created_at
+        "#,
+        user_id,
+        kind.as_str(),
+        name,
+        content_hash,
+        content.len() as i64,
+        storage_path,
+        summary
+    ).fetch_one(&state.db).await.map_err(|e| {
+        error!("Database error storing artifact {}: {}", name, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    info!("Stored {} artifact {} v{} ({})", kind, artifact.name, artifact.version, content_hash);
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("Stored {} v{}", artifact.name, artifact.version),
+        data: Some(artifact),
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct ArtifactListQuery {
+    pub kind: Option<String>,
+}
+
+/// List the schema artifacts of the authenticated user, newest first
+async fn list_schema_artifacts(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<ArtifactListQuery>,
+) -> Result<Json<ApiResponse<Vec<SchemaArtifactInfo>>>, StatusCode> {
+    let claims = authenticate_headers(&state.db, &headers).await?;
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let artifacts = sqlx::query_as!(
+        SchemaArtifactInfo,
+        "SELECT id, kind, name, version, content_hash, file_size, summary, created_at FROM schema_artifacts
+         WHERE user_id = $1 AND ($2::text IS NULL OR kind = $2)
+         ORDER BY created_at DESC",
+        user_id,
+        query.kind
+    ).fetch_all(&state.db).await.map_err(|e| {
+        error!("Database error retrieving artifacts: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("Found {} artifact(s)", artifacts.len()),
+        data: Some(artifacts),
+    }))
+}
+
+#[derive(Serialize)]
+pub struct SchemaInfo {
+    pub id: Uuid,
+    pub schema_hash: String,
+    pub message_count: i32,
+    pub first_seen_at: chrono::DateTime<chrono::Utc>,
+    /// Flights (file pairs, or lone .data files) decoded with this schema
+    pub flight_count: i64,
+}
+
+/// List the distinct protocols files were decoded with, newest first
+async fn list_schemas(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ApiResponse<Vec<SchemaInfo>>>, StatusCode> {
+    let schemas = sqlx::query_as!(
+        SchemaInfo,
+        r#"
+        SELECT s.id, s.schema_hash, s.message_count, s.first_seen_at,
+               COUNT(DISTINCT COALESCE(f.file_pair_id, f.id)) AS "flight_count!"
+        FROM schemas s
+        LEFT JOIN log_files f ON f.schema_id = s.id
+        GROUP BY s.id
+        ORDER BY s.first_seen_at DESC
+        "#
+    ).fetch_all(&state.db).await.map_err(|e| {
+        error!("Database error retrieving schemas: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("Found {} schema(s)", schemas.len()),
+        data: Some(schemas),
+    }))
+}
+
+#[derive(Serialize)]
+pub struct SchemaFlight {
+    pub file_pair_id: Option<Uuid>,
+    pub file_ids: Vec<Uuid>,
+    pub filenames: Vec<String>,
+    pub log_start_utc: Option<chrono::DateTime<chrono::Utc>>,
+    pub uploaded_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Flights of the authenticated user decoded with a given schema, most recent first
+async fn list_schema_flights(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(schema_hash): Path<String>,
+) -> Result<Json<ApiResponse<Vec<SchemaFlight>>>, StatusCode> {
+    let claims = authenticate_headers(&state.db, &headers).await?;
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let flights = sqlx::query_as!(
+        SchemaFlight,
+        r#"
+        SELECT f.file_pair_id,
+               ARRAY_AGG(f.id ORDER BY f.original_filename) AS "file_ids!",
+               ARRAY_AGG(f.original_filename ORDER BY f.original_filename) AS "filenames!: Vec<String>",
+               MAX(f.log_start_utc) AS log_start_utc,
+               MIN(f.upload_timestamp) AS "uploaded_at!"
+        FROM log_files f
+        JOIN schemas s ON s.id = f.schema_id
+        WHERE s.schema_hash = $1 AND f.user_id = $2
+        GROUP BY COALESCE(f.file_pair_id, f.id), f.file_pair_id
+        ORDER BY MAX(f.log_start_utc) DESC NULLS LAST, MIN(f.upload_timestamp) DESC
+        "#,
+        schema_hash,
+        user_id
+    ).fetch_all(&state.db).await.map_err(|e| {
+        error!("Database error retrieving flights of schema {}: {}", schema_hash, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("Found {} flight(s) using schema {}", flights.len(), schema_hash),
+        data: Some(flights),
     }))
 }
 
-async fn list_log_files(
-    State(state): State<Arc<AppState>>,
-) -> Result<Json<ApiResponse<Vec<LogFileInfo>>>, StatusCode> {
-    let result = sqlx::query_as!(
-        LogFileInfo,
-        r#"
-        SELECT 
-            id,
-            original_filename,
-            file_size,
-            upload_timestamp,
-            COALESCE(processing_status, 'pending') as "processing_status!",
-            COALESCE(content_type, 'application/octet-stream') as "content_type!",
-            is_processed
-        FROM log_files
-        ORDER BY upload_timestamp DESC
-        "#
-    ).fetch_all(&state.db).await;
+#[derive(Serialize)]
+pub struct BuildFlight {
+    pub flight_id: Uuid,
+    pub file_pair_id: Option<Uuid>,
+    pub filenames: Vec<String>,
+    pub log_start_utc: Option<chrono::DateTime<chrono::Utc>>,
+    pub runtime: Option<PaparazziVersion>,
+    /// Logged by a ground station of another version than the firmware
+    pub runtime_mismatch: bool,
+}
 
-    match result {
-        Ok(files) => {
-            Ok(Json(ApiResponse {
-                success: true,
-                data: Some(files),
-                message: "Files retrieved successfully".to_string(),
-            }))
-        }
-        Err(e) => {
-            error!("Database error retrieving files: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
-    }
+#[derive(Serialize)]
+pub struct FirmwareBuild {
+    /// `None` for flights whose log does not tell the build version
+    pub build: Option<PaparazziVersion>,
+    pub dirty: bool,
+    pub mismatched_flights: usize,
+    pub flights: Vec<BuildFlight>,
 }
 
-async fn get_log_file(
+/// Flights of the authenticated user grouped by the firmware build they flew, most recent first
+async fn list_firmware_builds(
     State(state): State<Arc<AppState>>,
-    axum::extract::Path(file_id): axum::extract::Path<Uuid>,
-) -> Result<Json<ApiResponse<LogFileInfo>>, StatusCode> {
-    let result = sqlx::query_as!(
-        LogFileInfo,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<Vec<FirmwareBuild>>>, StatusCode> {
+    let claims = authenticate_headers(&state.db, &headers).await?;
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let records = sqlx::query!(
         r#"
-        SELECT 
-            id,
-            original_filename,
-            file_size,
-            upload_timestamp,
-            COALESCE(processing_status, 'pending') as "processing_status!",
-            COALESCE(content_type, 'application/octet-stream') as "content_type!",
-            is_processed
+        SELECT COALESCE(file_pair_id, id) AS "flight_id!",
+               file_pair_id,
+               ARRAY_AGG(original_filename ORDER BY original_filename) AS "filenames!: Vec<String>",
+               MAX(log_start_utc) AS log_start_utc,
+               (ARRAY_AGG(metadata->'paparazzi') FILTER (WHERE metadata->'paparazzi' IS NOT NULL))[1] AS "paparazzi!"
         FROM log_files
-        WHERE id = $1
+        WHERE user_id = $1
+        GROUP BY COALESCE(file_pair_id, id), file_pair_id
+        HAVING BOOL_OR(metadata->'paparazzi' IS NOT NULL)
+        ORDER BY MAX(log_start_utc) DESC NULLS LAST
         "#,
-        file_id
-    ).fetch_optional(&state.db).await;
+        user_id
+    ).fetch_all(&state.db).await.map_err(|e| {
+        error!("Database error retrieving firmware builds: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let mut builds: Vec<FirmwareBuild> = Vec::new();
+    for record in records {
+        let provenance: BuildProvenance = serde_json::from_value(record.paparazzi).unwrap_or_default();
+        let flight = BuildFlight {
+            flight_id: record.flight_id,
+            file_pair_id: record.file_pair_id,
+            filenames: record.filenames,
+            log_start_utc: record.log_start_utc,
+            runtime_mismatch: provenance.mismatch(),
+            runtime: provenance.runtime.clone(),
+        };
+        let index = match builds.iter().position(|build| build.build == provenance.build) {
+            Some(index) => index,
+            None => {
+                builds.push(FirmwareBuild {
+                    dirty: provenance.dirty(),
+                    build: provenance.build,
+                    mismatched_flights: 0,
+                    flights: Vec::new(),
+                });
+                builds.len() - 1
+            }
+        };
+        builds[index].mismatched_flights += flight.runtime_mismatch as usize;
+        builds[index].flights.push(flight);
+    }
 
-    match result {
-        Ok(Some(file)) => {
-            Ok(Json(ApiResponse {
-                success: true,
-                data: Some(file),
-                message: "File retrieved successfully".to_string(),
-            }))
-        }
-        Ok(None) => {
-            Ok(Json(ApiResponse {
-                success: false,
-                data: None,
-                message: "File not found".to_string(),
-            }))
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("Found {} firmware build(s)", builds.len()),
+        data: Some(builds),
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct ProtocolDiffQuery {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Serialize)]
+pub struct ProtocolDiffResponse {
+    pub from_hash: String,
+    pub to_hash: String,
+    pub diff: ProtocolDiff,
+    /// Same changes as `diff`, one line each
+    pub summary: String,
+}
+
+impl ProtocolDiffResponse {
+    fn new(from: &MessageDictionary, to: &MessageDictionary) -> Self {
+        let diff = ProtocolDiff::between(from, to);
+        Self {
+            from_hash: from.schema_hash(),
+            to_hash: to.schema_hash(),
+            summary: diff.to_string(),
+            diff,
         }
-        Err(e) => {
-            error!("Database error retrieving file {}: {}", file_id, e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+/// Message definitions of a registered schema
+async fn load_schema_protocol(pool: &PgPool, schema_hash: &str) -> anyhow::Result<Option<MessageDictionary>> {
+    let protocol = sqlx::query_scalar!("SELECT protocol FROM schemas WHERE schema_hash = $1", schema_hash)
+        .fetch_optional(pool).await?;
+    match protocol {
+        Some(protocol) => Ok(Some(MessageDictionary::new(serde_json::from_value::<Vec<MessageDefinition>>(protocol)?))),
+        None => Ok(None),
+    }
+}
+
+/// Compare the message definitions of two registered schemas
+async fn diff_schemas(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ProtocolDiffQuery>,
+) -> Result<Json<ApiResponse<ProtocolDiffResponse>>, StatusCode> {
+    let mut protocols = Vec::with_capacity(2);
+    for schema_hash in [&query.from, &query.to] {
+        match load_schema_protocol(&state.db, schema_hash).await {
+            Ok(Some(protocol)) => protocols.push(protocol),
+            Ok(None) => {
+                return Ok(Json(ApiResponse {
+                    success: false,
+                    data: None,
+                    message: format!("Schema {} not found", schema_hash),
+                }));
+            }
+            Err(e) => {
+                error!("Failed to load schema {}: {}", schema_hash, e);
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            }
         }
     }
+
+    let response = ProtocolDiffResponse::new(&protocols[0], &protocols[1]);
+    Ok(Json(ApiResponse {
+        success: true,
+        message: response.summary.lines().next().unwrap_or_default().to_string(),
+        data: Some(response),
+    }))
 }
 
-async fn delete_log_file(
+/// Compare the message definitions two file pairs are decoded with
+async fn diff_files(
     State(state): State<Arc<AppState>>,
-    axum::extract::Path(file_id): axum::extract::Path<Uuid>,
-) -> Result<Json<ApiResponse<()>>, StatusCode> {
-    // Get file info first to delete from disk
-    let file_info = sqlx::query!(
-        "SELECT storage_path FROM log_files WHERE id = $1",
-        file_id
-    ).fetch_optional(&state.db).await
-     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    headers: HeaderMap,
+    Query(query): Query<ProtocolDiffQuery>,
+) -> Result<Json<ApiResponse<ProtocolDiffResponse>>, StatusCode> {
+    let claims = authenticate_headers(&state.db, &headers).await?;
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let failure = |message: String| Ok(Json(ApiResponse {
+        success: false,
+        data: None,
+        message,
+    }));
 
-    let storage_path = match file_info {
-        Some(info) => info.storage_path,
-        None => {
-            return Ok(Json(ApiResponse {
-                success: false,
-                data: None,
-                message: "File not found".to_string(),
-            }));
+    let mut protocols = Vec::with_capacity(2);
+    for file_id in [&query.from, &query.to] {
+        let Ok(file_id) = file_id.parse::<Uuid>() else {
+            return failure(format!("Invalid file id {}", file_id));
+        };
+        let paths = match resolve_owned_file_pair(&state.db, file_id, user_id).await {
+            Ok(paths) => paths,
+            Err(FilePairError::Database(_)) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+            Err(e) => return failure(e.message()),
+        };
+        match load_configuration(&state, &paths).await {
+            Ok(configuration) => protocols.push(configuration.protocol),
+            Err(e) => return failure(format!("Failed to read the protocol of {}: {}", paths.original_filename, e)),
         }
+    }
+
+    let response = ProtocolDiffResponse::new(&protocols[0], &protocols[1]);
+    Ok(Json(ApiResponse {
+        success: true,
+        message: response.summary.lines().next().unwrap_or_default().to_string(),
+        data: Some(response),
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct AttachArtifactRequest {
+    pub artifact_id: Uuid,
+    /// Aircraft an airframe or flight plan applies to; ignored for messages.xml
+    pub sender_id: Option<u8>,
+}
+
+/// Attach a schema artifact to a file pair, replacing the one of the same kind (and aircraft)
+async fn attach_artifact(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(file_id): Path<Uuid>,
+    Json(request): Json<AttachArtifactRequest>,
+) -> Result<Json<ApiResponse<Vec<ArtifactAttachment>>>, StatusCode> {
+    let claims = authenticate_headers(&state.db, &headers).await?;
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let failure = |message: String| Ok(Json(ApiResponse {
+        success: false,
+        data: None,
+        message,
+    }));
+    let paths = match resolve_owned_file_pair(&state.db, file_id, user_id).await {
+        Ok(paths) => paths,
+        Err(FilePairError::Database(_)) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+        Err(e) => return failure(e.message()),
+    };
+    let Some(file_pair_id) = paths.file_pair_id else {
+        return failure("File is not part of a file pair".to_string());
     };
 
-    // Delete from database
-    let result = sqlx::query!(
-        "DELETE FROM log_files WHERE id = $1",
-        file_id
-    ).execute(&state.db).await;
+    let artifact = sqlx::query!(
+        "SELECT kind FROM schema_artifacts WHERE id = $1 AND user_id = $2",
+        request.artifact_id,
+        user_id
+    ).fetch_optional(&state.db).await.map_err(|e| {
+        error!("Database error looking up artifact: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let Some(kind) = artifact.and_then(|a| a.kind.parse::<ArtifactKind>().ok()) else {
+        return failure("Artifact not found".to_string());
+    };
+    let sender_id = match (kind.is_per_aircraft(), request.sender_id) {
+        (true, None) => return failure(format!("A {} artifact must be attached to an aircraft (sender_id)", kind)),
+        (true, Some(sender_id)) => Some(sender_id as i16),
+        (false, _) => None,
+    };
 
-    match result {
-        Ok(rows) if rows.rows_affected() > 0 => {
-            // Delete file from disk
-            if let Err(e) = fs::remove_file(&storage_path).await {
-                error!("Failed to delete file from disk: {}", e);
-            }
-            
-            info!("Successfully deleted file: {}", file_id);
-            Ok(Json(ApiResponse {
-                success: true,
-                data: Some(()),
-                message: "File deleted successfully".to_string(),
-            }))
-        }
-        Ok(_) => {
-            Ok(Json(ApiResponse {
-                success: false,
-                data: None,
-                message: "File not found".to_string(),
-            }))
-        }
-        Err(e) => {
-            error!("Database error deleting file {}: {}", file_id, e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
+    let attached = async {
+        let mut tx = state.db.begin().await?;
+        sqlx::query!(
+            "DELETE FROM file_pair_artifacts WHERE file_pair_id = $1 AND kind = $2 AND sender_id IS NOT DISTINCT FROM $3",
+            file_pair_id,
+            kind.as_str(),
+            sender_id
+        ).execute(&mut *tx).await?;
+        sqlx::query!(
+            "INSERT INTO file_pair_artifacts (file_pair_id, artifact_id, kind, sender_id) VALUES ($1, $2, $3, $4)",
+            file_pair_id,
+            request.artifact_id,
+            kind.as_str(),
+            sender_id
+        ).execute(&mut *tx).await?;
+        invalidate_diagnostics(&mut tx, file_pair_id).await?;
+        tx.commit().await
+    };
+    if let Err(e) = attached.await {
+        error!("Database error attaching artifact to {}: {}", file_id, e);
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    // Decode the pair again with the artifact
+    if let Err(e) = state.file_processor.enqueue(file_id, 0).await {
+        warn!("Failed to queue {} for processing: {}", file_id, e);
     }
+
+    let attachments = pair_artifacts(&state.db, Some(file_pair_id)).await.map_err(|e| {
+        error!("Database error retrieving artifacts of {}: {}", file_id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("Attached {} artifact to {}", kind, paths.original_filename),
+        data: Some(attachments),
+    }))
 }
 
-/// Parse and analyze a specific log file
-async fn detect_schema(
+/// List the schema artifacts attached to a file pair
+async fn list_file_artifacts(
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
     Path(file_id): Path<Uuid>,
-) -> Result<Json<ApiResponse<SchemaDetectionResponse>>, StatusCode> {
-    // Get file path and pairing info from database
-    let file_info = sqlx::query!(
-        "SELECT storage_path, original_filename, file_pair_id, base_filename, file_extension FROM log_files WHERE id = $1",
-        file_id
-    ).fetch_optional(&state.db).await
-     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    let (storage_path, original_filename, file_pair_id, base_filename, file_extension) = match file_info {
-        Some(info) => (
-            info.storage_path, 
-            info.original_filename,
-            info.file_pair_id,
-            info.base_filename,
-            info.file_extension
-        ),
-        None => {
+) -> Result<Json<ApiResponse<Vec<ArtifactAttachment>>>, StatusCode> {
+    let claims = authenticate_headers(&state.db, &headers).await?;
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let paths = match resolve_owned_file_pair(&state.db, file_id, user_id).await {
+        Ok(paths) => paths,
+        Err(FilePairError::Database(_)) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+        Err(e) => {
             return Ok(Json(ApiResponse {
                 success: false,
                 data: None,
-                message: "File not found".to_string(),
+                message: e.message(),
             }));
         }
     };
 
-    // Find the matching file with the same file_pair_id but different extension
-    let target_extension = if file_extension == Some("log".to_string()) { "data" } else { "log" };
-    let paired_file_info = sqlx::query!(
-        "SELECT storage_path FROM log_files WHERE file_pair_id = $1 AND file_extension = $2 AND id != $3",
-        file_pair_id,
-        target_extension,
-        file_id
-    ).fetch_optional(&state.db).await
-     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let attachments = pair_artifacts(&state.db, paths.file_pair_id).await.map_err(|e| {
+        error!("Database error retrieving artifacts of {}: {}", file_id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(Json(ApiResponse {
+        success: true,
+        message: format!("{} artifact(s) attached to {}", attachments.len(), paths.original_filename),
+        data: Some(attachments),
+    }))
+}
 
-    let paired_storage_path = match paired_file_info {
-        Some(info) => info.storage_path,
-        None => {
+/// Detach a schema artifact from a file pair; the artifact itself is kept
+async fn detach_artifact(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path((file_id, artifact_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<ApiResponse<()>>, StatusCode> {
+    let claims = authenticate_headers(&state.db, &headers).await?;
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let paths = match resolve_owned_file_pair(&state.db, file_id, user_id).await {
+        Ok(paths) => paths,
+        Err(FilePairError::Database(_)) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+        Err(e) => {
             return Ok(Json(ApiResponse {
                 success: false,
                 data: None,
-                message: format!("Matching .{} file not found for pair", target_extension),
+                message: e.message(),
             }));
         }
     };
 
-    // Parse the log file using our schema manager with explicit log/data paths
-    let mut schema_manager = state.schema_manager.lock().await;
-    let (log_path, data_path) = if file_extension == Some("log".to_string()) {
-        (std::path::PathBuf::from(&storage_path), std::path::PathBuf::from(&paired_storage_path))
-    } else {
-        (std::path::PathBuf::from(&paired_storage_path), std::path::PathBuf::from(&storage_path))
-    };
-
-    match schema_manager.parse_log_file_with_data(&log_path, &data_path).await {
-        Ok(log_file) => {
-            let stats = schema_manager.get_log_statistics(&log_file);
-            
-            let response = SchemaDetectionResponse {
-                success: true,
-                schema_found: true,
-                confidence: 1.0, // We successfully parsed it
-                source: format!("Parsed from {} with {} messages", original_filename, stats.total_messages),
-                warnings: vec![], // Add warnings if needed
-                schema_hash: Some(format!("v{}-ac{}", 
-                    log_file.configuration.paparazzi_version.unwrap_or_else(|| "unknown".to_string()),
-                    log_file.configuration.aircraft.ac_id)),
-            };
-
-            Ok(Json(ApiResponse {
-                success: true,
-                data: Some(response),
-                message: format!("Successfully parsed log file with {} messages", stats.total_messages),
-            }))
-        }
-        Err(e) => {
-            warn!("Failed to parse log file {}: {}", original_filename, e);
-            
-            let response = SchemaDetectionResponse {
-                success: false,
-                schema_found: false,
-                confidence: 0.0,
-                source: "Failed to parse".to_string(),
-                warnings: vec![format!("Parse error: {}", e)],
-                schema_hash: None,
-            };
-
-            Ok(Json(ApiResponse {
-                success: false,
-                data: Some(response),
-                message: format!("Failed to parse log file: {}", e),
-            }))
+    let detached = async {
+        let mut tx = state.db.begin().await?;
+        let result = sqlx::query!(
+            "DELETE FROM file_pair_artifacts WHERE file_pair_id = $1 AND artifact_id = $2",
+            paths.file_pair_id,
+            artifact_id
+        ).execute(&mut *tx).await?;
+        if let Some(file_pair_id) = paths.file_pair_id
+            && result.rows_affected() > 0
+        {
+            invalidate_diagnostics(&mut tx, file_pair_id).await?;
         }
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(result)
+    };
+    let result = detached.await.map_err(|e| {
+        error!("Database error detaching artifact from {}: {}", file_id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if result.rows_affected() > 0
+        && let Err(e) = state.file_processor.enqueue(file_id, 0).await
+    {
+        warn!("Failed to queue {} for processing: {}", file_id, e);
     }
+
+    Ok(Json(ApiResponse {
+        success: result.rows_affected() > 0,
+        data: None,
+        message: if result.rows_affected() > 0 {
+            "Artifact detached".to_string()
+        } else {
+            "Artifact is not attached to this file".to_string()
+        },
+    }))
 }
 
 // Authentication route handlers
@@ -1040,9 +3306,26 @@ pub fn router() -> Router<Arc<AppState>> {
         // File management routes
         .route("/api/files/upload", post(upload_log_files))
         .route("/api/files", get(list_log_files))
+        .route("/api/files/diff", get(diff_files))
+        .route("/api/flights/builds", get(list_firmware_builds))
         .route("/api/files/{file_id}", get(get_log_file))
         .route("/api/files/{file_id}", axum::routing::delete(delete_log_file))
         .route("/api/files/{file_id}/schema", get(detect_schema))
+        .route("/api/files/{file_id}/aircraft", get(get_aircraft))
+        .route("/api/files/{file_id}/airframe", get(get_airframe))
+        .route("/api/files/{file_id}/flight-plan", get(get_flight_plan))
+        .route("/api/files/{file_id}/setting-changes", get(get_setting_changes))
+        .route("/api/files/{file_id}/diagnostics", get(get_file_diagnostics))
+        .route("/api/files/{file_id}/artifacts", get(list_file_artifacts))
+        .route("/api/files/{file_id}/artifacts", post(attach_artifact))
+        .route("/api/files/{file_id}/artifacts/{artifact_id}", axum::routing::delete(detach_artifact))
+        .route("/api/schemas/artifacts", post(upload_schema_artifact))
+        .route("/api/schemas/artifacts", get(list_schema_artifacts))
+        .route("/api/schemas", get(list_schemas))
+        .route("/api/schemas/diff", get(diff_schemas))
+        .route("/api/schemas/{schema_hash}/flights", get(list_schema_flights))
+        .route("/api/files/{file_id}/timeseries", get(get_time_series))
+        .route("/api/files/{file_id}/export", get(export_csv))
         // Analysis session routes
         .route("/api/analysis/sessions", post(create_analysis_session))
         .route("/api/analysis/sessions", get(list_analysis_sessions))
@@ -1052,8 +3335,173 @@ pub fn router() -> Router<Arc<AppState>> {
         // Analysis template routes
         .route("/api/analysis/templates", post(create_analysis_template))
         .route("/api/analysis/templates", get(list_analysis_templates))
-        // TODO: Add processing endpoints later
-        // .route("/api/processing/process", post(process_file))
-        // .route("/api/processing/status/{task_id}", get(get_processing_status))
-        // .route("/api/processing/process-next", post(process_next_queued))
+        .route("/api/processing/process", post(process_file))
+        .route("/api/processing/status/{file_id}", get(get_processing_status))
+        .route("/api/processing/jobs", get(list_processing_jobs))
+        .route("/api/events", get(processing_events))
+        .route("/api/events/ticket", post(issue_event_ticket))
+        .route("/api/processing/jobs/{job_id}/cancel", post(cancel_processing_job))
+        .route("/api/processing/jobs/{job_id}/retry", post(retry_processing_job))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{Body, to_bytes};
+    use axum::http::Request;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    const BOUNDARY: &str = "ppz-upload-boundary";
+
+    async fn json_body(response: axum::response::Response) -> serde_json::Value {
+        serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap()
+    }
+
+    fn upload_request(authorization: Option<&str>, filename: &str, content: &str) -> Request<Body> {
+        let body = format!(
+            "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"files\"; filename=\"{filename}\"\r\n\
+             Content-Type: application/octet-stream\r\n\r\n{content}\r\n--{BOUNDARY}--\r\n"
+        );
+        let mut request = Request::post("/api/files/upload")
+            .header(header::CONTENT_TYPE, format!("multipart/form-data; boundary={BOUNDARY}"));
+        if let Some(authorization) = authorization {
+            request = request.header(header::AUTHORIZATION, authorization);
+        }
+        request.body(Body::from(body)).unwrap()
+    }
+
+    #[sqlx::test]
+    async fn test_upload_events_reach_owner(db: PgPool) {
+        let user_id = sqlx::query_scalar!(
+            "INSERT INTO users (username, email, password_hash) VALUES ('pilot', 'pilot@example.com', '') RETURNING id"
+        ).fetch_one(&db).await.unwrap();
+        let session = UserService::new().create_session(&db, user_id, None, None).await.unwrap();
+        let authorization = format!("Bearer {}", session.session_token);
+
+        let upload_dir = std::env::temp_dir().join(format!("ppz-routes-{}", Uuid::new_v4()));
+        let state = Arc::new(AppState {
+            db: db.clone(),
+            schema_manager: Arc::new(tokio::sync::Mutex::new(SchemaManager::new())),
+            file_processor: Arc::new(FileProcessor::new(db.clone(), Default::default())),
+            events: Arc::new(EventBus::new(db.clone())),
+            upload_dir: upload_dir.clone(),
+        });
+        state.events.listen();
+        start_processing(&state);
+        let app = router().with_state(state);
+
+        // The ticket opens one stream, without an Authorization header
+        let response = app.clone().oneshot(
+            Request::post("/api/events/ticket").header(header::AUTHORIZATION, &authorization).body(Body::empty()).unwrap()
+        ).await.unwrap();
+        let ticket = json_body(response).await["data"]["ticket"].as_str().unwrap().to_string();
+        let events_request = || Request::get(format!("/api/events?ticket={}", ticket)).body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(events_request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let mut events = response.into_body().into_data_stream();
+        assert_eq!(app.clone().oneshot(events_request()).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+
+        // Events only reach this replica once its listener is connected
+        while sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!" FROM pg_stat_activity WHERE datname = current_database() AND query LIKE 'LISTEN%'"#
+        ).fetch_one(&db).await.unwrap() == 0 {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        let data = "1.000 38 ROTORCRAFT_FP 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15\n";
+        let response = app.clone().oneshot(upload_request(None, "flight.data", data)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = app.clone().oneshot(upload_request(Some(&authorization), "flight.data", data)).await.unwrap();
+        let uploaded = json_body(response).await;
+        let file_id: Uuid = uploaded["data"][0]["file_id"].as_str().unwrap().parse().unwrap();
+
+        let stored = sqlx::query!("SELECT user_id, storage_path FROM log_files WHERE id = $1", file_id)
+            .fetch_one(&db).await.unwrap();
+        assert_eq!(stored.user_id, user_id);
+        assert!(StdPath::new(&stored.storage_path).starts_with(&upload_dir));
+
+        let mut received = String::new();
+        let completed = format!(r#""file_id":"{}""#, file_id);
+        tokio::time::timeout(Duration::from_secs(30), async {
+            while let Some(chunk) = events.next().await {
+                received.push_str(&String::from_utf8_lossy(&chunk.unwrap()));
+                if received.lines().any(|line| line.contains(&completed) && line.contains(r#""status":"completed""#)) {
+                    break;
+                }
+            }
+        }).await.expect("no completed status event");
+        assert!(received.contains("event: status"));
+
+        // The schema view reads what the job stored
+        let schema_request = |id: Uuid| Request::get(format!("/api/files/{}/schema", id))
+            .header(header::AUTHORIZATION, &authorization).body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(schema_request(file_id)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let detection = json_body(response).await;
+        assert_eq!(detection["data"]["schema_found"], true);
+        assert_eq!(detection["data"]["dictionary"]["kind"], "bundled");
+        assert_eq!(app.clone().oneshot(schema_request(Uuid::new_v4())).await.unwrap().status(), StatusCode::NOT_FOUND);
+
+        // Jobs are only listed, queued, cancelled and retried by the owner of their files
+        let other_id = sqlx::query_scalar!(
+            "INSERT INTO users (username, email, password_hash) VALUES ('intruder', 'intruder@example.com', '') RETURNING id"
+        ).fetch_one(&db).await.unwrap();
+        let other = UserService::new().create_session(&db, other_id, None, None).await.unwrap();
+        let other = format!("Bearer {}", other.session_token);
+        let get = |uri: &str, authorization: &str| Request::get(uri).header(header::AUTHORIZATION, authorization).body(Body::empty()).unwrap();
+        let post = |uri: &str, authorization: &str, body: String| Request::post(uri)
+            .header(header::AUTHORIZATION, authorization)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body)).unwrap();
+
+        let jobs = json_body(app.clone().oneshot(get("/api/processing/jobs", &authorization)).await.unwrap()).await;
+        let job_id = jobs["data"][0]["id"].as_str().unwrap().to_string();
+        assert_eq!(jobs["data"][0]["status"], "completed");
+        let jobs = json_body(app.clone().oneshot(get("/api/processing/jobs", &other)).await.unwrap()).await;
+        assert_eq!(jobs["data"], serde_json::json!([]));
+        let response = app.clone().oneshot(Request::get("/api/processing/jobs").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let process = format!(r#"{{"file_id":"{}"}}"#, file_id);
+        let response = json_body(app.clone().oneshot(post("/api/processing/process", &other, process)).await.unwrap()).await;
+        assert_eq!((&response["success"], &response["message"]), (&serde_json::json!(false), &serde_json::json!("File not found")));
+        for action in ["cancel", "retry"] {
+            let uri = format!("/api/processing/jobs/{}/{}", job_id, action);
+            let response = json_body(app.clone().oneshot(post(&uri, &other, String::new())).await.unwrap()).await;
+            assert_eq!(response["success"], false, "{}", action);
+        }
+
+
+        // The files of a pair are only read and changed by their owner
+        for uri in ["", "/schema", "/aircraft", "/airframe", "/flight-plan", "/setting-changes", "/diagnostics", "/artifacts",
+            "/timeseries?channels=ROTORCRAFT_FP.east", "/export"]
+        {
+            let uri = format!("/api/files/{}{}", file_id, uri);
+            let response = app.clone().oneshot(get(&uri, &other)).await.unwrap();
+            if response.status() == StatusCode::OK {
+                let response = json_body(response).await;
+                assert_eq!(response["success"], false, "{}", uri);
+                assert_eq!(response["message"], "File not found", "{}", uri);
+            } else {
+                assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", uri);
+            }
+            let response = app.clone().oneshot(Request::get(&uri).body(Body::empty()).unwrap()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{}", uri);
+        }
+        let attach = format!(r#"{{"artifact_id":"{}"}}"#, Uuid::new_v4());
+        let response = app.clone().oneshot(post(&format!("/api/files/{}/artifacts", file_id), &other, attach)).await.unwrap();
+        assert_eq!(json_body(response).await["message"], "File not found");
+        let response = app.clone().oneshot(get(&format!("/api/files/{}", file_id), &authorization)).await.unwrap();
+        assert_eq!(json_body(response).await["data"]["id"], file_id.to_string());
+        let files = json_body(app.clone().oneshot(get("/api/files", &other)).await.unwrap()).await;
+        assert_eq!(files["data"], serde_json::json!([]));
+
+        let delete = |authorization: &str| Request::delete(format!("/api/files/{}", file_id))
+            .header(header::AUTHORIZATION, authorization).body(Body::empty()).unwrap();
+        assert_eq!(json_body(app.clone().oneshot(delete(&other)).await.unwrap()).await["success"], false);
+        assert_eq!(json_body(app.clone().oneshot(delete(&authorization)).await.unwrap()).await["success"], true);
+        assert!(!StdPath::new(&stored.storage_path).exists());
+        let _ = std::fs::remove_dir_all(&upload_dir);
+    }
 }
@@ -0,0 +1,309 @@
+//! Airframe configuration (`<airframe>`) embedded in the .log for each aircraft
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use super::xml::{attr, child, children, escape_stray_ampersands, parse_document};
+
+/// Typed model of an `<airframe>` element
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AirframeConfig {
+    pub name: String,
+    pub description: Option<String>,
+    /// md5 of the airframe file as uploaded, comparable to the md5sum of `ALIVE`.
+    /// Unknown for the copy embedded in a .log, which is not the file byte for byte.
+    #[serde(default)]
+    pub md5: Option<String>,
+    pub firmwares: Vec<FirmwareConfig>,
+    pub commands: Vec<CommandAxis>,
+    pub servos: Vec<Servo>,
+    pub command_laws: Vec<CommandLaw>,
+    pub sections: Vec<AirframeSection>,
+}
+
+/// `<firmware NAME>` with its build targets and modules
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FirmwareConfig {
+    pub name: String,
+    pub targets: Vec<FirmwareTarget>,
+    pub modules: Vec<ModuleConfig>,
+    pub defines: Vec<Define>,
+    pub configures: Vec<Configure>,
+}
+
+/// `<target NAME BOARD>` of a firmware
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FirmwareTarget {
+    pub name: String,
+    pub board: Option<String>,
+    pub modules: Vec<ModuleConfig>,
+    pub defines: Vec<Define>,
+    pub configures: Vec<Configure>,
+}
+
+/// `<module NAME TYPE>` loaded by a firmware or target
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModuleConfig {
+    pub name: String,
+    pub module_type: Option<String>,
+    pub defines: Vec<Define>,
+    pub configures: Vec<Configure>,
+}
+
+/// `<define NAME VALUE UNIT>` of a section, module or target
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Define {
+    pub name: String,
+    pub value: Option<String>,
+    pub unit: Option<String>,
+    pub define_type: Option<String>,
+    /// Value parsed as a number, when it is one (`16.`, `-180`, `0.9`)
+    pub numeric_value: Option<f64>,
+}
+
+/// `<configure NAME VALUE>` build option
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Configure {
+    pub name: String,
+    pub value: Option<String>,
+}
+
+/// `<section NAME PREFIX>` grouping defines
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AirframeSection {
+    pub name: Option<String>,
+    pub prefix: Option<String>,
+    pub defines: Vec<Define>,
+}
+
+/// `<axis NAME FAILSAFE_VALUE>` of the `<commands>` block
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CommandAxis {
+    pub name: String,
+    pub failsafe_value: Option<String>,
+}
+
+/// `<servo NAME NO MIN NEUTRAL MAX>` with the driver of its `<servos>` block
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Servo {
+    pub name: String,
+    pub driver: Option<String>,
+    pub no: Option<u32>,
+    pub min: Option<f64>,
+    pub neutral: Option<f64>,
+    pub max: Option<f64>,
+}
+
+/// Statement of the `<command_laws>` block
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum CommandLaw {
+    Set { servo: String, value: String },
+    Let { var: String, value: String },
+    Call { fun: String },
+}
+
+impl AirframeConfig {
+    /// Parse a standalone `<airframe>` document
+    pub fn from_xml(xml: &str) -> Result<Self> {
+        let source = xml;
+        let xml = escape_stray_ampersands(xml);
+        let document = parse_document(&xml)?;
+        let root = document.root_element();
+        if !root.has_tag_name("airframe") {
+            return Err(anyhow!("Expected <airframe> root element, found <{}>", root.tag_name().name()));
+        }
+        Ok(Self {
+            md5: Some(format!("{:x}", md5::compute(source))),
+            ..Self::from_node(root)
+        })
+    }
+
+    /// Build the model from a parsed `<airframe>` element
+    pub fn from_node(node: roxmltree::Node) -> Self {
+        let mut servos = Vec::new();
+        for group in children(node, "servos") {
+            let driver = attr(group, "DRIVER");
+            servos.extend(children(group, "servo").map(|servo| Servo {
+                name: attr(servo, "NAME").unwrap_or_default(),
+                driver: driver.clone(),
+                no: attr(servo, "NO").and_then(|v| v.trim().parse().ok()),
+                min: attr(servo, "MIN").and_then(|v| parse_number(&v)),
+                neutral: attr(servo, "NEUTRAL").and_then(|v| parse_number(&v)),
+                max: attr(servo, "MAX").and_then(|v| parse_number(&v)),
+            }));
+        }
+
+        let command_laws = children(node, "command_laws")
+            .flat_map(|laws| laws.children().filter(|n| n.is_element()))
+            .filter_map(|law| match law.tag_name().name() {
+                "set" => Some(CommandLaw::Set {
+                    servo: attr(law, "SERVO").unwrap_or_default(),
+                    value: attr(law, "VALUE").unwrap_or_default(),
+                }),
+                "let" => Some(CommandLaw::Let {
+                    var: attr(law, "VAR").unwrap_or_default(),
+                    value: attr(law, "VALUE").unwrap_or_default(),
+                }),
+                "call" => Some(CommandLaw::Call {
+                    fun: attr(law, "FUN").unwrap_or_default(),
+                }),
+                _ => None,
+            })
+            .collect();
+
+        Self {
+            name: attr(node, "NAME").unwrap_or_default(),
+            md5: None,
+            description: child(node, "description")
+                .and_then(|d| d.text())
+                .map(|text| text.trim().to_string())
+                .filter(|text| !text.is_empty()),
+            firmwares: children(node, "firmware").map(FirmwareConfig::from_node).collect(),
+            commands: children(node, "commands")
+                .flat_map(|commands| children(commands, "axis"))
+                .map(|axis| CommandAxis {
+                    name: attr(axis, "NAME").unwrap_or_default(),
+                    failsafe_value: attr(axis, "FAILSAFE_VALUE"),
+                })
+                .collect(),
+            servos,
+            command_laws,
+            sections: children(node, "section")
+                .map(|section| AirframeSection {
+                    name: attr(section, "NAME"),
+                    prefix: attr(section, "PREFIX"),
+                    defines: defines(section),
+                })
+                .collect(),
+        }
+    }
+
+    /// Name of the main firmware (`rotorcraft`, `fixedwing`, ...)
+    pub fn firmware_name(&self) -> Option<&str> {
+        self.firmwares.first().map(|f| f.name.as_str())
+    }
+
+    /// Look up a section define by its full name (section prefix + define name)
+    pub fn define(&self, full_name: &str) -> Option<&Define> {
+        self.sections.iter().find_map(|section| {
+            let prefix = section.prefix.as_deref().unwrap_or("");
+            let name = full_name.strip_prefix(prefix)?;
+            section.defines.iter().find(|d| d.name == name)
+        })
+    }
+}
+
+impl FirmwareConfig {
+    fn from_node(node: roxmltree::Node) -> Self {
+        Self {
+            name: attr(node, "NAME").unwrap_or_default(),
+            targets: children(node, "target")
+                .map(|target| FirmwareTarget {
+                    name: attr(target, "NAME").unwrap_or_default(),
+                    board: attr(target, "BOARD"),
+                    modules: modules(target),
+                    defines: defines(target),
+                    configures: configures(target),
+                })
+                .collect(),
+            modules: modules(node),
+            defines: defines(node),
+            configures: configures(node),
+        }
+    }
+}
+
+impl AirframeSection {
+    /// Name of a define of this section as used in the C code (`PREFIX` + `NAME`)
+    pub fn full_name(&self, define: &Define) -> String {
+        format!("{}{}", self.prefix.as_deref().unwrap_or(""), define.name)
+    }
+}
+
+fn modules(node: roxmltree::Node) -> Vec<ModuleConfig> {
+    children(node, "module")
+        .map(|module| ModuleConfig {
+            name: attr(module, "NAME").unwrap_or_default(),
+            module_type: attr(module, "TYPE"),
+            defines: defines(module),
+            configures: configures(module),
+        })
+        .collect()
+}
+
+fn defines(node: roxmltree::Node) -> Vec<Define> {
+    children(node, "define")
+        .map(|define| {
+            let value = attr(define, "VALUE");
+            Define {
+                name: attr(define, "NAME").unwrap_or_default(),
+                numeric_value: value.as_deref().and_then(parse_number),
+                value,
+                unit: attr(define, "UNIT"),
+                define_type: attr(define, "TYPE"),
+            }
+        })
+        .collect()
+}
+
+fn configures(node: roxmltree::Node) -> Vec<Configure> {
+    children(node, "configure")
+        .map(|configure| Configure {
+            name: attr(configure, "NAME").unwrap_or_default(),
+            value: attr(configure, "VALUE"),
+        })
+        .collect()
+}
+
+fn parse_number(value: &str) -> Option<f64> {
+    value.trim().parse::<f64>().ok().filter(|v| v.is_finite())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AIRFRAME: &str = r#"<airframe NAME="ardrone2_indi">
+        <description>?</description>
+        <firmware NAME="rotorcraft">
+          <target NAME="ap" BOARD="ardrone2">
+            <define NAME="AUTOPILOT_DISABLE_AHRS_KILL"/>
+          </target>
+          <module TYPE="int_cmpl_quat" NAME="ahrs">
+            <configure VALUE="TRUE" NAME="USE_MAGNETOMETER"/>
+          </module>
+        </firmware>
+        <servos driver="Pwm">
+          <servo NO="0" NEUTRAL="1" NAME="TOP_LEFT" MIN="0" MAX="500"/>
+        </servos>
+        <section PREFIX="IMU_" NAME="IMU">
+          <define VALUE="16." NAME="MAG_X_SENS" INTEGER="16"/>
+          <define VALUE="0." UNIT="deg" NAME="BODY_TO_IMU_PHI"/>
+        </section>
+        <command_laws>
+          <call FUN="stabilization_run(autopilot_in_flight(), &stabilization.rc_sp)"/>
+          <set VALUE="motor_mixing.commands[0]" SERVO="TOP_LEFT"/>
+        </command_laws>
+      </airframe>"#;
+
+    #[test]
+    fn test_parse_airframe() {
+        let airframe = AirframeConfig::from_xml(AIRFRAME).unwrap();
+
+        assert_eq!(airframe.name, "ardrone2_indi");
+        assert_eq!(airframe.firmware_name(), Some("rotorcraft"));
+        assert_eq!(airframe.firmwares[0].targets[0].board.as_deref(), Some("ardrone2"));
+        assert_eq!(airframe.firmwares[0].modules[0].configures[0].name, "USE_MAGNETOMETER");
+        assert_eq!(airframe.servos[0].driver.as_deref(), Some("Pwm"));
+        assert_eq!(airframe.servos[0].max, Some(500.0));
+
+        let phi = airframe.define("IMU_BODY_TO_IMU_PHI").unwrap();
+        assert_eq!(phi.unit.as_deref(), Some("deg"));
+        assert_eq!(phi.numeric_value, Some(0.0));
+        assert_eq!(airframe.define("IMU_MAG_X_SENS").unwrap().numeric_value, Some(16.0));
+
+        assert!(matches!(&airframe.command_laws[0], CommandLaw::Call { fun } if fun.contains("&stabilization")));
+        assert_eq!(airframe.command_laws.len(), 2);
+    }
+}
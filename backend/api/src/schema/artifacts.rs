@@ -0,0 +1,152 @@
+//! User-supplied schema files (messages.xml, airframe, flight plan) overriding what a .log embeds
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use uuid::Uuid;
+
+use super::{AirframeConfig, FlightPlan, MessageDictionary};
+
+/// What a schema artifact describes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArtifactKind {
+    /// pprzlink messages.xml, replaces the `<protocol>` of the .log
+    Messages,
+    /// Airframe XML of one aircraft
+    Airframe,
+    /// Flight plan XML of one aircraft
+    FlightPlan,
+}
+
+impl ArtifactKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ArtifactKind::Messages => "messages",
+            ArtifactKind::Airframe => "airframe",
+            ArtifactKind::FlightPlan => "flight_plan",
+        }
+    }
+
+    /// Whether the artifact applies to a single aircraft of the log
+    pub fn is_per_aircraft(&self) -> bool {
+        !matches!(self, ArtifactKind::Messages)
+    }
+}
+
+impl fmt::Display for ArtifactKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ArtifactKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "messages" => Ok(ArtifactKind::Messages),
+            "airframe" => Ok(ArtifactKind::Airframe),
+            "flight_plan" => Ok(ArtifactKind::FlightPlan),
+            other => Err(anyhow!("Unknown artifact kind: {}", other)),
+        }
+    }
+}
+
+/// Parsed content of a schema artifact
+#[derive(Debug, Clone)]
+pub enum SchemaArtifact {
+    Messages(MessageDictionary),
+    Airframe(AirframeConfig),
+    FlightPlan(FlightPlan),
+}
+
+impl SchemaArtifact {
+    /// Parse an uploaded file as the given kind of artifact
+    pub fn parse(kind: ArtifactKind, xml: &str) -> Result<Self> {
+        match kind {
+            ArtifactKind::Messages => MessageDictionary::from_xml(xml).map(SchemaArtifact::Messages),
+            ArtifactKind::Airframe => AirframeConfig::from_xml(xml).map(SchemaArtifact::Airframe),
+            ArtifactKind::FlightPlan => FlightPlan::from_xml(xml).map(SchemaArtifact::FlightPlan),
+        }
+    }
+
+    pub fn kind(&self) -> ArtifactKind {
+        match self {
+            SchemaArtifact::Messages(_) => ArtifactKind::Messages,
+            SchemaArtifact::Airframe(_) => ArtifactKind::Airframe,
+            SchemaArtifact::FlightPlan(_) => ArtifactKind::FlightPlan,
+        }
+    }
+
+    /// One-line description of the content, for listings
+    pub fn summary(&self) -> String {
+        match self {
+            SchemaArtifact::Messages(protocol) => format!("{} messages", protocol.len()),
+            SchemaArtifact::Airframe(airframe) => format!("airframe {} ({})",
+                airframe.name, airframe.firmware_name().unwrap_or("no firmware")),
+            SchemaArtifact::FlightPlan(flight_plan) => format!("flight plan {} ({} waypoints)",
+                flight_plan.name, flight_plan.waypoints.len()),
+        }
+    }
+}
+
+/// Registry key of an artifact: blake3 hash of its content
+pub fn artifact_hash(content: &[u8]) -> String {
+    blake3::hash(content).to_hex().to_string()
+}
+
+/// A stored artifact attached to a file pair
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArtifactAttachment {
+    pub artifact_id: Uuid,
+    pub kind: ArtifactKind,
+    pub name: String,
+    pub version: i32,
+    pub content_hash: String,
+    #[serde(skip)]
+    pub storage_path: PathBuf,
+    /// Aircraft the airframe or flight plan is used for
+    pub sender_id: Option<u8>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Layout of pprzlink's messages.xml: lower-case attributes and a doctype
+    const MESSAGES_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE protocol SYSTEM "messages.dtd">
+<protocol>
+  <msg_class name="telemetry" id="1">
+    <message name="MY_MODULE_STATUS" id="250">
+      <description>Added by a custom module</description>
+      <field name="state" type="uint8" values="IDLE|RUNNING"/>
+      <field name="rate" type="float" unit="rad/s" alt_unit="deg/s"/>
+    </message>
+  </msg_class>
+</protocol>"#;
+
+    #[test]
+    fn test_parse_artifacts() {
+        let artifact = SchemaArtifact::parse(ArtifactKind::Messages, MESSAGES_XML).unwrap();
+        assert_eq!(artifact.kind(), ArtifactKind::Messages);
+        let SchemaArtifact::Messages(protocol) = &artifact else {
+            unreachable!();
+        };
+        let message = protocol.get("MY_MODULE_STATUS").unwrap();
+        assert_eq!((message.id, message.fields.len()), (250, 2));
+        assert_eq!(message.field("rate").unwrap().alt_unit.as_deref(), Some("deg/s"));
+
+        assert!(SchemaArtifact::parse(ArtifactKind::Airframe, MESSAGES_XML).is_err());
+
+        // The registry does not hand back a messages.xml for the same bytes uploaded as an airframe
+        let mut manager = crate::schema::SchemaManager::new();
+        assert!(manager.register_artifact(ArtifactKind::Messages, MESSAGES_XML.as_bytes()).is_ok());
+        assert!(manager.register_artifact(ArtifactKind::Airframe, MESSAGES_XML.as_bytes()).is_err());
+        assert_eq!(artifact_hash(MESSAGES_XML.as_bytes()).len(), 64);
+        assert_eq!("flight_plan".parse::<ArtifactKind>().unwrap(), ArtifactKind::FlightPlan);
+    }
+}
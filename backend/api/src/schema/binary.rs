@@ -0,0 +1,679 @@
+//! Binary pprzlink transport (raw serial captures, pprzlog .tlm) decoded into telemetry messages
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Cursor, ErrorKind, Read, Seek, SeekFrom};
+use std::ops::Range;
+use tracing::debug;
+
+use super::{ATTACH_MESSAGES_XML, DataProgress, Diagnostic, DiagnosticKind, FieldType, FieldValue, MessageClass, MessageDefinition, MessageDictionary, TelemetryMessage};
+
+/// Start byte of a pprzlink frame
+pub const PPRZ_STX: u8 = 0x99;
+
+/// Baud rate assumed for the link when estimating timestamps (8N1, 10 bits per byte)
+pub const DEFAULT_BAUD_RATE: u32 = 57_600;
+
+/// Bytes of a pprzlog record around its pprzlink payload: STX, length, source, timestamp, checksum
+const PPRZLOG_OVERHEAD: usize = 8;
+
+/// Resolution of pprzlog timestamps, in seconds
+const PPRZLOG_TICK: f64 = 1e-4;
+
+/// Bytes read from the capture at a time
+const READ_CHUNK: usize = 64 * 1024;
+
+/// Bytes looked at to tell a text .data file from a binary capture
+const SNIFF_BYTES: u64 = 64 * 1024;
+
+/// Version of the pprzlink transport a capture was recorded with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PprzlinkVersion {
+    /// `STX len sender msg_id payload ck_a ck_b`
+    V1,
+    /// `STX len sender destination class|component msg_id payload ck_a ck_b`
+    V2,
+}
+
+impl PprzlinkVersion {
+    /// Bytes of the payload before the message fields
+    fn header_len(&self) -> usize {
+        match self {
+            PprzlinkVersion::V1 => 2,
+            PprzlinkVersion::V2 => 4,
+        }
+    }
+
+    /// Length of a frame without fields: STX, length, header and checksum
+    fn min_frame_len(&self) -> usize {
+        self.header_len() + 4
+    }
+}
+
+/// Encoding of a telemetry capture
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DataFormat {
+    /// Text .data file written by the ground station logger
+    #[default]
+    Text,
+    /// Raw pprzlink frames
+    Pprzlink(PprzlinkVersion),
+    /// pprzlink payloads recorded by the on-board logger (.tlm), each wrapped in a pprzlog
+    /// record `STX len source timestamp payload ck` with the time it was logged at
+    Pprzlog(PprzlinkVersion),
+}
+
+impl DataFormat {
+    /// Guess the format from the first bytes of a capture: the binary framing that
+    /// decodes the most messages, raw pprzlink v2 on a tie
+    pub fn detect(sample: &[u8], protocol: &MessageDictionary) -> Self {
+        if is_text(sample) {
+            return DataFormat::Text;
+        }
+        let decoded = |format: DataFormat| {
+            format.binary_reader(Cursor::new(sample), protocol, None).map_or(0, |mut reader| {
+                reader.by_ref().for_each(drop);
+                reader.progress().messages_decoded
+            })
+        };
+        [
+            DataFormat::Pprzlink(PprzlinkVersion::V2),
+            DataFormat::Pprzlink(PprzlinkVersion::V1),
+            DataFormat::Pprzlog(PprzlinkVersion::V2),
+            DataFormat::Pprzlog(PprzlinkVersion::V1),
+        ]
+            .into_iter()
+            .map(|format| (format, decoded(format)))
+            .fold((DataFormat::Pprzlink(PprzlinkVersion::V2), 0), |best, candidate| {
+                if candidate.1 > best.1 { candidate } else { best }
+            })
+            .0
+    }
+
+    /// pprzlink version of a binary capture
+    pub fn transport(&self) -> Option<PprzlinkVersion> {
+        match self {
+            DataFormat::Text => None,
+            DataFormat::Pprzlink(version) | DataFormat::Pprzlog(version) => Some(*version),
+        }
+    }
+
+    /// Reader for a binary capture in this format, None for text
+    pub fn binary_reader<'a, R: Read>(
+        self,
+        reader: R,
+        protocol: &'a MessageDictionary,
+        total_bytes: Option<u64>,
+    ) -> Option<BinaryTelemetryReader<'a, R>> {
+        match self {
+            DataFormat::Text => None,
+            DataFormat::Pprzlink(version) => Some(BinaryTelemetryReader::new(reader, protocol, version, total_bytes)),
+            DataFormat::Pprzlog(version) => Some(BinaryTelemetryReader::new(reader, protocol, version, total_bytes).pprzlog()),
+        }
+    }
+
+    /// Guess the format of an open file, leaving it rewound
+    pub fn detect_file(file: &mut File, protocol: &MessageDictionary) -> Result<Self> {
+        let mut sample = Vec::new();
+        file.take(SNIFF_BYTES).read_to_end(&mut sample)?;
+        file.seek(SeekFrom::Start(0))?;
+        Ok(Self::detect(&sample, protocol))
+    }
+}
+
+/// Text .data files are plain ASCII lines; the sample may end inside a UTF-8 character
+fn is_text(sample: &[u8]) -> bool {
+    let valid = match std::str::from_utf8(sample) {
+        Ok(_) => sample.len(),
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        Err(_) => return false,
+    };
+    sample[..valid].iter().all(|&b| b >= 0x20 || matches!(b, b'\t' | b'\r' | b'\n'))
+}
+
+/// First bytes of a frame in hex, for diagnostics
+fn hex_snippet(frame: &[u8]) -> String {
+    let mut snippet = frame.iter().take(32).map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(" ");
+    if frame.len() > 32 {
+        snippet.push_str(" …");
+    }
+    snippet
+}
+
+/// pprzlink checksum over the length byte and the payload
+fn checksum(bytes: &[u8]) -> (u8, u8) {
+    bytes.iter().fold((0u8, 0u8), |(ck_a, ck_b), &byte| {
+        let ck_a = ck_a.wrapping_add(byte);
+        (ck_a, ck_b.wrapping_add(ck_a))
+    })
+}
+
+/// Iterator over the messages of a binary pprzlink capture.
+///
+/// The capture is read in chunks and scanned for frames: bytes before a start
+/// byte are skipped (a resync), and a frame whose checksum does not match is
+/// dropped and the scan resumes one byte after its start. Frames of messages
+/// the dictionary does not know are counted as unknown.
+///
+/// Raw captures have no timestamps: a message is stamped with the time the
+/// link took to transmit the bytes before it, at the configured baud rate.
+/// pprzlog records carry the time they were logged at, in 1e-4 s ticks.
+/// pprzlink v1 frames do not carry the message class: frames sent by the
+/// ground (sender 0) are decoded as datalink messages, the others as telemetry.
+pub struct BinaryTelemetryReader<'a, R> {
+    reader: R,
+    version: PprzlinkVersion,
+    /// Frames are wrapped in pprzlog records
+    pprzlog: bool,
+    classes: HashMap<u8, MessageClass>,
+    definitions: HashMap<(MessageClass, u8), &'a MessageDefinition>,
+    buffer: Vec<u8>,
+    start: usize,
+    eof: bool,
+    bytes_per_second: f64,
+    progress: DataProgress,
+}
+
+impl<'a, R: Read> BinaryTelemetryReader<'a, R> {
+    pub fn new(reader: R, protocol: &'a MessageDictionary, version: PprzlinkVersion, total_bytes: Option<u64>) -> Self {
+        let mut classes = HashMap::new();
+        let mut definitions = HashMap::new();
+        for definition in &protocol.messages {
+            classes.entry(definition.class_id).or_insert(definition.class);
+            definitions.entry((definition.class, definition.id)).or_insert(definition);
+        }
+
+        Self {
+            reader,
+            version,
+            pprzlog: false,
+            classes,
+            definitions,
+            buffer: Vec::new(),
+            start: 0,
+            eof: false,
+            bytes_per_second: DEFAULT_BAUD_RATE as f64 / 10.0,
+            progress: DataProgress {
+                total_bytes,
+                format: DataFormat::Pprzlink(version),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Read pprzlog records, as written by the on-board logger, instead of raw frames
+    pub fn pprzlog(mut self) -> Self {
+        self.pprzlog = true;
+        self.progress.format = DataFormat::Pprzlog(self.version);
+        self
+    }
+
+    /// Baud rate of the captured link, used to estimate timestamps
+    pub fn with_baud_rate(mut self, baud_rate: u32) -> Self {
+        self.bytes_per_second = baud_rate.max(1) as f64 / 10.0;
+        self
+    }
+
+    pub fn progress(&self) -> &DataProgress {
+        &self.progress
+    }
+
+    fn available(&self) -> usize {
+        self.buffer.len() - self.start
+    }
+
+    fn consume(&mut self, count: usize) {
+        self.start += count;
+        self.progress.bytes_read += count as u64;
+    }
+
+    /// Make sure `needed` bytes are buffered, returning false at the end of the capture
+    fn fill(&mut self, needed: usize) -> std::io::Result<bool> {
+        while self.available() < needed {
+            if self.eof {
+                return Ok(false);
+            }
+            self.buffer.drain(..self.start);
+            self.start = 0;
+
+            let len = self.buffer.len();
+            self.buffer.resize(len + READ_CHUNK, 0);
+            let read = loop {
+                match self.reader.read(&mut self.buffer[len..]) {
+                    Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                    result => break result,
+                }
+            };
+            let read = read.inspect_err(|_| self.buffer.truncate(len))?;
+            self.buffer.truncate(len + read);
+            self.eof = read == 0;
+        }
+        Ok(true)
+    }
+
+    /// Next frame with a valid checksum
+    fn next_frame(&mut self) -> std::io::Result<Option<Frame>> {
+        let mut synchronized = true;
+        let mut lose_sync = |progress: &mut DataProgress| {
+            if synchronized {
+                synchronized = false;
+                progress.resyncs += 1;
+            }
+        };
+
+        loop {
+            if !self.fill(2)? {
+                self.consume(self.available());
+                return Ok(None);
+            }
+
+            let window = &self.buffer[self.start..];
+            if window[0] != PPRZ_STX {
+                let skip = window.iter().position(|&b| b == PPRZ_STX).unwrap_or(window.len());
+                lose_sync(&mut self.progress);
+                self.consume(skip);
+                continue;
+            }
+
+            // A start byte inside garbage or a payload rather than a frame
+            let (length, min_length) = if self.pprzlog {
+                (window[1] as usize + PPRZLOG_OVERHEAD, self.version.header_len() + PPRZLOG_OVERHEAD)
+            } else {
+                (window[1] as usize, self.version.min_frame_len())
+            };
+            if length < min_length {
+                lose_sync(&mut self.progress);
+                self.consume(1);
+                continue;
+            }
+
+            if !self.fill(length)? {
+                debug!("Capture ends inside a frame at byte {}", self.progress.bytes_read);
+                self.consume(self.available());
+                return Ok(None);
+            }
+
+            let frame = &self.buffer[self.start..self.start + length];
+            let valid = if self.pprzlog {
+                checksum(&frame[1..length - 1]).0 == frame[length - 1]
+            } else {
+                checksum(&frame[1..length - 2]) == (frame[length - 2], frame[length - 1])
+            };
+            if !valid {
+                self.progress.checksum_errors += 1;
+                self.progress.diagnostics.push(Diagnostic {
+                    line: self.progress.lines_read + self.progress.checksum_errors,
+                    byte_offset: self.progress.bytes_read,
+                    kind: DiagnosticKind::ChecksumError,
+                    message: format!("Checksum mismatch in a {} byte frame", length),
+                    snippet: hex_snippet(frame),
+                    suggestion: "The frame was corrupted on the link; check the radio link quality \
+                        and that the capture was recorded at the right baud rate".to_string(),
+                });
+                lose_sync(&mut self.progress);
+                self.consume(1);
+                continue;
+            }
+
+            let (payload, timestamp) = if self.pprzlog {
+                let ticks = u32::from_le_bytes([frame[3], frame[4], frame[5], frame[6]]);
+                (self.start + 7..self.start + length - 1, Some(ticks as f64 * PPRZLOG_TICK))
+            } else {
+                (self.start + 2..self.start + length - 2, None)
+            };
+            let frame = Frame {
+                bytes: self.start..self.start + length,
+                payload,
+                offset: self.progress.bytes_read,
+                timestamp,
+            };
+            self.consume(length);
+            return Ok(Some(frame));
+        }
+    }
+
+    /// Decode the payload of a frame with the message dictionary
+    fn decode(&mut self, frame: &Frame) -> Result<TelemetryMessage, (DiagnosticKind, String)> {
+        let payload = &self.buffer[frame.payload.clone()];
+        let (sender_id, class, message_id) = match self.version {
+            PprzlinkVersion::V1 => {
+                let class = if payload[0] == 0 { MessageClass::Datalink } else { MessageClass::Telemetry };
+                (payload[0], Some(class), payload[1])
+            }
+            PprzlinkVersion::V2 => (payload[0], self.classes.get(&(payload[2] & 0x0f)).copied(), payload[3]),
+        };
+
+        let Some(definition) = class.and_then(|class| self.definitions.get(&(class, message_id))) else {
+            let name = match class {
+                Some(class) => format!("{}:{}", class, message_id),
+                None => format!("class {}:{}", payload[2] & 0x0f, message_id),
+            };
+            *self.progress.mismatches.unknown_messages.entry(name.clone()).or_insert(0) += 1;
+            return Err((DiagnosticKind::UnknownMessage, format!("Message {} is not in the dictionary", name)));
+        };
+
+        let mut fields_bytes = &payload[self.version.header_len()..];
+        let mut fields = HashMap::with_capacity(definition.fields.len());
+        for field in &definition.fields {
+            let value = field.field_type.decode_binary(&mut fields_bytes)
+                .map_err(|e| (DiagnosticKind::ArityMismatch, format!("{} field {}: {}", definition.name, field.name, e)))?;
+            fields.insert(field.name.clone(), value);
+        }
+        if !fields_bytes.is_empty() {
+            return Err((DiagnosticKind::ArityMismatch,
+                format!("{}: {} byte(s) left after the last field", definition.name, fields_bytes.len())));
+        }
+
+        Ok(TelemetryMessage {
+            timestamp: frame.timestamp.unwrap_or(frame.offset as f64 / self.bytes_per_second),
+            sender_id,
+            message_id,
+            msg_class: definition.class,
+            message_name: definition.name.clone(),
+            fields,
+        })
+    }
+}
+
+/// A frame with a valid checksum, positioned in the reader's buffer
+struct Frame {
+    bytes: Range<usize>,
+    payload: Range<usize>,
+    /// Capture offset of the start byte
+    offset: u64,
+    /// Time the frame was logged at, pprzlog records only
+    timestamp: Option<f64>,
+}
+
+impl<R: Read> Iterator for BinaryTelemetryReader<'_, R> {
+    type Item = Result<TelemetryMessage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let frame = match self.next_frame() {
+                Ok(Some(frame)) => frame,
+                Ok(None) => {
+                    self.progress.finished = true;
+                    return None;
+                }
+                Err(e) => {
+                    self.progress.finished = true;
+                    return Some(Err(anyhow!("Failed to read capture: {}", e)));
+                }
+            };
+            self.progress.lines_read += 1;
+
+            match self.decode(&frame) {
+                Ok(message) => {
+                    self.progress.messages_decoded += 1;
+                    return Some(Ok(message));
+                }
+                Err((kind, message)) => {
+                    self.progress.lines_skipped += 1;
+                    debug!("Failed to decode frame at byte {}: {}", frame.offset, message);
+                    let suggestion = match kind {
+                        DiagnosticKind::UnknownMessage => ATTACH_MESSAGES_XML,
+                        _ => "The capture was recorded with another pprzlink version or dictionary; attach the matching messages.xml",
+                    };
+                    self.progress.diagnostics.push(Diagnostic {
+                        line: self.progress.lines_read + self.progress.checksum_errors,
+                        byte_offset: frame.offset,
+                        kind,
+                        message,
+                        snippet: hex_snippet(&self.buffer[frame.bytes]),
+                        suggestion: suggestion.to_string(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Encode a message into a pprzlink frame, the inverse of `BinaryTelemetryReader`
+pub fn encode_frame(
+    version: PprzlinkVersion,
+    definition: &MessageDefinition,
+    sender_id: u8,
+    fields: &HashMap<String, FieldValue>,
+) -> Result<Vec<u8>> {
+    let mut frame = vec![PPRZ_STX, 0, sender_id];
+    if version == PprzlinkVersion::V2 {
+        // Broadcast, class in the low nibble and component 0
+        frame.extend([0, definition.class_id & 0x0f]);
+    }
+    frame.push(definition.id);
+    for field in &definition.fields {
+        let value = fields.get(&field.name)
+            .ok_or_else(|| anyhow!("{}: missing field {}", definition.name, field.name))?;
+        field.field_type.encode_binary(value, &mut frame)?;
+    }
+
+    frame[1] = u8::try_from(frame.len() + 2)
+        .map_err(|_| anyhow!("{}: frame of {} bytes is too long", definition.name, frame.len() + 2))?;
+    let (ck_a, ck_b) = checksum(&frame[1..]);
+    frame.extend([ck_a, ck_b]);
+    Ok(frame)
+}
+
+/// Wrap a message in a pprzlog record logged at `timestamp` seconds, as in a .tlm
+pub fn encode_pprzlog_record(
+    version: PprzlinkVersion,
+    definition: &MessageDefinition,
+    sender_id: u8,
+    fields: &HashMap<String, FieldValue>,
+    timestamp: f64,
+) -> Result<Vec<u8>> {
+    let frame = encode_frame(version, definition, sender_id, fields)?;
+    let payload = &frame[2..frame.len() - 2];
+    // Source 0: the downlink
+    let mut record = vec![PPRZ_STX, payload.len() as u8, 0];
+    record.extend(((timestamp / PPRZLOG_TICK).round() as u32).to_le_bytes());
+    record.extend(payload);
+    record.push(checksum(&record[1..]).0);
+    Ok(record)
+}
+
+/// Split `N` bytes off the front of a payload
+fn take<const N: usize>(bytes: &mut &[u8]) -> Result<[u8; N]> {
+    let (head, rest) = bytes.split_first_chunk::<N>()
+        .ok_or_else(|| anyhow!("payload too short"))?;
+    *bytes = rest;
+    Ok(*head)
+}
+
+/// Characters of a `char[]` or `string`, up to the first NUL
+fn decode_string(bytes: &[u8]) -> FieldValue {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    FieldValue::String(String::from_utf8_lossy(&bytes[..end]).into_owned())
+}
+
+impl FieldType {
+    /// Decode one little-endian value off the front of a binary payload.
+    ///
+    /// Arrays without a declared size and strings are prefixed with their length as a uint8.
+    pub fn decode_binary(&self, bytes: &mut &[u8]) -> Result<FieldValue> {
+        Ok(match self {
+            FieldType::Uint8 => FieldValue::Uint8(u8::from_le_bytes(take(bytes)?)),
+            FieldType::Int8 => FieldValue::Int8(i8::from_le_bytes(take(bytes)?)),
+            FieldType::Uint16 => FieldValue::Uint16(u16::from_le_bytes(take(bytes)?)),
+            FieldType::Int16 => FieldValue::Int16(i16::from_le_bytes(take(bytes)?)),
+            FieldType::Uint32 => FieldValue::Uint32(u32::from_le_bytes(take(bytes)?)),
+            FieldType::Int32 => FieldValue::Int32(i32::from_le_bytes(take(bytes)?)),
+            FieldType::Uint64 => FieldValue::Uint64(u64::from_le_bytes(take(bytes)?)),
+            FieldType::Int64 => FieldValue::Int64(i64::from_le_bytes(take(bytes)?)),
+            FieldType::Float => FieldValue::Float(f32::from_le_bytes(take(bytes)?)),
+            FieldType::Double => FieldValue::Double(f64::from_le_bytes(take(bytes)?)),
+            FieldType::Char => decode_string(&take::<1>(bytes)?),
+            FieldType::String => {
+                let [len] = take(bytes)?;
+                let (content, rest) = bytes.split_at_checked(len as usize)
+                    .ok_or_else(|| anyhow!("string of {} bytes past the end of the payload", len))?;
+                *bytes = rest;
+                decode_string(content)
+            }
+            FieldType::Array { element_type, size } => {
+                let count = match size {
+                    Some(size) => *size,
+                    None => u8::from_le_bytes(take(bytes)?) as usize,
+                };
+                if matches!(**element_type, FieldType::Char | FieldType::String) {
+                    let (content, rest) = bytes.split_at_checked(count)
+                        .ok_or_else(|| anyhow!("{} characters past the end of the payload", count))?;
+                    *bytes = rest;
+                    return Ok(decode_string(content));
+                }
+                let elements = (0..count)
+                    .map(|_| element_type.decode_binary(bytes))
+                    .collect::<Result<Vec<_>>>()?;
+                FieldValue::Array(elements)
+            }
+        })
+    }
+
+    /// Append a value to a binary payload, the inverse of `decode_binary`
+    pub fn encode_binary(&self, value: &FieldValue, out: &mut Vec<u8>) -> Result<()> {
+        let as_number = |value: &FieldValue| value.as_f64()
+            .ok_or_else(|| anyhow!("expected a number, got {:?}", value));
+
+        match (self, value) {
+            (FieldType::Char | FieldType::String, FieldValue::String(s)) => {
+                if *self == FieldType::String {
+                    out.push(u8::try_from(s.len()).map_err(|_| anyhow!("string too long"))?);
+                }
+                out.extend(s.as_bytes());
+                if *self == FieldType::Char && s.is_empty() {
+                    out.push(0);
+                }
+            }
+            (FieldType::Array { element_type, size }, value) => {
+                if matches!(**element_type, FieldType::Char | FieldType::String) {
+                    let FieldValue::String(s) = value else {
+                        return Err(anyhow!("expected a string, got {:?}", value));
+                    };
+                    let mut content = s.as_bytes().to_vec();
+                    match size {
+                        Some(size) => content.resize(*size, 0),
+                        None => out.push(u8::try_from(content.len()).map_err(|_| anyhow!("string too long"))?),
+                    }
+                    out.extend(content);
+                    return Ok(());
+                }
+
+                let FieldValue::Array(elements) = value else {
+                    return Err(anyhow!("expected an array, got {:?}", value));
+                };
+                match size {
+                    Some(size) if *size != elements.len() => {
+                        return Err(anyhow!("expected {} array elements, got {}", size, elements.len()));
+                    }
+                    Some(_) => {}
+                    None => out.push(u8::try_from(elements.len()).map_err(|_| anyhow!("array too long"))?),
+                }
+                for element in elements {
+                    element_type.encode_binary(element, out)?;
+                }
+            }
+            (FieldType::Uint8, value) => out.extend((as_number(value)? as u8).to_le_bytes()),
+            (FieldType::Int8, value) => out.extend((as_number(value)? as i8).to_le_bytes()),
+            (FieldType::Uint16, value) => out.extend((as_number(value)? as u16).to_le_bytes()),
+            (FieldType::Int16, value) => out.extend((as_number(value)? as i16).to_le_bytes()),
+            (FieldType::Uint32, value) => out.extend((as_number(value)? as u32).to_le_bytes()),
+            (FieldType::Int32, value) => out.extend((as_number(value)? as i32).to_le_bytes()),
+            (FieldType::Uint64, FieldValue::Uint64(v)) => out.extend(v.to_le_bytes()),
+            (FieldType::Uint64, value) => out.extend((as_number(value)? as u64).to_le_bytes()),
+            (FieldType::Int64, FieldValue::Int64(v)) => out.extend(v.to_le_bytes()),
+            (FieldType::Int64, value) => out.extend((as_number(value)? as i64).to_le_bytes()),
+            (FieldType::Float, value) => out.extend((as_number(value)? as f32).to_le_bytes()),
+            (FieldType::Double, value) => out.extend(as_number(value)?.to_le_bytes()),
+            (FieldType::Char | FieldType::String, value) => {
+                return Err(anyhow!("expected a string, got {:?}", value));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::TelemetryReader;
+    use crate::schema::fixtures::sample_protocol;
+
+    const DATA: &str = "\
+1.000 38 ROTORCRAFT_FP 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15
+2.500 38 ALIVE 1,2,3
+3.000 13 GPS_INT 1 2 3 4 5 6 7 8 9 10 11 12 308361370 0 8 3 1
+4.000 0 SETTING 2 7 0.25
+";
+
+    #[test]
+    fn test_binary_frames_match_text() {
+        let protocol = sample_protocol();
+        let expected: Vec<_> = TelemetryReader::new(DATA.as_bytes(), &protocol, None)
+            .collect::<Result<_>>().unwrap();
+
+        for version in [PprzlinkVersion::V1, PprzlinkVersion::V2] {
+            // Line noise before the first frame, a corrupted frame and a frame the dictionary does not know
+            let mut capture = vec![0x00, PPRZ_STX, 0x02, 0x42];
+            for (i, message) in expected.iter().enumerate() {
+                let definition = protocol.get(&message.message_name).unwrap();
+                let mut frame = encode_frame(version, definition, message.sender_id, &message.fields).unwrap();
+                capture.extend(&frame);
+                if i == 1 {
+                    let last = frame.len() - 3;
+                    frame[last] ^= 0xff;
+                    capture.extend(&frame);
+                }
+            }
+            let unknown = MessageDefinition {
+                name: "MY_MODULE_STATUS".to_string(),
+                id: 250,
+                class: MessageClass::Telemetry,
+                class_id: 1,
+                fields: Vec::new(),
+            };
+            capture.extend(encode_frame(version, &unknown, 38, &HashMap::new()).unwrap());
+
+            assert_eq!(DataFormat::detect(&capture, &protocol), DataFormat::Pprzlink(version));
+            assert_eq!(DataFormat::detect(DATA.as_bytes(), &protocol), DataFormat::Text);
+
+            let mut reader = BinaryTelemetryReader::new(Cursor::new(&capture), &protocol, version, None);
+            let decoded: Vec<_> = reader.by_ref().collect::<Result<_>>().unwrap();
+            assert_eq!(decoded.len(), expected.len());
+            for (binary, text) in decoded.iter().zip(&expected) {
+                assert_eq!((binary.sender_id, binary.msg_class), (text.sender_id, text.msg_class));
+                assert_eq!(binary.message_name, text.message_name);
+                assert_eq!(binary.fields, text.fields);
+            }
+            assert!(decoded.windows(2).all(|pair| pair[0].timestamp < pair[1].timestamp));
+
+            let progress = reader.progress();
+            assert_eq!(progress.bytes_read, capture.len() as u64);
+            assert_eq!((progress.checksum_errors, progress.resyncs), (1, 2));
+            assert_eq!((progress.messages_decoded, progress.lines_skipped), (4, 1));
+            assert_eq!(progress.mismatches.unknown_messages["telemetry:250"], 1);
+            let kinds: Vec<_> = progress.diagnostics.entries.iter().map(|d| (d.line, d.kind)).collect();
+            assert_eq!(kinds, [(3, DiagnosticKind::ChecksumError), (6, DiagnosticKind::UnknownMessage)]);
+
+            // The same messages logged on board, stamped with the pprzlog record time
+            let mut tlm = vec![0x00];
+            for message in &expected {
+                let definition = protocol.get(&message.message_name).unwrap();
+                tlm.extend(encode_pprzlog_record(version, definition, message.sender_id, &message.fields, message.timestamp).unwrap());
+            }
+            assert_eq!(DataFormat::detect(&tlm, &protocol), DataFormat::Pprzlog(version));
+
+            let mut reader = DataFormat::Pprzlog(version).binary_reader(Cursor::new(&tlm), &protocol, None).unwrap();
+            let decoded: Vec<_> = reader.by_ref().collect::<Result<_>>().unwrap();
+            let timestamps: Vec<_> = decoded.iter().map(|m| (m.timestamp, m.message_name.as_str())).collect();
+            let expected_timestamps: Vec<_> = expected.iter().map(|m| (m.timestamp, m.message_name.as_str())).collect();
+            assert_eq!(timestamps, expected_timestamps);
+            assert!(decoded.iter().zip(&expected).all(|(binary, text)| binary.fields == text.fields));
+            assert_eq!((reader.progress().checksum_errors, reader.progress().resyncs), (0, 1));
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! Message dictionaries shipped with the backend, used to decode a .data file uploaded without its .log
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+use super::{DataFormat, MessageDictionary, PprzlinkVersion};
+
+/// A pprzlink messages.xml compiled into the binary
+#[derive(Debug)]
+pub struct BundledDictionary {
+    pub name: &'static str,
+    pub version: &'static str,
+    /// Transport version the dictionary was released with
+    pub transport: PprzlinkVersion,
+    xml: &'static str,
+    parsed: OnceLock<Result<MessageDictionary, String>>,
+}
+
+/// pprzlink v2 dictionary, taken from the `<protocol>` section of a paparazzi v7.0 log
+pub static PPRZLINK_V2: BundledDictionary = BundledDictionary {
+    name: "pprzlink",
+    version: "2.0",
+    transport: PprzlinkVersion::V2,
+    xml: include_str!("messages/pprzlink_v2.xml"),
+    parsed: OnceLock::new(),
+};
+
+/// Every dictionary compiled into the binary, one per transport version at most.
+///
+/// There is no pprzlink v1 messages.xml to ship yet: a v1 capture without its .log
+/// needs a messages.xml attached, it is never decoded with the v2 message ids.
+pub static BUNDLED_DICTIONARIES: [&BundledDictionary; 1] = [&PPRZLINK_V2];
+
+/// Dictionary used when a text .data file has no .log
+pub fn default_dictionary() -> &'static BundledDictionary {
+    &PPRZLINK_V2
+}
+
+/// Dictionary released with the transport version of a capture, the default one for text files
+/// (which name their messages instead of numbering them)
+pub fn bundled_dictionary(format: DataFormat) -> Option<&'static BundledDictionary> {
+    match format {
+        DataFormat::Text => Some(default_dictionary()),
+        DataFormat::Pprzlink(version) | DataFormat::Pprzlog(version) => BUNDLED_DICTIONARIES.iter()
+            .find(|bundled| bundled.transport == version)
+            .copied(),
+    }
+}
+
+impl BundledDictionary {
+    /// Parsed dictionary; the XML is only parsed on first use
+    pub fn load(&self) -> Result<MessageDictionary> {
+        self.parsed
+            .get_or_init(|| MessageDictionary::from_xml(self.xml).map_err(|e| e.to_string()))
+            .clone()
+            .map_err(|e| anyhow!("Bundled {} v{} dictionary is invalid: {}", self.name, self.version, e))
+    }
+
+    pub fn source(&self) -> DictionarySource {
+        DictionarySource::Bundled {
+            name: self.name.to_string(),
+            version: self.version.to_string(),
+        }
+    }
+}
+
+/// Where the message dictionary used to decode a .data file comes from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum DictionarySource {
+    /// `<protocol>` section of the matching .log
+    Log,
+    /// Built-in dictionary, the .log was not available
+    Bundled { name: String, version: String },
+    /// messages.xml uploaded by a user and attached to the file pair
+    Artifact { name: String, version: i32, hash: String },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundled_dictionary() {
+        let dictionary = default_dictionary().load().unwrap();
+        assert_eq!(dictionary.len(), 365);
+        assert_eq!(dictionary.get("ROTORCRAFT_FP").unwrap().fields.len(), 15);
+        assert_eq!(default_dictionary().source(), DictionarySource::Bundled {
+            name: "pprzlink".to_string(),
+            version: "2.0".to_string(),
+        });
+        assert_eq!(bundled_dictionary(DataFormat::Pprzlink(PprzlinkVersion::V2)).unwrap().transport, PprzlinkVersion::V2);
+        assert_eq!(bundled_dictionary(DataFormat::Text).unwrap().version, "2.0");
+    }
+
+    #[test]
+    fn test_no_v1_fallback() {
+        // v1 message ids are not the v2 ones: no dictionary rather than the wrong one
+        assert!(bundled_dictionary(DataFormat::Pprzlink(PprzlinkVersion::V1)).is_none());
+        assert!(bundled_dictionary(DataFormat::Pprzlog(PprzlinkVersion::V1)).is_none());
+    }
+}
@@ -0,0 +1,604 @@
+//! Columnar cache of the decoded messages of a flight, one Arrow IPC file per message type
+//!
+//! Each table has a timestamp and a sender column followed by one typed column per field.
+//! A manifest records the time range of every record batch, so that queries read only the
+//! batches of the requested time range and only the requested columns.
+
+use anyhow::{anyhow, Result};
+use arrow_array::builder::{
+    Float32Builder, Float64Builder, Int8Builder, Int16Builder, Int32Builder, Int64Builder, ListBuilder,
+    StringBuilder, UInt8Builder, UInt16Builder, UInt32Builder, UInt64Builder,
+};
+use arrow_array::cast::AsArray;
+use arrow_array::types::{
+    Float32Type, Float64Type, Int8Type, Int16Type, Int32Type, Int64Type, UInt8Type, UInt16Type, UInt32Type, UInt64Type,
+};
+use arrow_array::{Array, ArrayRef, RecordBatch};
+use arrow_ipc::reader::FileReader;
+use arrow_ipc::writer::FileWriter;
+use arrow_schema::{DataType, Field, Schema};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_stream::{Stream, StreamExt};
+use tracing::info;
+
+use super::{ChannelRef, FieldType, FieldValue, MessageDefinition, MessageDictionary, TelemetryMessage};
+
+/// Version of the decoding behind the cache; bump it when a message decodes to different values,
+/// so that every cache is rebuilt
+pub const PARSER_VERSION: u32 = 1;
+
+/// Rows per record batch, the unit a time range query skips or reads, unless set with
+/// [`ColumnarCache::with_batch_rows`]
+pub const BATCH_ROWS: usize = 65_536;
+
+/// Messages handed to the thread of a [`ColumnarWriterTask`] at a time
+const WRITER_CHUNK: usize = 1024;
+
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// Column names of the message header, underscored so that they cannot clash with a field
+const TIMESTAMP_COLUMN: &str = "_timestamp";
+const SENDER_COLUMN: &str = "_sender_id";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColumnarManifest {
+    pub parser_version: u32,
+    /// `schema_hash` of the dictionary the messages were decoded with
+    pub schema_hash: String,
+    pub built_at: DateTime<Utc>,
+    /// Tables by message name
+    pub tables: BTreeMap<String, ColumnarTable>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColumnarTable {
+    pub file: String,
+    pub rows: usize,
+    /// Field columns, after the timestamp and sender ones
+    pub columns: Vec<String>,
+    pub batches: Vec<BatchRange>,
+}
+
+/// Rows and time range of a record batch
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchRange {
+    pub rows: usize,
+    pub start: f64,
+    pub end: f64,
+}
+
+impl BatchRange {
+    fn overlaps(&self, start: Option<f64>, end: Option<f64>) -> bool {
+        start.is_none_or(|start| self.end >= start) && end.is_none_or(|end| self.start <= end)
+    }
+}
+
+/// Samples of one channel, with raw values
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChannelSamples {
+    pub timestamps: Vec<f64>,
+    pub values: Vec<f64>,
+}
+
+/// Columnar cache of a file pair, stored in its own directory
+pub struct ColumnarCache {
+    dir: PathBuf,
+    batch_rows: usize,
+}
+
+impl ColumnarCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into(), batch_rows: BATCH_ROWS }
+    }
+
+    /// Rows per record batch of the tables written from now on
+    pub fn with_batch_rows(mut self, batch_rows: usize) -> Self {
+        self.batch_rows = batch_rows.max(1);
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn manifest(&self) -> Option<ColumnarManifest> {
+        let bytes = fs::read(self.dir.join(MANIFEST_FILE)).ok()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+
+    /// Manifest of the cache if it was built by this parser from the same dictionary
+    pub fn current(&self, schema_hash: &str) -> Option<ColumnarManifest> {
+        self.manifest()
+            .filter(|manifest| manifest.parser_version == PARSER_VERSION && manifest.schema_hash == schema_hash)
+    }
+
+    /// Start a new cache in a staging directory, fed one message at a time
+    pub fn writer(&self, protocol: &MessageDictionary, schema_hash: &str) -> Result<ColumnarWriter> {
+        let staging = self.dir.with_extension(format!("tmp-{}", uuid::Uuid::new_v4().simple()));
+        fs::create_dir_all(&staging)?;
+        Ok(ColumnarWriter {
+            dir: self.dir.clone(),
+            staging,
+            batch_rows: self.batch_rows,
+            protocol: protocol.clone(),
+            schema_hash: schema_hash.to_string(),
+            tables: BTreeMap::new(),
+        })
+    }
+
+    /// Write the cache of a stream of messages, replacing the previous one once complete
+    pub async fn build<S>(&self, messages: &mut S, protocol: &MessageDictionary, schema_hash: &str) -> Result<ColumnarManifest>
+    where
+        S: Stream<Item = Result<TelemetryMessage>> + Unpin,
+    {
+        let mut writer = ColumnarWriterTask::spawn(self.writer(protocol, schema_hash)?);
+        while let Some(message) = messages.next().await {
+            writer.add(message?).await;
+        }
+        writer.finish().await
+    }
+
+    /// Samples of channels of a single message, reading only their columns and the batches of the time range
+    pub fn read_channels(
+        &self,
+        manifest: &ColumnarManifest,
+        message: &str,
+        channels: &[ChannelRef],
+        start: Option<f64>,
+        end: Option<f64>,
+        sender_id: Option<u8>,
+    ) -> Result<Vec<ChannelSamples>> {
+        let mut samples = vec![ChannelSamples::default(); channels.len()];
+        let Some(table) = manifest.tables.get(message) else {
+            return Ok(samples);
+        };
+
+        // Timestamp, sender, then the columns of the channels in projection order
+        let mut projection = vec![0, 1];
+        let mut column_of = Vec::with_capacity(channels.len());
+        for channel in channels {
+            column_of.push(table.columns.iter().position(|column| *column == channel.field).map(|index| {
+                let index = index + 2;
+                projection.iter().position(|&p| p == index).unwrap_or_else(|| {
+                    projection.push(index);
+                    projection.len() - 1
+                })
+            }));
+        }
+
+        let file = File::open(self.dir.join(&table.file))?;
+        let mut reader = FileReader::try_new(BufReader::new(file), Some(projection))?;
+        for (index, range) in table.batches.iter().enumerate() {
+            if !range.overlaps(start, end) {
+                continue;
+            }
+            reader.set_index(index)?;
+            let batch = reader.next().ok_or_else(|| anyhow!("Missing batch {} of {}", index, table.file))??;
+            let timestamps = batch.column(0).as_primitive::<Float64Type>();
+            let senders = batch.column(1).as_primitive::<UInt8Type>();
+
+            for row in 0..batch.num_rows() {
+                let timestamp = timestamps.value(row);
+                if start.is_some_and(|start| timestamp < start)
+                    || end.is_some_and(|end| timestamp > end)
+                    || sender_id.is_some_and(|sender_id| senders.value(row) != sender_id)
+                {
+                    continue;
+                }
+                for ((channel, column), entry) in channels.iter().zip(&column_of).zip(samples.iter_mut()) {
+                    let value = column.and_then(|column| numeric_value(batch.column(column).as_ref(), row, channel.index));
+                    if let Some(value) = value {
+                        entry.timestamps.push(timestamp);
+                        entry.values.push(value);
+                    }
+                }
+            }
+        }
+        Ok(samples)
+    }
+}
+
+/// Cache being written; dropped unfinished, its staging directory is removed
+pub struct ColumnarWriter {
+    dir: PathBuf,
+    staging: PathBuf,
+    batch_rows: usize,
+    protocol: MessageDictionary,
+    schema_hash: String,
+    tables: BTreeMap<String, TableWriter>,
+}
+
+impl ColumnarWriter {
+    pub fn add(&mut self, message: &TelemetryMessage) -> Result<()> {
+        if !self.tables.contains_key(&message.message_name) {
+            let Some(definition) = self.protocol.get(&message.message_name) else {
+                return Ok(());
+            };
+            self.tables.insert(message.message_name.clone(), TableWriter::new(&self.staging, definition, self.batch_rows));
+        }
+        match self.tables.get_mut(&message.message_name) {
+            Some(table) => table.add(message),
+            None => Ok(()),
+        }
+    }
+
+    /// Write the manifest and replace the previous cache with this one. The previous cache is
+    /// renamed aside first, so the cache directory is never left half deleted.
+    pub fn finish(mut self) -> Result<ColumnarManifest> {
+        let manifest = ColumnarManifest {
+            parser_version: PARSER_VERSION,
+            schema_hash: self.schema_hash.clone(),
+            built_at: Utc::now(),
+            tables: std::mem::take(&mut self.tables).into_iter()
+                .map(|(name, table)| Ok((name, table.finish()?)))
+                .collect::<Result<_>>()?,
+        };
+        // Written last: a directory without manifest is an incomplete cache
+        fs::write(self.staging.join(MANIFEST_FILE), serde_json::to_vec_pretty(&manifest)?)?;
+
+        let previous = self.dir.with_extension(format!("old-{}", uuid::Uuid::new_v4().simple()));
+        let replaced = fs::rename(&self.dir, &previous).is_ok();
+        let installed = fs::rename(&self.staging, &self.dir);
+        if installed.is_err() && replaced && !self.dir.exists() {
+            let _ = fs::rename(&previous, &self.dir);
+        }
+        let _ = fs::remove_dir_all(&previous);
+        if let Err(e) = installed {
+            // Another build of the same cache finished first
+            if ColumnarCache::new(&self.dir).manifest().is_none() {
+                return Err(e.into());
+            }
+        }
+        info!("Built columnar cache {:?}: {} table(s), {} rows", self.dir, manifest.tables.len(),
+            manifest.tables.values().map(|table| table.rows).sum::<usize>());
+        Ok(manifest)
+    }
+}
+
+impl Drop for ColumnarWriter {
+    fn drop(&mut self) {
+        // Gone once the cache was moved in place
+        let _ = fs::remove_dir_all(&self.staging);
+    }
+}
+
+/// [`ColumnarWriter`] running on a blocking thread, so that its file I/O stays off the async workers.
+///
+/// Dropped without [`finish`](Self::finish), the cache is abandoned.
+pub struct ColumnarWriterTask {
+    chunk: Vec<TelemetryMessage>,
+    sender: mpsc::Sender<Vec<TelemetryMessage>>,
+    commit: Arc<AtomicBool>,
+    task: JoinHandle<Result<Option<ColumnarManifest>>>,
+}
+
+impl ColumnarWriterTask {
+    pub fn spawn(mut writer: ColumnarWriter) -> Self {
+        let (sender, mut receiver) = mpsc::channel::<Vec<TelemetryMessage>>(4);
+        let commit = Arc::new(AtomicBool::new(false));
+        let committed = commit.clone();
+        let task = tokio::task::spawn_blocking(move || {
+            while let Some(chunk) = receiver.blocking_recv() {
+                for message in &chunk {
+                    writer.add(message)?;
+                }
+            }
+            match committed.load(Ordering::SeqCst) {
+                true => writer.finish().map(Some),
+                false => Ok(None),
+            }
+        });
+        Self { chunk: Vec::with_capacity(WRITER_CHUNK), sender, commit, task }
+    }
+
+    /// Queue a message; once the writer failed, messages are dropped and `finish` returns its error
+    pub async fn add(&mut self, message: TelemetryMessage) {
+        self.chunk.push(message);
+        if self.chunk.len() == WRITER_CHUNK {
+            let chunk = std::mem::replace(&mut self.chunk, Vec::with_capacity(WRITER_CHUNK));
+            let _ = self.sender.send(chunk).await;
+        }
+    }
+
+    pub async fn finish(self) -> Result<ColumnarManifest> {
+        if !self.chunk.is_empty() {
+            let _ = self.sender.send(self.chunk).await;
+        }
+        self.commit.store(true, Ordering::SeqCst);
+        drop(self.sender);
+        self.task.await??.ok_or_else(|| anyhow!("Columnar writer stopped before it was finished"))
+    }
+}
+
+/// Arrow IPC file of one message type, written a record batch at a time
+struct TableWriter {
+    file: String,
+    path: PathBuf,
+    columns: Vec<String>,
+    timestamps: Float64Builder,
+    senders: UInt8Builder,
+    values: Vec<Column>,
+    writer: Option<FileWriter<BufWriter<File>>>,
+    batches: Vec<BatchRange>,
+    /// Time range of the batch being built
+    range: Option<(f64, f64)>,
+    rows: usize,
+    batch_rows: usize,
+}
+
+impl TableWriter {
+    fn new(dir: &Path, definition: &MessageDefinition, batch_rows: usize) -> Self {
+        let file = format!("{}.arrow", definition.name.replace(|c: char| !c.is_ascii_alphanumeric() && c != '_', "_"));
+        Self {
+            path: dir.join(&file),
+            file,
+            columns: definition.fields.iter().map(|field| field.name.clone()).collect(),
+            timestamps: Float64Builder::new(),
+            senders: UInt8Builder::new(),
+            values: definition.fields.iter().map(|field| Column::new(&field.field_type)).collect(),
+            writer: None,
+            batches: Vec::new(),
+            range: None,
+            rows: 0,
+            batch_rows,
+        }
+    }
+
+    fn add(&mut self, message: &TelemetryMessage) -> Result<()> {
+        self.timestamps.append_value(message.timestamp);
+        self.senders.append_value(message.sender_id);
+        for (name, column) in self.columns.iter().zip(&mut self.values) {
+            column.append(message.fields.get(name));
+        }
+        self.range = Some(match self.range {
+            Some((start, end)) => (start.min(message.timestamp), end.max(message.timestamp)),
+            None => (message.timestamp, message.timestamp),
+        });
+        self.rows += 1;
+        if self.rows == self.batch_rows {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let Some((start, end)) = self.range.take() else {
+            return Ok(());
+        };
+        let mut fields = vec![
+            Field::new(TIMESTAMP_COLUMN, DataType::Float64, false),
+            Field::new(SENDER_COLUMN, DataType::UInt8, false),
+        ];
+        let mut arrays: Vec<ArrayRef> = vec![Arc::new(self.timestamps.finish()), Arc::new(self.senders.finish())];
+        for (name, column) in self.columns.iter().zip(&mut self.values) {
+            let array = column.finish();
+            fields.push(Field::new(name, array.data_type().clone(), true));
+            arrays.push(array);
+        }
+        let batch = RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays)?;
+
+        let writer = match self.writer.take() {
+            Some(writer) => writer,
+            None => FileWriter::try_new(BufWriter::new(File::create(&self.path)?), &batch.schema())?,
+        };
+        self.writer.insert(writer).write(&batch)?;
+        self.batches.push(BatchRange { rows: self.rows, start, end });
+        self.rows = 0;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<ColumnarTable> {
+        self.flush()?;
+        if let Some(writer) = &mut self.writer {
+            writer.finish()?;
+        }
+        Ok(ColumnarTable {
+            file: self.file,
+            rows: self.batches.iter().map(|batch| batch.rows).sum(),
+            columns: self.columns,
+            batches: self.batches,
+        })
+    }
+}
+
+/// Builder of the column of a field, typed after its declaration
+enum Column {
+    UInt8(UInt8Builder),
+    Int8(Int8Builder),
+    UInt16(UInt16Builder),
+    Int16(Int16Builder),
+    UInt32(UInt32Builder),
+    Int32(Int32Builder),
+    UInt64(UInt64Builder),
+    Int64(Int64Builder),
+    Float32(Float32Builder),
+    Float64(Float64Builder),
+    Utf8(StringBuilder),
+    /// Numeric arrays, as float64 which holds every element type but 64-bit integers exactly
+    List(ListBuilder<Float64Builder>),
+}
+
+impl Column {
+    fn new(field_type: &FieldType) -> Self {
+        match field_type {
+            FieldType::Uint8 => Column::UInt8(UInt8Builder::new()),
+            FieldType::Int8 => Column::Int8(Int8Builder::new()),
+            FieldType::Uint16 => Column::UInt16(UInt16Builder::new()),
+            FieldType::Int16 => Column::Int16(Int16Builder::new()),
+            FieldType::Uint32 => Column::UInt32(UInt32Builder::new()),
+            FieldType::Int32 => Column::Int32(Int32Builder::new()),
+            FieldType::Uint64 => Column::UInt64(UInt64Builder::new()),
+            FieldType::Int64 => Column::Int64(Int64Builder::new()),
+            FieldType::Float => Column::Float32(Float32Builder::new()),
+            FieldType::Double => Column::Float64(Float64Builder::new()),
+            FieldType::Char | FieldType::String => Column::Utf8(StringBuilder::new()),
+            // char[] decodes to a string
+            FieldType::Array { element_type, .. } if matches!(**element_type, FieldType::Char | FieldType::String) =>
+                Column::Utf8(StringBuilder::new()),
+            FieldType::Array { .. } => Column::List(ListBuilder::new(Float64Builder::new())),
+        }
+    }
+
+    /// Append a value, or null when it is missing or not of the declared type
+    fn append(&mut self, value: Option<&FieldValue>) {
+        match (self, value) {
+            (Column::UInt8(b), Some(FieldValue::Uint8(v))) => b.append_value(*v),
+            (Column::Int8(b), Some(FieldValue::Int8(v))) => b.append_value(*v),
+            (Column::UInt16(b), Some(FieldValue::Uint16(v))) => b.append_value(*v),
+            (Column::Int16(b), Some(FieldValue::Int16(v))) => b.append_value(*v),
+            (Column::UInt32(b), Some(FieldValue::Uint32(v))) => b.append_value(*v),
+            (Column::Int32(b), Some(FieldValue::Int32(v))) => b.append_value(*v),
+            (Column::UInt64(b), Some(FieldValue::Uint64(v))) => b.append_value(*v),
+            (Column::Int64(b), Some(FieldValue::Int64(v))) => b.append_value(*v),
+            (Column::Float32(b), Some(FieldValue::Float(v))) => b.append_value(*v),
+            (Column::Float64(b), Some(FieldValue::Double(v))) => b.append_value(*v),
+            (Column::Utf8(b), Some(FieldValue::String(v))) => b.append_value(v),
+            (Column::List(b), Some(FieldValue::Array(elements))) => {
+                for element in elements {
+                    b.values().append_option(element.as_f64());
+                }
+                b.append(true);
+            }
+            (Column::UInt8(b), _) => b.append_null(),
+            (Column::Int8(b), _) => b.append_null(),
+            (Column::UInt16(b), _) => b.append_null(),
+            (Column::Int16(b), _) => b.append_null(),
+            (Column::UInt32(b), _) => b.append_null(),
+            (Column::Int32(b), _) => b.append_null(),
+            (Column::UInt64(b), _) => b.append_null(),
+            (Column::Int64(b), _) => b.append_null(),
+            (Column::Float32(b), _) => b.append_null(),
+            (Column::Float64(b), _) => b.append_null(),
+            (Column::Utf8(b), _) => b.append_null(),
+            (Column::List(b), _) => b.append_null(),
+        }
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            Column::UInt8(b) => Arc::new(b.finish()),
+            Column::Int8(b) => Arc::new(b.finish()),
+            Column::UInt16(b) => Arc::new(b.finish()),
+            Column::Int16(b) => Arc::new(b.finish()),
+            Column::UInt32(b) => Arc::new(b.finish()),
+            Column::Int32(b) => Arc::new(b.finish()),
+            Column::UInt64(b) => Arc::new(b.finish()),
+            Column::Int64(b) => Arc::new(b.finish()),
+            Column::Float32(b) => Arc::new(b.finish()),
+            Column::Float64(b) => Arc::new(b.finish()),
+            Column::Utf8(b) => Arc::new(b.finish()),
+            Column::List(b) => Arc::new(b.finish()),
+        }
+    }
+}
+
+/// Numeric value of a row of a column, or of an element of it for an array field
+fn numeric_value(array: &dyn Array, row: usize, index: Option<usize>) -> Option<f64> {
+    if array.is_null(row) {
+        return None;
+    }
+    let value = match (array.data_type(), index) {
+        (DataType::List(_), Some(index)) => {
+            let elements = array.as_list::<i32>().value(row);
+            let elements = elements.as_primitive::<Float64Type>();
+            return (index < elements.len() && !elements.is_null(index)).then(|| elements.value(index));
+        }
+        (_, Some(_)) => return None,
+        (DataType::UInt8, None) => array.as_primitive::<UInt8Type>().value(row) as f64,
+        (DataType::Int8, None) => array.as_primitive::<Int8Type>().value(row) as f64,
+        (DataType::UInt16, None) => array.as_primitive::<UInt16Type>().value(row) as f64,
+        (DataType::Int16, None) => array.as_primitive::<Int16Type>().value(row) as f64,
+        (DataType::UInt32, None) => array.as_primitive::<UInt32Type>().value(row) as f64,
+        (DataType::Int32, None) => array.as_primitive::<Int32Type>().value(row) as f64,
+        (DataType::UInt64, None) => array.as_primitive::<UInt64Type>().value(row) as f64,
+        (DataType::Int64, None) => array.as_primitive::<Int64Type>().value(row) as f64,
+        (DataType::Float32, None) => array.as_primitive::<Float32Type>().value(row) as f64,
+        (DataType::Float64, None) => array.as_primitive::<Float64Type>().value(row),
+        _ => return None,
+    };
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::TelemetryReader;
+
+    const PROTOCOL: &str = r#"<protocol>
+    <msg_class NAME="telemetry" ID="1">
+      <message NAME="GPS_INT" ID="155">
+        <field TYPE="int32" NAME="alt" UNIT="mm"/>
+        <field TYPE="uint8" NAME="fix"/>
+      </message>
+      <message NAME="IMU_GYRO_RAW" ID="203">
+        <field TYPE="int32[3]" NAME="gp"/>
+        <field TYPE="char[]" NAME="desc"/>
+      </message>
+    </msg_class>
+  </protocol>"#;
+
+    #[tokio::test]
+    async fn test_columnar_cache() {
+        let protocol = MessageDictionary::from_xml(PROTOCOL).unwrap();
+        let data = "1.0 1 GPS_INT 1000 3\n1.5 1 IMU_GYRO_RAW 1,2,3 \"a b\"\n2.0 2 GPS_INT 2000 3\n\
+            2.5 1 IMU_GYRO_RAW 4,5,6 x\n3.0 1 GPS_INT 3000 0\n";
+        let dir = std::env::temp_dir().join(format!("ppz-columnar-{}", uuid::Uuid::new_v4()));
+        let cache = ColumnarCache::new(&dir);
+        assert!(cache.current("hash").is_none());
+
+        let mut messages = tokio_stream::iter(TelemetryReader::new(data.as_bytes(), &protocol, None));
+        let manifest = cache.build(&mut messages, &protocol, "hash").await.unwrap();
+        assert_eq!(cache.current("hash"), Some(manifest.clone()));
+        assert!(cache.current("other").is_none());
+        let gps = &manifest.tables["GPS_INT"];
+        assert_eq!((gps.rows, gps.columns.as_slice()), (3, ["alt".to_string(), "fix".to_string()].as_slice()));
+        assert_eq!(gps.batches, [BatchRange { rows: 3, start: 1.0, end: 3.0 }]);
+
+        let channels: Vec<ChannelRef> = ["GPS_INT.alt".parse().unwrap(), "GPS_INT.fix".parse().unwrap()].into();
+        let samples = cache.read_channels(&manifest, "GPS_INT", &channels, Some(1.5), None, Some(1)).unwrap();
+        assert_eq!(samples[0], ChannelSamples { timestamps: vec![3.0], values: vec![3000.0] });
+        assert_eq!(samples[1].values, [0.0]);
+
+        let gyro = ["IMU_GYRO_RAW.gp[2]".parse().unwrap(), "IMU_GYRO_RAW.desc".parse().unwrap()];
+        let samples = cache.read_channels(&manifest, "IMU_GYRO_RAW", &gyro, None, None, None).unwrap();
+        assert_eq!(samples[0], ChannelSamples { timestamps: vec![1.5, 2.5], values: vec![3.0, 6.0] });
+        assert!(samples[1].values.is_empty());
+        // Out of the time range of every batch
+        let samples = cache.read_channels(&manifest, "GPS_INT", &channels, Some(10.0), None, None).unwrap();
+        assert!(samples[0].timestamps.is_empty());
+
+        // Rebuilt in batches of 4 rows in place of the first cache; a range reads only its batches
+        let data: String = (0..10).map(|i| format!("{}.0 1 GPS_INT {} 3\n", i, i * 100)).collect();
+        let cache = ColumnarCache::new(&dir).with_batch_rows(4);
+        let mut messages = tokio_stream::iter(TelemetryReader::new(data.as_bytes(), &protocol, None));
+        let manifest = cache.build(&mut messages, &protocol, "hash").await.unwrap();
+        assert!(!manifest.tables.contains_key("IMU_GYRO_RAW"));
+        let ranges: Vec<_> = manifest.tables["GPS_INT"].batches.iter()
+            .map(|batch| (batch.rows, batch.start, batch.end))
+            .collect();
+        assert_eq!(ranges, [(4, 0.0, 3.0), (4, 4.0, 7.0), (2, 8.0, 9.0)]);
+        let samples = cache.read_channels(&manifest, "GPS_INT", &channels[..1], Some(2.5), Some(8.0), None).unwrap();
+        assert_eq!(samples[0].timestamps, [3.0, 4.0, 5.0, 6.0, 7.0, 8.0]);
+        assert_eq!(samples[0].values, [300.0, 400.0, 500.0, 600.0, 700.0, 800.0]);
+        let samples = cache.read_channels(&manifest, "GPS_INT", &channels[..1], Some(4.5), Some(6.0), None).unwrap();
+        assert_eq!(samples[0].timestamps, [5.0, 6.0]);
+
+        // Neither the staging nor the previous cache is left next to it
+        let name = dir.file_name().unwrap().to_string_lossy().to_string();
+        let siblings = fs::read_dir(dir.parent().unwrap()).unwrap()
+            .filter(|entry| entry.as_ref().unwrap().file_name().to_string_lossy().starts_with(&name))
+            .count();
+        assert_eq!(siblings, 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,185 @@
+//! Check of the airframe configuration of a .log against the md5sum the aircraft reports in `ALIVE`
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::{Diagnostic, DiagnosticKind, FieldValue, LogConfiguration, MessageClass, TelemetryMessage};
+
+/// Outcome of comparing the `ALIVE` md5sum of an aircraft with its configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigCheckStatus {
+    Match,
+    /// The configuration analysed is not the one that flew
+    Mismatch,
+    /// No airframe for the sender, or firmware built without an md5sum
+    Unknown,
+    /// Only the airframe embedded in the .log is known, whose md5 is not the one of the file
+    /// the firmware was built from
+    Unverifiable,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConfigCheck {
+    pub sender_id: u8,
+    pub aircraft: Option<String>,
+    /// md5sum of the first `ALIVE` of the sender, in hex
+    pub alive_md5: String,
+    /// md5 of the airframe configuration the flight is analysed with
+    pub config_md5: Option<String>,
+    pub status: ConfigCheckStatus,
+    /// Timestamp of the first `ALIVE` of the sender
+    pub timestamp: f64,
+}
+
+impl ConfigCheck {
+    /// File-level diagnostic for a mismatch
+    pub fn diagnostic(&self) -> Option<Diagnostic> {
+        if self.status != ConfigCheckStatus::Mismatch {
+            return None;
+        }
+        Some(Diagnostic {
+            line: 0,
+            byte_offset: 0,
+            kind: DiagnosticKind::ConfigMismatch,
+            message: format!("Aircraft {} reports configuration md5 {}, the airframe analysed has {}",
+                self.aircraft.as_deref().unwrap_or(&self.sender_id.to_string()),
+                self.alive_md5,
+                self.config_md5.as_deref().unwrap_or_default()),
+            snippet: format!("{:.3} {} ALIVE {}", self.timestamp, self.sender_id, self.alive_md5),
+            suggestion: "The attached airframe file is not the one the firmware was built from; \
+                attach the airframe file that was flashed".to_string(),
+        })
+    }
+}
+
+/// md5sum reported by each aircraft, collected while streaming a .data file
+#[derive(Debug, Default)]
+pub struct AliveMd5s {
+    by_sender: BTreeMap<u8, (f64, String)>,
+}
+
+impl AliveMd5s {
+    pub fn add(&mut self, message: &TelemetryMessage) {
+        if message.msg_class != MessageClass::Telemetry
+            || message.message_name != "ALIVE"
+            || self.by_sender.contains_key(&message.sender_id)
+        {
+            return;
+        }
+        if let Some(FieldValue::Array(bytes)) = message.fields.get("md5sum") {
+            let md5 = bytes.iter()
+                .filter_map(|byte| byte.as_f64())
+                .map(|byte| format!("{:02x}", byte as u8))
+                .collect();
+            self.by_sender.insert(message.sender_id, (message.timestamp, md5));
+        }
+    }
+
+    /// Compare every reported md5sum with the airframe of the same sender
+    pub fn check(&self, config: &LogConfiguration) -> Vec<ConfigCheck> {
+        self.by_sender.iter()
+            .map(|(&sender_id, (timestamp, alive_md5))| {
+                let airframe = config.airframe_for_sender(sender_id);
+                let config_md5 = airframe.and_then(|airframe| airframe.md5.clone());
+                let unset = alive_md5.is_empty() || alive_md5.chars().all(|c| c == '0');
+                let status = match &config_md5 {
+                    _ if unset => ConfigCheckStatus::Unknown,
+                    None if airframe.is_some() => ConfigCheckStatus::Unverifiable,
+                    None => ConfigCheckStatus::Unknown,
+                    Some(md5) if md5.eq_ignore_ascii_case(alive_md5) => ConfigCheckStatus::Match,
+                    Some(_) => ConfigCheckStatus::Mismatch,
+                };
+                ConfigCheck {
+                    sender_id,
+                    aircraft: config.aircraft_for_sender(sender_id).map(|aircraft| aircraft.name.clone()),
+                    alive_md5: alive_md5.clone(),
+                    config_md5,
+                    status,
+                    timestamp: *timestamp,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{AirframeConfig, SchemaManager, TelemetryReader};
+    use crate::schema::fixtures::sample_log;
+
+    fn alive(timestamp: f64, sender_id: u8, md5: &[u8]) -> String {
+        format!("{} {} ALIVE {}\n", timestamp, sender_id, md5.iter().map(u8::to_string).collect::<Vec<_>>().join(","))
+    }
+
+    #[test]
+    fn test_alive_md5_check() {
+        let log_content = sample_log();
+        let mut config = SchemaManager::new().parse_log_configuration(&log_content).unwrap();
+
+        // The airframe file of sender 13, as the firmware was built from it
+        let start = log_content.find(r#"<airframe NAME="ardrone2_indi">"#).unwrap();
+        let end = start + log_content[start..].find("</airframe>").unwrap() + "</airframe>".len();
+        let airframe_file = &log_content[start..end];
+        let flown = md5::compute(airframe_file).0;
+
+        let data = [alive(1.0, 13, &flown), alive(1.1, 12, &[7; 16]), alive(1.2, 14, &[0; 16]), alive(1.3, 250, &flown)].concat();
+        let mut alive = AliveMd5s::default();
+        for message in TelemetryReader::new(data.as_bytes(), &config.protocol, None) {
+            alive.add(&message.unwrap());
+        }
+
+        // Only the copies embedded in the .log: nothing can be told from their md5
+        let statuses: Vec<_> = alive.check(&config).iter().map(|c| (c.sender_id, c.status)).collect();
+        assert_eq!(statuses, [
+            (12, ConfigCheckStatus::Unverifiable),
+            (13, ConfigCheckStatus::Unverifiable),
+            (14, ConfigCheckStatus::Unknown),
+            (250, ConfigCheckStatus::Unknown),
+        ]);
+        assert!(alive.check(&config).iter().all(|check| check.diagnostic().is_none()));
+
+        // Airframe files attached to senders 12 and 13
+        config.airframes.insert(12, AirframeConfig::from_xml(airframe_file).unwrap());
+        config.airframes.insert(13, AirframeConfig::from_xml(airframe_file).unwrap());
+        let checks = alive.check(&config);
+        let statuses: Vec<_> = checks.iter().map(|c| (c.sender_id, c.status)).collect();
+        assert_eq!(&statuses[..2], [(12, ConfigCheckStatus::Mismatch), (13, ConfigCheckStatus::Match)]);
+        assert_eq!(checks[0].alive_md5, "07".repeat(16));
+        let diagnostic = checks[0].diagnostic().unwrap();
+        assert_eq!((diagnostic.line, diagnostic.kind), (0, DiagnosticKind::ConfigMismatch));
+        assert!(checks[1].diagnostic().is_none());
+    }
+
+    #[test]
+    fn test_ignored_alive_messages() {
+        let config = SchemaManager::new().parse_log_configuration(&sample_log()).unwrap();
+        let message = |msg_class, sender_id, md5sum: Option<FieldValue>| TelemetryMessage {
+            timestamp: 1.0,
+            sender_id,
+            message_id: 2,
+            msg_class,
+            message_name: "ALIVE".to_string(),
+            fields: md5sum.into_iter().map(|md5sum| ("md5sum".to_string(), md5sum)).collect(),
+        };
+        let bytes = |byte: u8| FieldValue::Array(vec![FieldValue::Uint8(byte); 16]);
+
+        let mut alive = AliveMd5s::default();
+        // Not telemetry, no md5sum, or an md5sum that is not an array
+        alive.add(&message(MessageClass::Datalink, 12, Some(bytes(7))));
+        alive.add(&message(MessageClass::Telemetry, 12, None));
+        alive.add(&message(MessageClass::Telemetry, 12, Some(FieldValue::String("07".repeat(16)))));
+        assert!(alive.check(&config).is_empty());
+
+        // Only the first md5sum of a sender counts, an empty one tells nothing
+        alive.add(&message(MessageClass::Telemetry, 12, Some(bytes(7))));
+        alive.add(&message(MessageClass::Telemetry, 12, Some(bytes(8))));
+        alive.add(&message(MessageClass::Telemetry, 13, Some(FieldValue::Array(Vec::new()))));
+        let checks = alive.check(&config);
+        assert_eq!(checks.len(), 2);
+        assert_eq!(checks[0].alive_md5, "07".repeat(16));
+        assert_eq!((checks[1].alive_md5.as_str(), checks[1].status), ("", ConfigCheckStatus::Unknown));
+        assert!(checks[1].diagnostic().is_none());
+    }
+}
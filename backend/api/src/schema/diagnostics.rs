@@ -0,0 +1,278 @@
+//! Structured diagnostics of the lines and frames a .data reader could not decode
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use super::{MessageDictionary, split_fields};
+
+/// Diagnostics kept per file; further ones are only counted
+pub const MAX_DIAGNOSTICS: usize = 500;
+
+/// Suggestion for a message the dictionary does not know
+pub const ATTACH_MESSAGES_XML: &str = "Upload the messages.xml the aircraft was built with and attach it to this file";
+
+/// Longest raw snippet kept in a diagnostic, in characters
+const SNIPPET_CHARS: usize = 160;
+
+/// Why a line or frame was not decoded (or decoded with a doubt)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticKind {
+    /// Message name or id missing from the dictionary
+    UnknownMessage,
+    /// Number of fields (or payload size) differs from the dictionary
+    ArityMismatch,
+    /// Timestamp, sender or field value that does not parse
+    BadNumber,
+    /// Line cut short, usually the last one of a log that was not closed
+    TruncatedLine,
+    /// Timestamp earlier than the one of the previous message; the message is still decoded
+    NonMonotonicTime,
+    /// Binary frame whose checksum does not match
+    ChecksumError,
+    /// `ALIVE` md5sum different from the md5 of the airframe configuration analysed
+    ConfigMismatch,
+    /// Section of the .log configuration that is not well-formed XML and was left out
+    MalformedConfig,
+}
+
+impl DiagnosticKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DiagnosticKind::UnknownMessage => "unknown_message",
+            DiagnosticKind::ArityMismatch => "arity_mismatch",
+            DiagnosticKind::BadNumber => "bad_number",
+            DiagnosticKind::TruncatedLine => "truncated_line",
+            DiagnosticKind::NonMonotonicTime => "non_monotonic_time",
+            DiagnosticKind::ChecksumError => "checksum_error",
+            DiagnosticKind::ConfigMismatch => "config_mismatch",
+            DiagnosticKind::MalformedConfig => "malformed_config",
+        }
+    }
+}
+
+impl fmt::Display for DiagnosticKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for DiagnosticKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "unknown_message" => Ok(DiagnosticKind::UnknownMessage),
+            "arity_mismatch" => Ok(DiagnosticKind::ArityMismatch),
+            "bad_number" => Ok(DiagnosticKind::BadNumber),
+            "truncated_line" => Ok(DiagnosticKind::TruncatedLine),
+            "non_monotonic_time" => Ok(DiagnosticKind::NonMonotonicTime),
+            "checksum_error" => Ok(DiagnosticKind::ChecksumError),
+            "config_mismatch" => Ok(DiagnosticKind::ConfigMismatch),
+            "malformed_config" => Ok(DiagnosticKind::MalformedConfig),
+            other => Err(anyhow::anyhow!("Unknown diagnostic kind: {}", other)),
+        }
+    }
+}
+
+/// One problem found while reading a .data file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    /// 1-based line number; for a binary capture, the frame number counting corrupted frames.
+    /// 0 for a diagnostic about the whole file
+    pub line: usize,
+    /// Offset of the start of the line or frame in the file
+    pub byte_offset: u64,
+    pub kind: DiagnosticKind,
+    pub message: String,
+    /// Start of the raw line, or the frame bytes in hex
+    pub snippet: String,
+    /// What the user can do about it
+    pub suggestion: String,
+}
+
+impl Diagnostic {
+    /// File-level diagnostic for a section of the .log configuration that could not be parsed
+    pub fn malformed_config(section: &str, log_line: usize, text: &str, error: impl fmt::Display) -> Self {
+        Self {
+            line: 0,
+            byte_offset: 0,
+            kind: DiagnosticKind::MalformedConfig,
+            message: format!("{} at line {} of the .log is not well-formed: {}", section, log_line, error),
+            snippet: snippet(text),
+            suggestion: "The rest of the configuration is still used; upload the original file \
+                and attach it to this file to replace the section".to_string(),
+        }
+    }
+}
+
+/// Diagnostics collected over a file: every problem is counted, the first `MAX_DIAGNOSTICS` are kept
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Diagnostics {
+    pub counts: BTreeMap<DiagnosticKind, usize>,
+    pub entries: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub fn total(&self) -> usize {
+        self.counts.values().sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// Whether some diagnostics were counted but not kept
+    pub fn truncated(&self) -> bool {
+        self.entries.len() < self.total()
+    }
+
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        *self.counts.entry(diagnostic.kind).or_insert(0) += 1;
+        if self.entries.len() < MAX_DIAGNOSTICS {
+            self.entries.push(diagnostic);
+        }
+    }
+
+    /// Record why a text line failed to decode.
+    ///
+    /// `complete` is false for a last line without its newline.
+    pub fn record_line(&mut self, line_number: usize, byte_offset: u64, line: &str, complete: bool, protocol: &MessageDictionary) {
+        let (kind, message, suggestion) = classify_line(line, complete, protocol);
+        self.push(Diagnostic {
+            line: line_number,
+            byte_offset,
+            kind,
+            message,
+            snippet: snippet(line),
+            suggestion,
+        });
+    }
+
+    /// Record a message whose timestamp goes back in time
+    pub fn record_time_jump(&mut self, line_number: usize, byte_offset: u64, line: &str, previous: f64, timestamp: f64) {
+        self.push(Diagnostic {
+            line: line_number,
+            byte_offset,
+            kind: DiagnosticKind::NonMonotonicTime,
+            message: format!("Timestamp {} is {:.3}s before the previous message ({})", timestamp, previous - timestamp, previous),
+            snippet: snippet(line),
+            suggestion: "The ground station clock was reset or several logs were concatenated; \
+                split the file at this line or sort the messages by time".to_string(),
+        });
+    }
+
+    /// Merge the diagnostics of a chunk of a file starting after `line_offset` lines and `byte_offset` bytes
+    pub fn extend_chunk(&mut self, chunk: Diagnostics, line_offset: usize, byte_offset: u64) {
+        for (kind, count) in chunk.counts {
+            *self.counts.entry(kind).or_insert(0) += count;
+        }
+        let room = MAX_DIAGNOSTICS.saturating_sub(self.entries.len());
+        self.entries.extend(chunk.entries.into_iter().take(room).map(|mut diagnostic| {
+            diagnostic.line += line_offset;
+            diagnostic.byte_offset += byte_offset;
+            diagnostic
+        }));
+    }
+}
+
+/// Start of a raw line, cut at a character boundary
+fn snippet(line: &str) -> String {
+    match line.char_indices().nth(SNIPPET_CHARS) {
+        Some((end, _)) => format!("{}…", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+/// Kind, description and suggestion for a line `parse_telemetry_line` rejected
+fn classify_line(line: &str, complete: bool, protocol: &MessageDictionary) -> (DiagnosticKind, String, String) {
+    const TRUNCATED: &str = "The logger stopped while writing this line (crash or power loss); \
+        only this line is lost";
+
+    let parts = split_fields(line.trim_start_matches('(').trim_end_matches(')'));
+    if parts.len() < 3 {
+        return (DiagnosticKind::TruncatedLine, format!("Only {} of at least 3 fields", parts.len()), TRUNCATED.to_string());
+    }
+    if parts[0].parse::<f64>().is_err() {
+        return (DiagnosticKind::BadNumber, format!("Invalid timestamp '{}'", parts[0]),
+            "The line is corrupted; check that the file is a .data log and was transferred in binary mode".to_string());
+    }
+    if parts[1].parse::<u8>().is_err() {
+        return (DiagnosticKind::BadNumber, format!("Invalid sender id '{}'", parts[1]),
+            "Sender ids are 0-255; the line is corrupted".to_string());
+    }
+
+    let name = parts[2];
+    let Some(definition) = protocol.get(name) else {
+        return (DiagnosticKind::UnknownMessage, format!("Message {} is not in the dictionary", name),
+            ATTACH_MESSAGES_XML.to_string());
+    };
+
+    let values = &parts[3..];
+    if values.len() != definition.fields.len() {
+        if !complete && values.len() < definition.fields.len() {
+            return (DiagnosticKind::TruncatedLine,
+                format!("{} stops after {} of {} fields", name, values.len(), definition.fields.len()),
+                TRUNCATED.to_string());
+        }
+        return (DiagnosticKind::ArityMismatch,
+            format!("{} has {} fields, the dictionary declares {}", name, values.len(), definition.fields.len()),
+            "The log was recorded with another pprzlink version; attach the matching messages.xml".to_string());
+    }
+
+    for (field, raw) in definition.fields.iter().zip(values) {
+        if let Err(e) = field.field_type.parse_value(raw) {
+            let kind = if complete { DiagnosticKind::BadNumber } else { DiagnosticKind::TruncatedLine };
+            return (kind, format!("Invalid value '{}' for {}.{}: {}", raw, name, field.name, e),
+                format!("{}.{} is declared as {:?}; the line is corrupted or the dictionary does not match",
+                    name, field.name, field.field_type));
+        }
+    }
+
+    (DiagnosticKind::BadNumber, format!("{} could not be decoded", name),
+        "The line is corrupted".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::TelemetryReader;
+    use crate::schema::fixtures::sample_protocol;
+
+    const DATA: &str = "\
+1.000 38 ROTORCRAFT_FP 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15
+1.250 38 NOT_A_MESSAGE 1 2
+1.500 38 ROTORCRAFT_FP 1 2 3
+1.750 38 ROTORCRAFT_FP 1 2 3 4 5 6 7 8 9 10 11 12 13 14 x
+0.500 38 ALIVE 1,2,3
+2.000 38 ROTORCRAFT_FP 1 2 3 4 5";
+
+    #[test]
+    fn test_line_diagnostics() {
+        let protocol = sample_protocol();
+        let mut reader = TelemetryReader::new(DATA.as_bytes(), &protocol, None);
+        assert_eq!(reader.by_ref().filter_map(Result::ok).count(), 2);
+
+        let diagnostics = &reader.progress().diagnostics;
+        let kinds: Vec<_> = diagnostics.entries.iter().map(|d| (d.line, d.kind)).collect();
+        assert_eq!(kinds, [
+            (2, DiagnosticKind::UnknownMessage),
+            (3, DiagnosticKind::ArityMismatch),
+            (4, DiagnosticKind::BadNumber),
+            (5, DiagnosticKind::NonMonotonicTime),
+            (6, DiagnosticKind::TruncatedLine),
+        ]);
+        assert_eq!(diagnostics.entries[1].byte_offset, DATA.find("1.500").unwrap() as u64);
+        assert!(diagnostics.entries[2].message.contains("ROTORCRAFT_FP.flight_time"));
+        assert_eq!((diagnostics.total(), diagnostics.truncated()), (5, false));
+
+        let mut merged = Diagnostics::default();
+        merged.extend_chunk(diagnostics.clone(), 0, 0);
+        merged.extend_chunk(diagnostics.clone(), 6, DATA.len() as u64);
+        assert_eq!(merged.counts[&DiagnosticKind::TruncatedLine], 2);
+        assert_eq!(merged.entries[9].line, 12);
+        assert_eq!(merged.entries[9].byte_offset, (DATA.len() + DATA.rfind("2.000").unwrap()) as u64);
+    }
+}
@@ -0,0 +1,254 @@
+//! Differences between two message protocols, e.g. the ones of two builds of an aircraft
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::{FieldDefinition, MessageClass, MessageDefinition, MessageDictionary};
+
+/// A message identified by its class and name
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MessageRef {
+    pub class: MessageClass,
+    pub name: String,
+    pub id: u8,
+}
+
+/// How one field of a message changed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum FieldChange {
+    Added { field: String, position: usize, field_type: String },
+    Removed { field: String, position: usize, field_type: String },
+    TypeChanged { field: String, from: String, to: String },
+    /// Field moved relative to the fields both versions have
+    Moved { field: String, from: usize, to: usize },
+    /// `unit`, `alt_unit` or `alt_unit_coef` changed
+    UnitChanged { field: String, attribute: String, from: Option<String>, to: Option<String> },
+}
+
+/// Changes of a message present in both protocols
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MessageChanges {
+    pub class: MessageClass,
+    pub name: String,
+    /// (from, to) when the message id changed
+    pub id_change: Option<(u8, u8)>,
+    pub fields: Vec<FieldChange>,
+}
+
+/// Differences between two protocols, messages matched by class and name
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProtocolDiff {
+    pub added: Vec<MessageRef>,
+    pub removed: Vec<MessageRef>,
+    pub changed: Vec<MessageChanges>,
+}
+
+impl ProtocolDiff {
+    pub fn between(from: &MessageDictionary, to: &MessageDictionary) -> Self {
+        let (old, new) = (by_class_and_name(from), by_class_and_name(to));
+        let reference = |m: &MessageDefinition| MessageRef { class: m.class, name: m.name.clone(), id: m.id };
+
+        let mut diff = ProtocolDiff::default();
+        for (key, message) in &old {
+            match new.get(key) {
+                None => diff.removed.push(reference(message)),
+                Some(updated) => {
+                    let fields = field_changes(&message.fields, &updated.fields);
+                    let id_change = (message.id != updated.id).then_some((message.id, updated.id));
+                    if id_change.is_some() || !fields.is_empty() {
+                        diff.changed.push(MessageChanges { class: message.class, name: message.name.clone(), id_change, fields });
+                    }
+                }
+            }
+        }
+        diff.added = new.iter()
+            .filter(|(key, _)| !old.contains_key(*key))
+            .map(|(_, message)| reference(message))
+            .collect();
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+fn by_class_and_name(dictionary: &MessageDictionary) -> BTreeMap<(u8, String), &MessageDefinition> {
+    dictionary.messages.iter().map(|m| ((m.class_id, m.name.clone()), m)).collect()
+}
+
+fn field_changes(old: &[FieldDefinition], new: &[FieldDefinition]) -> Vec<FieldChange> {
+    let position = |fields: &[FieldDefinition], name: &str| fields.iter().position(|f| f.name == name);
+    let mut changes = Vec::new();
+
+    for (index, field) in old.iter().enumerate() {
+        if position(new, &field.name).is_none() {
+            changes.push(FieldChange::Removed { field: field.name.clone(), position: index, field_type: field.field_type.to_string() });
+        }
+    }
+    for (index, field) in new.iter().enumerate() {
+        if position(old, &field.name).is_none() {
+            changes.push(FieldChange::Added { field: field.name.clone(), position: index, field_type: field.field_type.to_string() });
+        }
+    }
+
+    // Order of the fields both versions have, so that an insertion alone is not reported as moves
+    let common_old: Vec<_> = old.iter().filter(|f| position(new, &f.name).is_some()).collect();
+    let common_new: Vec<_> = new.iter().filter(|f| position(old, &f.name).is_some()).collect();
+    for (rank, field) in common_old.iter().enumerate() {
+        let new_rank = common_new.iter().position(|f| f.name == field.name).unwrap_or(rank);
+        let updated = common_new[new_rank];
+        let (from, to) = (position(old, &field.name).unwrap_or(0), position(new, &field.name).unwrap_or(0));
+        if rank != new_rank {
+            changes.push(FieldChange::Moved { field: field.name.clone(), from, to });
+        }
+        if field.field_type != updated.field_type {
+            changes.push(FieldChange::TypeChanged {
+                field: field.name.clone(),
+                from: field.field_type.to_string(),
+                to: updated.field_type.to_string(),
+            });
+        }
+        let units = [
+            ("unit", field.unit.clone(), updated.unit.clone()),
+            ("alt_unit", field.alt_unit.clone(), updated.alt_unit.clone()),
+            ("alt_unit_coef", field.alt_unit_coef.map(|c| c.to_string()), updated.alt_unit_coef.map(|c| c.to_string())),
+        ];
+        for (attribute, from, to) in units {
+            if from != to {
+                changes.push(FieldChange::UnitChanged { field: field.name.clone(), attribute: attribute.to_string(), from, to });
+            }
+        }
+    }
+    changes
+}
+
+impl fmt::Display for FieldChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unit = |value: &Option<String>| value.clone().unwrap_or_else(|| "none".to_string());
+        match self {
+            FieldChange::Added { field, position, field_type } => write!(f, "+ {} {} (field {})", field_type, field, position),
+            FieldChange::Removed { field, position, field_type } => write!(f, "- {} {} (was field {})", field_type, field, position),
+            FieldChange::TypeChanged { field, from, to } => write!(f, "~ {}: type {} -> {}", field, from, to),
+            FieldChange::Moved { field, from, to } => write!(f, "~ {}: moved from field {} to {}", field, from, to),
+            FieldChange::UnitChanged { field, attribute, from, to } =>
+                write!(f, "~ {}: {} {} -> {}", field, attribute, unit(from), unit(to)),
+        }
+    }
+}
+
+/// Human-readable summary, one line per message and per field change
+impl fmt::Display for ProtocolDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "Identical message definitions");
+        }
+        writeln!(f, "{} added, {} removed, {} changed message(s)", self.added.len(), self.removed.len(), self.changed.len())?;
+        for message in &self.added {
+            writeln!(f, "+ {}.{} (id {})", message.class, message.name, message.id)?;
+        }
+        for message in &self.removed {
+            writeln!(f, "- {}.{} (id {})", message.class, message.name, message.id)?;
+        }
+        for message in &self.changed {
+            write!(f, "~ {}.{}", message.class, message.name)?;
+            match message.id_change {
+                Some((from, to)) => writeln!(f, ": id {} -> {}", from, to)?,
+                None => writeln!(f)?,
+            }
+            for change in &message.fields {
+                writeln!(f, "    {}", change)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FROM: &str = r#"<protocol>
+    <msg_class NAME="telemetry" ID="1">
+      <message NAME="ALIVE" ID="2"><field TYPE="uint8[]" NAME="md5sum"/></message>
+      <message NAME="GPS_INT" ID="155">
+        <field TYPE="int32" NAME="lat" UNIT="1e7deg"/>
+        <field TYPE="int32" NAME="lon" UNIT="1e7deg"/>
+        <field TYPE="int32" NAME="alt" UNIT="mm"/>
+        <field TYPE="uint8" NAME="fix"/>
+      </message>
+      <message NAME="OLD" ID="9"><field TYPE="uint8" NAME="x"/></message>
+    </msg_class>
+  </protocol>"#;
+
+    const TO: &str = r#"<protocol>
+    <msg_class NAME="telemetry" ID="1">
+      <message NAME="ALIVE" ID="3"><field TYPE="uint8[]" NAME="md5sum"/></message>
+      <message NAME="GPS_INT" ID="155">
+        <field TYPE="int32" NAME="lon" UNIT="1e7deg"/>
+        <field TYPE="int32" NAME="lat" UNIT="1e7deg"/>
+        <field TYPE="int32" NAME="hmsl" UNIT="mm"/>
+        <field TYPE="int32" NAME="alt" UNIT="cm"/>
+        <field TYPE="uint16" NAME="fix"/>
+      </message>
+      <message NAME="NEW" ID="10"><field TYPE="uint8" NAME="x"/></message>
+    </msg_class>
+  </protocol>"#;
+
+    #[test]
+    fn test_protocol_diff() {
+        let from = MessageDictionary::from_xml(FROM).unwrap();
+        let to = MessageDictionary::from_xml(TO).unwrap();
+        assert!(ProtocolDiff::between(&from, &from).is_empty());
+
+        let diff = ProtocolDiff::between(&from, &to);
+        assert_eq!(diff.added.iter().map(|m| m.name.as_str()).collect::<Vec<_>>(), ["NEW"]);
+        assert_eq!(diff.removed.iter().map(|m| m.name.as_str()).collect::<Vec<_>>(), ["OLD"]);
+        assert_eq!(diff.changed.len(), 2);
+        assert_eq!((diff.changed[0].name.as_str(), diff.changed[0].id_change), ("ALIVE", Some((2, 3))));
+
+        let gps = &diff.changed[1].fields;
+        assert_eq!(gps, &[
+            FieldChange::Added { field: "hmsl".into(), position: 2, field_type: "int32".into() },
+            FieldChange::Moved { field: "lat".into(), from: 0, to: 1 },
+            FieldChange::Moved { field: "lon".into(), from: 1, to: 0 },
+            FieldChange::UnitChanged { field: "alt".into(), attribute: "unit".into(), from: Some("mm".into()), to: Some("cm".into()) },
+            FieldChange::TypeChanged { field: "fix".into(), from: "uint8".into(), to: "uint16".into() },
+        ]);
+
+        let summary = diff.to_string();
+        assert!(summary.starts_with("1 added, 1 removed, 2 changed message(s)"));
+        assert!(summary.contains("~ telemetry.ALIVE: id 2 -> 3"));
+        assert!(summary.contains("    ~ fix: type uint8 -> uint16"));
+    }
+
+    #[test]
+    fn test_unmatched_messages() {
+        let from = MessageDictionary::from_xml(FROM).unwrap();
+        let empty = MessageDictionary::from_xml("<protocol/>").unwrap();
+
+        // Against an empty protocol every message is added or removed, none changed
+        let diff = ProtocolDiff::between(&empty, &from);
+        assert_eq!((diff.added.len(), diff.removed.len(), diff.changed.len()), (3, 0, 0));
+        let diff = ProtocolDiff::between(&from, &empty);
+        assert_eq!((diff.added.len(), diff.removed.len(), diff.changed.len()), (0, 3, 0));
+        assert_eq!(ProtocolDiff::between(&empty, &empty).to_string(), "Identical message definitions\n");
+
+        // Same name in another class, a renamed message and a renamed field are not matched up
+        let moved = FROM
+            .replace(r#"<msg_class NAME="telemetry" ID="1">"#, r#"<msg_class NAME="datalink" ID="2">"#);
+        let diff = ProtocolDiff::between(&from, &MessageDictionary::from_xml(&moved).unwrap());
+        assert_eq!((diff.added.len(), diff.removed.len(), diff.changed.len()), (3, 3, 0));
+        assert_eq!(diff.added[0].class, MessageClass::Datalink);
+
+        let renamed = FROM.replace(r#"NAME="OLD""#, r#"NAME="RENAMED""#).replace(r#"NAME="fix""#, r#"NAME="fix_type""#);
+        let diff = ProtocolDiff::between(&from, &MessageDictionary::from_xml(&renamed).unwrap());
+        assert_eq!((diff.added[0].name.as_str(), diff.removed[0].name.as_str()), ("RENAMED", "OLD"));
+        assert_eq!(diff.changed[0].fields, [
+            FieldChange::Removed { field: "fix".into(), position: 3, field_type: "uint8".into() },
+            FieldChange::Added { field: "fix_type".into(), position: 3, field_type: "uint8".into() },
+        ]);
+    }
+}
@@ -0,0 +1,20 @@
+//! Sample logs shared by the schema tests
+
+use super::MessageDictionary;
+
+/// Paparazzi v7.0 log of a 62 aircraft session
+pub const SAMPLE_LOG: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/25_07_09__15_38_54.log");
+
+/// Text capture written for the tests with the messages of the sample log: ac 13 flying
+/// a circle, with the ground station restarted after 375 lines so the timestamps start over
+pub const CLOCK_RESET_DATA: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/rotorcraft_clock_reset.data");
+
+/// Content of the sample .log
+pub fn sample_log() -> String {
+    std::fs::read_to_string(SAMPLE_LOG).unwrap()
+}
+
+/// Message dictionary of the sample .log
+pub fn sample_protocol() -> MessageDictionary {
+    MessageDictionary::from_log_content(&sample_log()).unwrap()
+}
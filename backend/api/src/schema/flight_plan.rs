@@ -0,0 +1,401 @@
+//! Flight plan (`<flight_plan>`) embedded in the .log for each aircraft
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+use super::xml::{attr, children, escape_stray_ampersands, parse_document};
+
+// WGS84 ellipsoid
+const WGS84_A: f64 = 6378137.0;
+const WGS84_E2: f64 = 6.694379990141316e-3;
+
+/// Typed model of a `<flight_plan>` element
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlightPlan {
+    pub name: String,
+    pub origin: GeodeticOrigin,
+    /// Default waypoint altitude (MSL)
+    pub alt: f64,
+    pub security_height: Option<f64>,
+    pub max_dist_from_home: Option<f64>,
+    pub qfu: Option<f64>,
+    pub waypoints: Vec<Waypoint>,
+    pub sectors: Vec<Sector>,
+    /// Exceptions checked in every block
+    pub exceptions: Vec<FlightPlanException>,
+    pub blocks: Vec<Block>,
+}
+
+/// Geodetic reference of the flight plan (`LAT0`, `LON0`, `GROUND_ALT`)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct GeodeticOrigin {
+    pub lat: f64,
+    pub lon: f64,
+    pub ground_alt: f64,
+}
+
+/// Waypoint resolved to absolute coordinates
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Waypoint {
+    pub name: String,
+    /// East offset from the origin in meters, when declared relative to it
+    pub x: Option<f64>,
+    /// North offset from the origin in meters, when declared relative to it
+    pub y: Option<f64>,
+    pub lat: f64,
+    pub lon: f64,
+    /// Altitude above mean sea level
+    pub alt: f64,
+    /// Height above the origin's ground altitude
+    pub height: f64,
+}
+
+/// `<sector NAME COLOR>` polygon through waypoints
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sector {
+    pub name: String,
+    pub color: Option<String>,
+    pub corners: Vec<String>,
+}
+
+/// `<exception COND DEROUTE>`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlightPlanException {
+    pub cond: String,
+    pub deroute: Option<String>,
+    pub exec: Option<String>,
+}
+
+/// `<block NAME>` and its stages
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Block {
+    pub name: String,
+    pub strip_button: Option<String>,
+    pub strip_icon: Option<String>,
+    pub pre_call: Option<String>,
+    pub post_call: Option<String>,
+    pub exceptions: Vec<FlightPlanException>,
+    pub stages: Vec<Stage>,
+}
+
+/// Navigation stage of a block (`go`, `stay`, `circle`, `while`, ...), nested for loops
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Stage {
+    pub kind: String,
+    pub attributes: BTreeMap<String, String>,
+    pub stages: Vec<Stage>,
+}
+
+impl FlightPlan {
+    /// Parse a standalone `<flight_plan>` document
+    pub fn from_xml(xml: &str) -> Result<Self> {
+        let xml = escape_stray_ampersands(xml);
+        let document = parse_document(&xml)?;
+        let root = document.root_element();
+        if !root.has_tag_name("flight_plan") {
+            return Err(anyhow!("Expected <flight_plan> root element, found <{}>", root.tag_name().name()));
+        }
+        Self::from_node(root)
+    }
+
+    /// Build the model from a parsed `<flight_plan>` element, resolving waypoints to LLA
+    pub fn from_node(node: roxmltree::Node) -> Result<Self> {
+        let name = attr(node, "NAME").unwrap_or_default();
+        let required = |key: &str| attr(node, key)
+            .ok_or_else(|| anyhow!("Flight plan '{}' has no {} attribute", name, key));
+
+        let origin = GeodeticOrigin {
+            lat: parse_dms(&required("LAT0")?)?,
+            lon: parse_dms(&required("LON0")?)?,
+            ground_alt: parse_number(&required("GROUND_ALT")?)?,
+        };
+        let alt = parse_number(&required("ALT")?)?;
+
+        let waypoints = children(node, "waypoints")
+            .flat_map(|waypoints| children(waypoints, "waypoint"))
+            .map(|waypoint| Waypoint::from_node(waypoint, &origin, alt))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            origin,
+            alt,
+            security_height: attr(node, "SECURITY_HEIGHT").and_then(|v| parse_number(&v).ok()),
+            max_dist_from_home: attr(node, "MAX_DIST_FROM_HOME").and_then(|v| parse_number(&v).ok()),
+            qfu: attr(node, "QFU").and_then(|v| parse_number(&v).ok()),
+            waypoints,
+            sectors: children(node, "sectors")
+                .flat_map(|sectors| children(sectors, "sector"))
+                .map(|sector| Sector {
+                    name: attr(sector, "NAME").unwrap_or_default(),
+                    color: attr(sector, "COLOR"),
+                    corners: children(sector, "corner").filter_map(|corner| attr(corner, "NAME")).collect(),
+                })
+                .collect(),
+            exceptions: children(node, "exceptions")
+                .flat_map(|exceptions| children(exceptions, "exception"))
+                .map(FlightPlanException::from_node)
+                .collect(),
+            blocks: children(node, "blocks")
+                .flat_map(|blocks| children(blocks, "block"))
+                .map(Block::from_node)
+                .collect(),
+            name,
+        })
+    }
+
+    pub fn waypoint(&self, name: &str) -> Option<&Waypoint> {
+        self.waypoints.iter().find(|w| w.name == name)
+    }
+
+    /// GeoJSON FeatureCollection with the origin, waypoints and sectors of the plan
+    pub fn to_geojson(&self) -> Value {
+        let mut features = vec![json!({
+            "type": "Feature",
+            "geometry": {
+                "type": "Point",
+                "coordinates": [self.origin.lon, self.origin.lat, self.origin.ground_alt],
+            },
+            "properties": {
+                "kind": "origin",
+                "flight_plan": self.name,
+                "max_dist_from_home": self.max_dist_from_home,
+            },
+        })];
+
+        features.extend(self.waypoints.iter().map(|waypoint| json!({
+            "type": "Feature",
+            "geometry": {
+                "type": "Point",
+                "coordinates": [waypoint.lon, waypoint.lat, waypoint.alt],
+            },
+            "properties": {
+                "kind": "waypoint",
+                "name": waypoint.name,
+                "alt": waypoint.alt,
+                "height": waypoint.height,
+            },
+        })));
+
+        for sector in &self.sectors {
+            let mut ring: Vec<_> = sector.corners.iter()
+                .filter_map(|corner| self.waypoint(corner))
+                .map(|waypoint| [waypoint.lon, waypoint.lat])
+                .collect();
+            if ring.len() < 3 {
+                continue;
+            }
+            ring.push(ring[0]);
+            features.push(json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "Polygon",
+                    "coordinates": [ring],
+                },
+                "properties": {
+                    "kind": "sector",
+                    "name": sector.name,
+                    "color": sector.color,
+                },
+            }));
+        }
+
+        json!({
+            "type": "FeatureCollection",
+            "features": features,
+        })
+    }
+}
+
+impl GeodeticOrigin {
+    /// Position of a point given in meters east/north of the origin (local tangent plane)
+    pub fn offset(&self, east: f64, north: f64) -> (f64, f64) {
+        let lat = self.lat.to_radians();
+        let w = (1.0 - WGS84_E2 * lat.sin().powi(2)).sqrt();
+        let meridian_radius = WGS84_A * (1.0 - WGS84_E2) / w.powi(3);
+        let normal_radius = WGS84_A / w;
+
+        (
+            self.lat + (north / meridian_radius).to_degrees(),
+            self.lon + (east / (normal_radius * lat.cos())).to_degrees(),
+        )
+    }
+}
+
+impl Waypoint {
+    fn from_node(node: roxmltree::Node, origin: &GeodeticOrigin, default_alt: f64) -> Result<Self> {
+        let name = attr(node, "NAME").unwrap_or_default();
+        let number = |key: &str| attr(node, key).map(|v| parse_number(&v)).transpose();
+
+        let x = number("X")?;
+        let y = number("Y")?;
+        let (lat, lon) = match (attr(node, "LAT"), attr(node, "LON")) {
+            (Some(lat), Some(lon)) => (parse_dms(&lat)?, parse_dms(&lon)?),
+            _ => match (x, y) {
+                (Some(x), Some(y)) => origin.offset(x, y),
+                _ => return Err(anyhow!("Waypoint '{}' has neither X/Y nor LAT/LON", name)),
+            },
+        };
+
+        // ALT is above sea level, HEIGHT above the ground altitude of the origin
+        let alt = match (number("ALT")?, number("HEIGHT")?) {
+            (Some(alt), _) => alt,
+            (None, Some(height)) => origin.ground_alt + height,
+            (None, None) => default_alt,
+        };
+
+        Ok(Self {
+            name,
+            x,
+            y,
+            lat,
+            lon,
+            alt,
+            height: alt - origin.ground_alt,
+        })
+    }
+}
+
+impl FlightPlanException {
+    fn from_node(node: roxmltree::Node) -> Self {
+        Self {
+            cond: attr(node, "COND").unwrap_or_default(),
+            deroute: attr(node, "DEROUTE"),
+            exec: attr(node, "EXEC"),
+        }
+    }
+}
+
+impl Block {
+    fn from_node(node: roxmltree::Node) -> Self {
+        Self {
+            name: attr(node, "NAME").unwrap_or_default(),
+            strip_button: attr(node, "STRIP_BUTTON"),
+            strip_icon: attr(node, "STRIP_ICON"),
+            pre_call: attr(node, "PRE_CALL"),
+            post_call: attr(node, "POST_CALL"),
+            exceptions: children(node, "exception").map(FlightPlanException::from_node).collect(),
+            stages: stages(node),
+        }
+    }
+}
+
+fn stages(node: roxmltree::Node) -> Vec<Stage> {
+    node.children()
+        .filter(|child| child.is_element() && !child.has_tag_name("exception"))
+        .map(|child| Stage {
+            kind: child.tag_name().name().to_string(),
+            attributes: child.attributes()
+                .map(|a| (a.name().to_ascii_uppercase(), a.value().to_string()))
+                .collect(),
+            stages: stages(child),
+        })
+        .collect()
+}
+
+/// Parse a coordinate in decimal degrees (`43.5641`) or degrees-minutes-seconds (`43 33 50.83`)
+pub fn parse_dms(value: &str) -> Result<f64> {
+    let parts: Vec<f64> = value.split_whitespace()
+        .map(|part| part.parse::<f64>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| anyhow!("Invalid coordinate '{}'", value))?;
+
+    let (degrees, minutes, seconds) = match parts.as_slice() {
+        [degrees] => (*degrees, 0.0, 0.0),
+        [degrees, minutes] => (*degrees, *minutes, 0.0),
+        [degrees, minutes, seconds] => (*degrees, *minutes, *seconds),
+        _ => return Err(anyhow!("Invalid coordinate '{}'", value)),
+    };
+
+    // The sign of the degrees applies to the whole coordinate, including "-0 30 0"
+    let magnitude = degrees.abs() + minutes / 60.0 + seconds / 3600.0;
+    if value.trim_start().starts_with('-') {
+        Ok(-magnitude)
+    } else {
+        Ok(magnitude)
+    }
+}
+
+fn parse_number(value: &str) -> Result<f64> {
+    value.trim().parse::<f64>()
+        .map_err(|_| anyhow!("Invalid number '{}'", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FLIGHT_PLAN: &str = r#"<flight_plan SECURITY_HEIGHT="2" NAME="Rotorcraft Basic (Enac)" MAX_DIST_FROM_HOME="150" LON0="1 28 52.61" LAT0="43 33 50.83" GROUND_ALT="147" ALT="152">
+        <waypoints>
+          <waypoint Y="0.0" X="0.0" NAME="HOME"/>
+          <waypoint Y="100.0" X="100.0" NAME="NE"/>
+          <waypoint Y="-50" X="-20" NAME="CAM" HEIGHT="2."/>
+          <waypoint NAME="t0" LON="4.3767772" LAT="51.9906059" ALT="10"/>
+        </waypoints>
+        <sectors>
+          <sector NAME="ZONE" COLOR="red">
+            <corner NAME="HOME"/>
+            <corner NAME="NE"/>
+            <corner NAME="CAM"/>
+          </sector>
+        </sectors>
+        <exceptions>
+          <exception cond="(electrical.bat_low&&(exception_flag_0==false))" deroute="Land" exec="SetValue(exception_flag_0,true)"/>
+        </exceptions>
+        <blocks>
+          <block STRIP_BUTTON="Takeoff" NAME="Takeoff">
+            <exception DEROUTE="Standby" COND="stateGetPositionEnu_f() @DEREF z @GT 2.0"/>
+            <stay WP="CLIMB" VMODE="climb"/>
+            <for VAR="i" TO="16" FROM="1">
+              <heading COURSE="90 * $i"/>
+            </for>
+          </block>
+        </blocks>
+      </flight_plan>"#;
+
+    fn assert_close(a: f64, b: f64, tolerance: f64) {
+        assert!((a - b).abs() < tolerance, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_parse_dms() {
+        assert_close(parse_dms("43 33 50.83").unwrap(), 43.564119, 1e-6);
+        assert_close(parse_dms("1 28 52.61").unwrap(), 1.481281, 1e-6);
+        assert_close(parse_dms("-8.871222").unwrap(), -8.871222, 1e-9);
+        assert_close(parse_dms("-0 30 0").unwrap(), -0.5, 1e-9);
+        assert!(parse_dms("north").is_err());
+    }
+
+    #[test]
+    fn test_parse_flight_plan() {
+        let plan = FlightPlan::from_xml(FLIGHT_PLAN).unwrap();
+
+        assert_eq!(plan.max_dist_from_home, Some(150.0));
+        assert_eq!(plan.waypoints.len(), 4);
+
+        let home = plan.waypoint("HOME").unwrap();
+        assert_close(home.lat, plan.origin.lat, 1e-12);
+        assert_eq!(home.alt, 152.0);
+        assert_eq!(home.height, 5.0);
+
+        // 100 m north and east of the origin
+        let ne = plan.waypoint("NE").unwrap();
+        assert_close(ne.lat - plan.origin.lat, 100.0 / 111_132.0, 1e-5);
+        assert_close(ne.lon - plan.origin.lon, 100.0 / (111_320.0 * plan.origin.lat.to_radians().cos()), 1e-5);
+
+        assert_eq!(plan.waypoint("CAM").unwrap().alt, 149.0);
+        assert_eq!(plan.waypoint("t0").unwrap().lat, 51.9906059);
+
+        assert_eq!(plan.exceptions[0].cond, "(electrical.bat_low&&(exception_flag_0==false))");
+        let takeoff = &plan.blocks[0];
+        assert_eq!(takeoff.exceptions[0].deroute.as_deref(), Some("Standby"));
+        assert_eq!(takeoff.stages[0].kind, "stay");
+        assert_eq!(takeoff.stages[1].stages[0].attributes["COURSE"], "90 * $i");
+
+        let geojson = plan.to_geojson();
+        let features = geojson["features"].as_array().unwrap();
+        assert_eq!(features.len(), 1 + 4 + 1);
+        assert_eq!(features[5]["geometry"]["coordinates"][0].as_array().unwrap().len(), 4);
+    }
+}
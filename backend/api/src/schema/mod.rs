@@ -1,54 +1,184 @@
 use std::collections::HashMap;
 
+pub mod airframe;
+pub mod artifacts;
+pub mod binary;
+pub mod bundled;
+pub mod columnar;
+pub mod config_check;
+pub mod diagnostics;
+pub mod diff;
+#[cfg(test)]
+pub mod fixtures;
+pub mod flight_plan;
+pub mod parallel;
+pub mod protocol;
+pub mod settings;
+pub mod stream;
+pub mod time;
+pub mod units;
+pub mod version;
+pub mod xml;
+
+pub use airframe::*;
+pub use artifacts::*;
+pub use binary::*;
+pub use bundled::*;
+pub use columnar::*;
+pub use config_check::*;
+pub use diagnostics::*;
+pub use diff::*;
+pub use flight_plan::*;
+pub use parallel::*;
+pub use protocol::*;
+pub use settings::*;
+pub use stream::*;
+pub use time::*;
+pub use units::*;
+pub use version::*;
+
 pub struct SchemaManager {
-    cache: HashMap<String, String>,
+    /// Parsed schema artifacts, keyed by their kind and the blake3 hash of their content
+    artifacts: HashMap<(ArtifactKind, String), SchemaArtifact>,
 }
 
 impl SchemaManager {
     /// Parse a PaparazziUAV log file with explicit log and data file paths
-    pub async fn parse_log_file_with_data(&mut self, log_path: &Path, data_path: &Path) -> Result<LogFile> {
+    pub async fn parse_log_file_with_data(&self, log_path: &Path, data_path: &Path) -> Result<LogFile> {
         let log_path = log_path.to_path_buf();
         let data_file_path = data_path.to_path_buf();
         let cache_key = format!("{}|{}", log_path.to_string_lossy(), data_file_path.to_string_lossy());
 
         info!("Parsing PaparazziUAV log file: {:?} with data file: {:?}", log_path, data_file_path);
 
-        // Read and parse the .log file
-        let log_content = fs::read_to_string(&log_path).await
-            .map_err(|e| anyhow!("Failed to read log file {:?}: {}", log_path, e))?;
-
-        let configuration = self.parse_log_configuration(&log_content)?;
+        let configuration = self.load_log_configuration(&log_path).await?;
 
         // Parse telemetry messages from the provided data file
-        let telemetry_messages = self.parse_telemetry_data(&data_file_path, &configuration).await?;
+        let messages = self.parse_telemetry_data(&data_file_path, &configuration).await?;
 
         let log_file = LogFile {
             configuration,
-            messages: telemetry_messages,
+            messages,
             file_path: log_path.clone(),
             data_file_path,
         };
 
         Ok(log_file)
     }
+
+    /// Read and parse the configuration header of a .log file
+    pub async fn load_log_configuration(&self, log_path: &Path) -> Result<LogConfiguration> {
+        let log_content = fs::read_to_string(log_path).await
+            .map_err(|e| anyhow!("Failed to read log file {:?}: {}", log_path, e))?;
+
+        self.parse_log_configuration(&log_content)
+    }
+
+    /// Configuration for a .data file uploaded without its .log, decoded with `bundled`.
+    ///
+    /// Nothing is known about the aircraft; `local_start` is the ground station local time
+    /// the log was started at when the caller can tell it (e.g. from the file name). Its UTC
+    /// offset is unknown: it is kept as a local time, and only a GPS anchor gives UTC times.
+    pub fn bundled_configuration(&self, data_file: &str, bundled: &BundledDictionary, local_start: Option<NaiveDateTime>) -> Result<LogConfiguration> {
+        info!("No .log for {}, decoding with the bundled {} v{} dictionary", data_file, bundled.name, bundled.version);
+
+        Ok(LogConfiguration {
+            time_of_day: 0.0,
+            time_source: if local_start.is_some() { TimeSource::LocalFilename } else { TimeSource::TimeOfDay },
+            local_start,
+            data_file: data_file.to_string(),
+            aircraft: BTreeMap::new(),
+            airframes: BTreeMap::new(),
+            flight_plans: BTreeMap::new(),
+            settings: BTreeMap::new(),
+            paparazzi_version: None,
+            build_version: None,
+            protocol: bundled.load()?,
+            dictionary: bundled.source(),
+            diagnostics: Vec::new(),
+        })
+    }
+
+    /// Stream decoded telemetry messages from a .data file without loading it in memory
+    pub fn stream_telemetry_data(&self, data_path: &Path, config: &LogConfiguration) -> Result<TelemetryStream> {
+        TelemetryStream::open(data_path, config.protocol.clone())
+    }
+
+    /// Hash identifying the message protocol a file pair was decoded with
+    pub fn get_schema_hash(&self, config: &LogConfiguration) -> String {
+        config.protocol.schema_hash()
+    }
+
+    /// Parse an uploaded artifact and keep it in the registry, returning its hash
+    pub fn register_artifact(&mut self, kind: ArtifactKind, content: &[u8]) -> Result<(String, &SchemaArtifact)> {
+        let xml = std::str::from_utf8(content)
+            .map_err(|_| anyhow!("{} artifact is not valid UTF-8", kind))?;
+        let hash = artifact_hash(content);
+        let artifact = match self.artifacts.entry((kind, hash.clone())) {
+            std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+            std::collections::hash_map::Entry::Vacant(entry) => entry.insert(SchemaArtifact::parse(kind, xml)?),
+        };
+        Ok((hash, artifact))
+    }
+
+    /// Registered artifact of an attachment, parsed from the content read from its storage
+    pub fn load_artifact(&mut self, attachment: &ArtifactAttachment, content: &[u8]) -> Result<&SchemaArtifact> {
+        if artifact_hash(content) != attachment.content_hash {
+            return Err(anyhow!("Artifact {} does not match its hash", attachment.artifact_id));
+        }
+        Ok(self.register_artifact(attachment.kind, content)?.1)
+    }
+
+    /// Override the configuration of a file pair with the artifacts attached to it and their content
+    pub fn apply_artifacts(&mut self, config: &mut LogConfiguration, artifacts: &[(ArtifactAttachment, Vec<u8>)]) -> Result<()> {
+        for (attachment, content) in artifacts {
+            match self.load_artifact(attachment, content)? {
+                SchemaArtifact::Messages(protocol) => {
+                    config.protocol = protocol.clone();
+                    config.dictionary = DictionarySource::Artifact {
+                        name: attachment.name.clone(),
+                        version: attachment.version,
+                        hash: attachment.content_hash.clone(),
+                    };
+                }
+                SchemaArtifact::Airframe(airframe) => {
+                    let sender_id = attachment.sender_id
+                        .ok_or_else(|| anyhow!("Airframe artifact {} is not attached to an aircraft", attachment.artifact_id))?;
+                    config.airframes.insert(sender_id, airframe.clone());
+                }
+                SchemaArtifact::FlightPlan(flight_plan) => {
+                    let sender_id = attachment.sender_id
+                        .ok_or_else(|| anyhow!("Flight plan artifact {} is not attached to an aircraft", attachment.artifact_id))?;
+                    config.flight_plans.insert(sender_id, flight_plan.clone());
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub fn new() -> Self {
         Self {
-            cache: HashMap::new(),
+            artifacts: HashMap::new(),
         }
     }
 }
 
+use chrono::NaiveDateTime;
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, HashSet};
 use tokio::fs;
+use tokio_stream::StreamExt;
 use tracing::{debug, info, warn};
 
 /// Represents a parsed PaparazziUAV log file with its configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug)]
 pub struct LogFile {
     pub configuration: LogConfiguration,
-    pub messages: Vec<TelemetryMessage>,
+    /// Messages of the .data file, decoded as they are read; the lines that were
+    /// skipped or looked wrong are in its diagnostics once it is exhausted
+    pub messages: ParallelTelemetry,
     pub file_path: PathBuf,
     pub data_file_path: PathBuf,
 }
@@ -57,10 +187,31 @@ pub struct LogFile {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LogConfiguration {
     pub time_of_day: f64,
+    /// Where `time_of_day` comes from
+    #[serde(default)]
+    pub time_source: TimeSource,
+    /// Ground station local time in the file name of a .data file without its .log, UTC offset unknown
+    #[serde(default)]
+    pub local_start: Option<NaiveDateTime>,
     pub data_file: String,
-    pub aircraft: AircraftInfo,
-    pub paparazzi_version: Option<String>,
-    pub build_version: Option<String>,
+    /// Every aircraft of the `<conf>` section, keyed by the sender_id it logs with
+    pub aircraft: BTreeMap<u8, AircraftInfo>,
+    /// Airframe configuration of each aircraft, keyed by sender_id
+    pub airframes: BTreeMap<u8, AirframeConfig>,
+    /// Flight plan of each aircraft, keyed by sender_id
+    pub flight_plans: BTreeMap<u8, FlightPlan>,
+    /// Datalink settings of each aircraft, keyed by sender_id
+    pub settings: BTreeMap<u8, SettingsTable>,
+    /// Version of the ground station that logged the flight
+    pub paparazzi_version: Option<PaparazziVersion>,
+    /// Version the aircraft firmware was built from
+    pub build_version: Option<PaparazziVersion>,
+    pub protocol: MessageDictionary,
+    /// Where `protocol` comes from
+    pub dictionary: DictionarySource,
+    /// Sections of the .log that could not be parsed and were left out
+    #[serde(default)]
+    pub diagnostics: Vec<Diagnostic>,
 }
 
 /// Aircraft configuration information
@@ -73,14 +224,35 @@ pub struct AircraftInfo {
     pub firmware: Option<String>,
 }
 
-/// Individual telemetry message
+/// Per-aircraft configuration parsed from the `<conf>` section
+#[derive(Default)]
+struct AircraftConfigs {
+    aircraft: BTreeMap<u8, AircraftInfo>,
+    airframes: BTreeMap<u8, AirframeConfig>,
+    flight_plans: BTreeMap<u8, FlightPlan>,
+    settings: BTreeMap<u8, SettingsTable>,
+    /// Sections left out because they are not well-formed
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Statistics of the messages sent by a single aircraft
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AircraftStatistics {
+    pub sender_id: u8,
+    /// Configuration of the sender, `None` when it is not declared in the .log
+    pub aircraft: Option<AircraftInfo>,
+    pub statistics: LogStatistics,
+}
+
+/// Individual telemetry message
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TelemetryMessage {
     pub timestamp: f64,
     pub sender_id: u8,
     pub message_id: u8,
+    pub msg_class: MessageClass,
     pub message_name: String,
-    pub fields: HashMap<String, String>,
+    pub fields: HashMap<String, FieldValue>,
 }
 
 /// Statistics about a log file
@@ -89,23 +261,165 @@ pub struct LogStatistics {
     pub total_messages: usize,
     pub unique_senders: usize,
     pub unique_message_types: usize,
+    pub messages_by_class: HashMap<String, usize>,
     pub time_span: f64,
     pub message_rate: f64,
 }
 
+impl LogStatistics {
+    /// Compute statistics over a telemetry stream, consuming it message by message
+    pub async fn from_stream(stream: &mut TelemetryStream) -> Result<Self> {
+        let mut builder = LogStatisticsBuilder::new();
+        while let Some(message) = stream.next().await {
+            builder.add(&message?);
+        }
+        Ok(builder.finish())
+    }
+
+    /// Compute statistics over a telemetry stream and check the `ALIVE` md5sums against the configuration
+    pub async fn from_stream_checked(
+        stream: &mut TelemetryStream,
+        config: &LogConfiguration,
+    ) -> Result<(Self, Vec<ConfigCheck>)> {
+        let mut builder = LogStatisticsBuilder::new();
+        let mut alive = AliveMd5s::default();
+        while let Some(message) = stream.next().await {
+            let message = message?;
+            builder.add(&message);
+            alive.add(&message);
+        }
+        Ok((builder.finish(), alive.check(config)))
+    }
+
+    /// Compute overall and per-aircraft statistics over a telemetry stream
+    pub async fn from_stream_by_aircraft(
+        stream: &mut TelemetryStream,
+        config: &LogConfiguration,
+    ) -> Result<(Self, Vec<AircraftStatistics>)> {
+        let mut overall = LogStatisticsBuilder::new();
+        let mut by_sender: BTreeMap<u8, LogStatisticsBuilder> = BTreeMap::new();
+        while let Some(message) = stream.next().await {
+            let message = message?;
+            overall.add(&message);
+            by_sender.entry(message.sender_id).or_default().add(&message);
+        }
+
+        let per_aircraft = by_sender.into_iter()
+            .map(|(sender_id, builder)| AircraftStatistics {
+                sender_id,
+                aircraft: config.aircraft_for_sender(sender_id).cloned(),
+                statistics: builder.finish(),
+            })
+            .collect();
+
+        Ok((overall.finish(), per_aircraft))
+    }
+}
+
+impl LogConfiguration {
+    /// Paparazzi versions the flight was logged and built with
+    pub fn build_provenance(&self) -> BuildProvenance {
+        BuildProvenance {
+            runtime: self.paparazzi_version.clone(),
+            build: self.build_version.clone(),
+        }
+    }
+
+    /// Aircraft configuration of the sender of a message
+    pub fn aircraft_for_sender(&self, sender_id: u8) -> Option<&AircraftInfo> {
+        self.aircraft.get(&sender_id)
+    }
+
+    /// Airframe configuration of the sender of a message
+    pub fn airframe_for_sender(&self, sender_id: u8) -> Option<&AirframeConfig> {
+        self.airframes.get(&sender_id)
+    }
+
+    /// Flight plan of the sender of a message
+    pub fn flight_plan_for_sender(&self, sender_id: u8) -> Option<&FlightPlan> {
+        self.flight_plans.get(&sender_id)
+    }
+
+    /// Settings table of the sender of a message
+    pub fn settings_for_sender(&self, sender_id: u8) -> Option<&SettingsTable> {
+        self.settings.get(&sender_id)
+    }
+}
+
+/// Incremental statistics over a stream of telemetry messages
+#[derive(Debug, Default)]
+pub struct LogStatisticsBuilder {
+    total_messages: usize,
+    senders: HashSet<u8>,
+    message_types: HashSet<(MessageClass, u8)>,
+    messages_by_class: HashMap<String, usize>,
+    first_timestamp: Option<f64>,
+    last_timestamp: Option<f64>,
+}
+
+impl LogStatisticsBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, message: &TelemetryMessage) {
+        self.total_messages += 1;
+        self.senders.insert(message.sender_id);
+        self.message_types.insert((message.msg_class, message.message_id));
+        *self.messages_by_class.entry(message.msg_class.to_string()).or_insert(0) += 1;
+        self.first_timestamp.get_or_insert(message.timestamp);
+        self.last_timestamp = Some(message.timestamp);
+    }
+
+    pub fn finish(self) -> LogStatistics {
+        let time_span = match (self.first_timestamp, self.last_timestamp) {
+            (Some(first), Some(last)) if self.total_messages > 1 => last - first,
+            _ => 0.0,
+        };
+
+        let message_rate = if time_span > 0.0 {
+            self.total_messages as f64 / time_span
+        } else {
+            0.0
+        };
+
+        LogStatistics {
+            total_messages: self.total_messages,
+            unique_senders: self.senders.len(),
+            unique_message_types: self.message_types.len(),
+            messages_by_class: self.messages_by_class,
+            time_span,
+            message_rate,
+        }
+    }
+}
+
+impl LogFile {
+    /// Remaining messages belonging to a single message class
+    pub fn messages_in_class(&mut self, class: MessageClass) -> impl Iterator<Item = Result<TelemetryMessage>> + '_ {
+        self.messages.by_ref().filter(move |m| m.as_ref().map_or(true, |m| m.msg_class == class))
+    }
+
+    /// Remaining messages sent by a single aircraft
+    pub fn messages_from(&mut self, sender_id: u8) -> impl Iterator<Item = Result<TelemetryMessage>> + '_ {
+        self.messages.by_ref().filter(move |m| m.as_ref().map_or(true, |m| m.sender_id == sender_id))
+    }
+
+    /// Remaining uplink (datalink) messages sent to the aircraft
+    pub fn uplink_messages(&mut self) -> impl Iterator<Item = Result<TelemetryMessage>> + '_ {
+        self.messages.by_ref().filter(|m| m.as_ref().map_or(true, |m| m.msg_class.is_uplink()))
+    }
+}
+
 impl SchemaManager {
     /// Parse a PaparazziUAV log file pair (.log + .data)
-    pub async fn parse_log_file(&mut self, log_path: &Path) -> Result<LogFile> {
+    pub async fn parse_log_file(&self, log_path: &Path) -> Result<LogFile> {
         let log_path = log_path.to_path_buf();
         let cache_key = log_path.to_string_lossy().to_string();
         
         info!("Parsing PaparazziUAV log file: {:?}", log_path);
 
-        // Read and parse the .log file
-        let log_content = fs::read_to_string(&log_path).await
-            .map_err(|e| anyhow!("Failed to read log file {:?}: {}", log_path, e))?;
-
-        let configuration = self.parse_log_configuration(&log_content)?;
+        let configuration = self.load_log_configuration(&log_path).await?;
         
         // Determine data file path
         let data_file_path = log_path.with_extension("data");
@@ -114,11 +428,11 @@ impl SchemaManager {
         }
 
         // Parse telemetry messages
-        let telemetry_messages = self.parse_telemetry_data(&data_file_path, &configuration).await?;
+        let messages = self.parse_telemetry_data(&data_file_path, &configuration).await?;
 
         let log_file = LogFile {
             configuration,
-            messages: telemetry_messages,
+            messages,
             file_path: log_path.clone(),
             data_file_path,
         };
@@ -126,182 +440,310 @@ impl SchemaManager {
         Ok(log_file)
     }
 
-    /// Get statistics for a log file
-    pub fn get_log_statistics(&self, log_file: &LogFile) -> LogStatistics {
-        let total_messages = log_file.messages.len();
-        let unique_senders: std::collections::HashSet<u8> = log_file.messages.iter().map(|m| m.sender_id).collect();
-        let unique_message_types: std::collections::HashSet<u8> = log_file.messages.iter().map(|m| m.message_id).collect();
-        
-        let time_span = if total_messages > 1 {
-            log_file.messages.last().unwrap().timestamp - log_file.messages.first().unwrap().timestamp
-        } else {
-            0.0
-        };
-        
-        let message_rate = if time_span > 0.0 {
-            total_messages as f64 / time_span
-        } else {
-            0.0
-        };
+    /// Get statistics for a log file, reading the rest of its messages
+    pub fn get_log_statistics(&self, log_file: &mut LogFile) -> Result<LogStatistics> {
+        let mut builder = LogStatisticsBuilder::new();
+        for message in log_file.messages.by_ref() {
+            builder.add(&message?);
+        }
+        Ok(builder.finish())
+    }
 
-        LogStatistics {
-            total_messages,
-            unique_senders: unique_senders.len(),
-            unique_message_types: unique_message_types.len(),
-            time_span,
-            message_rate,
+    /// Get statistics for every aircraft that sent messages in a log file, reading the rest of its messages
+    pub fn get_aircraft_statistics(&self, log_file: &mut LogFile) -> Result<Vec<AircraftStatistics>> {
+        let mut by_sender: BTreeMap<u8, LogStatisticsBuilder> = BTreeMap::new();
+        for message in log_file.messages.by_ref() {
+            let message = message?;
+            by_sender.entry(message.sender_id).or_default().add(&message);
         }
+
+        Ok(by_sender.into_iter()
+            .map(|(sender_id, builder)| AircraftStatistics {
+                sender_id,
+                aircraft: log_file.configuration.aircraft_for_sender(sender_id).cloned(),
+                statistics: builder.finish(),
+            })
+            .collect())
     }
 
-    /// Parse the log file configuration from .log file content
-    fn parse_log_configuration(&self, log_content: &str) -> Result<LogConfiguration> {
-        // Find the paparazzi version from comments
-        let paparazzi_version = log_content
-            .lines()
-            .find(|line| line.contains("paparazzi_version"))
-            .and_then(|line| {
-                if let Some(start) = line.find("paparazzi_version") {
-                    let version_part = &line[start..];
-                    if let Some(v_start) = version_part.find(' ') {
-                        let version_str = &version_part[v_start + 1..];
-                        Some(version_str.trim_end_matches(" -->").trim().to_string())
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
-            });
+    /// Parse the log file configuration from .log file content.
+    ///
+    /// The airframe, flight plan and settings of every aircraft and the protocol are parsed
+    /// on their own: one that is not well-formed is left out with a diagnostic instead of
+    /// failing the whole configuration.
+    pub fn parse_log_configuration(&self, log_content: &str) -> Result<LogConfiguration> {
+        let BuildProvenance { runtime, build } = BuildProvenance::from_log_header(log_content);
 
-        // Find the configuration element
-        let config_start = log_content.find("<configuration")
+        let (_, configuration_element) = xml::find_elements(log_content, "configuration").into_iter().next()
             .ok_or_else(|| anyhow!("No <configuration> element found in log file"))?;
-        let config_line_end = log_content[config_start..].find('>')
+        let header = xml::start_tag(configuration_element)
             .ok_or_else(|| anyhow!("Malformed <configuration> element"))?;
-        let config_element = &log_content[config_start..config_start + config_line_end + 1];
-        
+        // The logger leaves raw `&` in attributes, escaped before parsing
+        let header = xml::escape_stray_ampersands(&header);
+        let header = xml::parse_document(&header)
+            .map_err(|e| anyhow!("Malformed <configuration> element: {}", e))?;
+        let config_element = header.root_element();
+
         // Extract configuration attributes
-        let time_of_day = self.extract_attribute(config_element, "time_of_day")?
+        let time_of_day = xml::attr(config_element, "time_of_day")
+            .ok_or_else(|| anyhow!("Attribute 'time_of_day' not found"))?
             .parse::<f64>()
             .map_err(|_| anyhow!("Invalid time_of_day format"))?;
-        
-        let data_file = self.extract_attribute(config_element, "data_file")?;
-        
-        // Find aircraft configuration
-        let aircraft_start = log_content.find("<aircraft")
-            .ok_or_else(|| anyhow!("No <aircraft> element found in log file"))?;
-        let aircraft_line_end = log_content[aircraft_start..].find('>')
-            .ok_or_else(|| anyhow!("Malformed <aircraft> element"))?;
-        let aircraft_element = &log_content[aircraft_start..aircraft_start + aircraft_line_end + 1];
-        
-        let ac_id = self.extract_attribute(aircraft_element, "ac_id")?
-            .parse::<u32>()
-            .map_err(|_| anyhow!("Invalid ac_id format"))?;
-        
-        let ac_name = self.extract_attribute(aircraft_element, "name")
-            .unwrap_or_else(|_| format!("Aircraft_{}", ac_id));
-        
-        let aircraft = AircraftInfo {
-            ac_id,
-            name: ac_name,
-            flight_plan: self.extract_attribute(aircraft_element, "flight_plan").ok(),
-            airframe: self.extract_attribute(aircraft_element, "airframe").ok(),
-            firmware: None, // Will be extracted from firmware element if needed
+
+        let data_file = xml::attr(config_element, "data_file")
+            .ok_or_else(|| anyhow!("Attribute 'data_file' not found"))?;
+
+        // Every aircraft configuration, not just the first one
+        let AircraftConfigs { aircraft, airframes, flight_plans, settings, mut diagnostics } = self.parse_aircraft(log_content)?;
+        debug!("Loaded {} aircraft configurations", aircraft.len());
+
+        // Message dictionary used to decode the .data file
+        let (protocol, dictionary) = match MessageDictionary::from_log_content(log_content) {
+            Ok(protocol) => (protocol, DictionarySource::Log),
+            Err(e) => {
+                let bundled = default_dictionary();
+                warn!("Unusable <protocol> section, decoding with the bundled {} v{} dictionary: {}", bundled.name, bundled.version, e);
+                let offset = log_content.find("<protocol>").unwrap_or(0);
+                let end = log_content.ceil_char_boundary((offset + 200).min(log_content.len()));
+                diagnostics.push(Diagnostic::malformed_config("<protocol>", line_at(log_content, offset),
+                    &log_content[offset..end], &e));
+                (bundled.load()?, bundled.source())
+            }
         };
-        
+        debug!("Loaded {} message definitions from {:?}", protocol.len(), dictionary);
+
         Ok(LogConfiguration {
             time_of_day,
+            time_source: TimeSource::TimeOfDay,
+            local_start: None,
             data_file,
             aircraft,
-            paparazzi_version,
-            build_version: None, // Can be extracted from build comment if needed
+            airframes,
+            flight_plans,
+            settings,
+            paparazzi_version: runtime,
+            build_version: build,
+            protocol,
+            dictionary,
+            diagnostics,
         })
     }
 
-    /// Parse telemetry data from .data file
-    async fn parse_telemetry_data(&self, data_path: &Path, _config: &LogConfiguration) -> Result<Vec<TelemetryMessage>> {
-        let data_content = fs::read_to_string(data_path).await
-            .map_err(|e| anyhow!("Failed to read data file {:?}: {}", data_path, e))?;
-        
-        let mut messages = Vec::new();
-        
-        for (line_num, line) in data_content.lines().enumerate() {
-            if line.trim().is_empty() || line.starts_with('#') {
+    /// Start decoding telemetry data from .data file, chunks of it in parallel
+    async fn parse_telemetry_data(&self, data_path: &Path, config: &LogConfiguration) -> Result<ParallelTelemetry> {
+        let data_path = data_path.to_path_buf();
+        let protocol = config.protocol.clone();
+
+        tokio::task::spawn_blocking(move || parse_telemetry_parallel(&data_path, protocol, DEFAULT_CHUNK_BYTES))
+            .await
+            .map_err(|e| anyhow!("Telemetry parser task failed: {}", e))?
+    }
+
+    /// Parse all `<aircraft>` elements of the `<conf>` section with their airframes, flight plans and settings, keyed by ac_id
+    fn parse_aircraft(&self, log_content: &str) -> Result<AircraftConfigs> {
+        let mut configs = AircraftConfigs::default();
+
+        let (conf_offset, conf) = xml::find_elements(log_content, "conf").into_iter().next()
+            .ok_or_else(|| anyhow!("No <conf> element found in log file"))?;
+        for (offset, element) in xml::find_elements(conf, "aircraft") {
+            let offset = conf_offset + offset;
+            let header = xml::start_tag(element).unwrap_or_default();
+            let header = xml::escape_stray_ampersands(&header);
+            let Some(document) = parse_section(log_content, offset, "<aircraft>", element, &mut configs.diagnostics, |_| {
+                xml::parse_document(&header)
+            }) else {
+                continue;
+            };
+            let aircraft_element = document.root_element();
+
+            let Some((ac_id, sender_id)) = parse_section(log_content, offset, "<aircraft>", &header, &mut configs.diagnostics, |_| {
+                let ac_id = xml::attr(aircraft_element, "ac_id")
+                    .ok_or_else(|| anyhow!("Attribute 'ac_id' not found"))?;
+                let ac_id = ac_id.parse::<u32>()
+                    .map_err(|_| anyhow!("Invalid ac_id '{}'", ac_id))?;
+                // sender_id is a single byte in the telemetry link
+                let sender_id = u8::try_from(ac_id)
+                    .map_err(|_| anyhow!("ac_id {} does not fit the one-byte sender id", ac_id))?;
+                Ok((ac_id, sender_id))
+            }) else {
                 continue;
+            };
+            let name = xml::attr(aircraft_element, "name")
+                .unwrap_or_else(|| format!("Aircraft_{}", ac_id));
+
+            let body_offset = element.find('>').map_or(element.len(), |end| end + 1);
+            let body = &element[body_offset..];
+            // `<autopilot>` and `<generated_settings>` have `<settings>` of their own:
+            // a section is the first one outside the sections found before it
+            let mut found: Vec<(usize, usize)> = Vec::new();
+            let mut section = |tag: &'static str| {
+                let (start, text) = xml::find_elements(body, tag).into_iter()
+                    .find(|(start, _)| !found.iter().any(|range| (range.0..range.1).contains(start)))?;
+                found.push((start, start + text.len()));
+                Some((offset + body_offset + start, text))
+            };
+            let diagnostics = &mut configs.diagnostics;
+
+            let airframe = section("airframe").and_then(|(offset, text)| {
+                parse_section(log_content, offset, &format!("Airframe of aircraft {}", sender_id), text, diagnostics, |text| {
+                    let escaped = xml::escape_stray_ampersands(text);
+                    xml::parse_document(&escaped).map(|document| AirframeConfig::from_node(document.root_element()))
+                })
+            });
+
+            // A flight plan that cannot be resolved should not hide the rest of the configuration
+            let flight_plan = section("flight_plan").and_then(|(offset, text)| {
+                parse_section(log_content, offset, &format!("Flight plan of aircraft {}", sender_id), text, diagnostics, |text| {
+                    let escaped = xml::escape_stray_ampersands(text);
+                    xml::parse_document(&escaped).and_then(|document| FlightPlan::from_node(document.root_element()))
+                })
+            });
+            if let Some(flight_plan) = flight_plan {
+                configs.flight_plans.insert(sender_id, flight_plan);
             }
-            
-            match self.parse_telemetry_line(line) {
-                Ok(msg) => messages.push(msg),
-                Err(e) => {
-                    // Log warning but continue parsing
-                    info!("Warning: Failed to parse line {}: {} ({})", line_num + 1, e, line);
-                }
+
+            section("autopilot");
+            section("generated_settings");
+            let settings = section("settings").and_then(|(offset, text)| {
+                parse_section(log_content, offset, &format!("Settings of aircraft {}", sender_id), text, diagnostics, |text| {
+                    let escaped = xml::escape_stray_ampersands(text);
+                    xml::parse_document(&escaped).map(|document| SettingsTable::from_node(document.root_element()))
+                })
+            });
+            if let Some(settings) = settings {
+                configs.settings.insert(sender_id, settings);
+            }
+
+            configs.aircraft.insert(sender_id, AircraftInfo {
+                ac_id,
+                name,
+                flight_plan: xml::attr(aircraft_element, "flight_plan"),
+                airframe: xml::attr(aircraft_element, "airframe"),
+                firmware: airframe.as_ref().and_then(|a| a.firmware_name()).map(str::to_string),
+            });
+            if let Some(airframe) = airframe {
+                configs.airframes.insert(sender_id, airframe);
             }
         }
-        
-        info!("Parsed {} telemetry messages from {:?}", messages.len(), data_path);
-        Ok(messages)
-    }
 
-    /// Extract an attribute value from XML content
-    fn extract_attribute(&self, xml_content: &str, attr_name: &str) -> Result<String> {
-        let pattern = format!(r#"{}="([^"]*)""#, attr_name);
-        let re = regex::Regex::new(&pattern)
-            .map_err(|e| anyhow!("Invalid regex pattern: {}", e))?;
-        
-        let captures = re.captures(xml_content)
-            .ok_or_else(|| anyhow!("Attribute '{}' not found", attr_name))?;
-        
-        Ok(captures[1].to_string())
+        if configs.aircraft.is_empty() {
+            return Err(anyhow!("No <aircraft> element found in log file"));
+        }
+        Ok(configs)
     }
+}
 
-    /// Parse a single telemetry message line
-    fn parse_telemetry_line(&self, line: &str) -> Result<TelemetryMessage> {
-        // PaparazziUAV telemetry format: (timestamp sender_id MESSAGE_NAME field1 field2 ...)
-        let trimmed = line.trim();
-        
-        // Remove parentheses if present
-        let content = if trimmed.starts_with('(') && trimmed.ends_with(')') {
-            &trimmed[1..trimmed.len()-1]
-        } else {
-            trimmed
-        };
-        
-        let parts: Vec<&str> = content.split_whitespace().collect();
-        
-        if parts.len() < 3 {
-            return Err(anyhow!("Invalid telemetry line format: too few fields"));
-        }
-        
-        let timestamp = parts[0].parse::<f64>()
-            .map_err(|_| anyhow!("Invalid timestamp format"))?;
-        
-        let sender_id = parts[1].parse::<u8>()
-            .map_err(|_| anyhow!("Invalid sender_id format"))?;
-        
-        // Message name is the third field (string, not numeric ID)
-        let message_name = parts[2].to_string();
-        
-        // Generate a consistent numeric ID from the message name for compatibility
-        let message_id = {
-            let mut hash: u32 = 0;
-            for byte in message_name.bytes() {
-                hash = hash.wrapping_mul(31).wrapping_add(byte as u32);
-            }
-            (hash % 256) as u8  // Keep it within u8 range
-        };
-        
-        let mut fields = HashMap::new();
-        for (i, field) in parts.iter().skip(3).enumerate() {
-            fields.insert(format!("field_{}", i), field.to_string());
+/// 1-based line of a byte offset of the .log
+fn line_at(log_content: &str, offset: usize) -> usize {
+    log_content[..offset].matches('\n').count() + 1
+}
+
+/// Parse one section of the .log, recording a diagnostic and skipping it when it is not well-formed
+fn parse_section<T>(
+    log_content: &str,
+    offset: usize,
+    section: &str,
+    text: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+    parse: impl FnOnce(&str) -> Result<T>,
+) -> Option<T> {
+    match parse(text) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            let line = line_at(log_content, offset);
+            warn!("Leaving out {} at line {} of the .log: {}", section, line, e);
+            diagnostics.push(Diagnostic::malformed_config(section, line, text, e));
+            None
         }
-        
-        Ok(TelemetryMessage {
-            timestamp,
-            sender_id,
-            message_id,
-            message_name,
-            fields,
-        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::fixtures::sample_log;
+
+    #[test]
+    fn test_parse_all_aircraft() {
+        let log_content = sample_log();
+        let configuration = SchemaManager::new().parse_log_configuration(&log_content).unwrap();
+
+        assert_eq!(configuration.aircraft.len(), 62);
+
+        let indi = configuration.aircraft_for_sender(13).unwrap();
+        assert_eq!(indi.name, "ARDrone2_indi");
+        assert_eq!(indi.firmware.as_deref(), Some("rotorcraft"));
+
+        // The last aircraft of the <conf> section is found as well
+        assert_eq!(configuration.aircraft_for_sender(36).unwrap().name, "RW3_AG_OPTI");
+        assert!(configuration.aircraft_for_sender(250).is_none());
+
+        // Airframes come from the same XML tree, including those next to malformed flight plans
+        assert_eq!(configuration.airframes.len(), 62);
+        let airframe = configuration.airframe_for_sender(13).unwrap();
+        assert_eq!(airframe.name, "ardrone2_indi");
+        let max_bank = airframe.define("GUIDANCE_H_MAX_BANK").unwrap();
+        assert_eq!((max_bank.numeric_value, max_bank.unit.as_deref()), (Some(20.0), Some("deg")));
+
+        assert_eq!(configuration.flight_plans.len(), 62);
+        let flight_plan = configuration.flight_plan_for_sender(13).unwrap();
+        assert_eq!(flight_plan.name, "Rotorcraft Basic (Enac)");
+        assert!((flight_plan.origin.lat - 43.564119).abs() < 1e-6);
+        assert_eq!(flight_plan.waypoints.len(), 9);
+
+        // The other 177 <dl_setting> of the log are the autopilot's own copies of its settings
+        assert_eq!(configuration.settings.values().map(SettingsTable::len).sum::<usize>(), 372);
+        let ap = configuration.settings_for_sender(12).unwrap().settings.iter().find(|s| s.var == "autopilot_mode_ap").unwrap();
+        assert_eq!((ap.index, ap.label(2.0)), (3, Some("NAV")));
+        assert!(configuration.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_malformed_sections() {
+        let log_content = sample_log();
+        // An unterminated attribute in one airframe, a stray `<` in the first flight plan
+        let broken = log_content
+            .replacen(r#"<airframe NAME="ardrone2_indi">"#, r#"<airframe NAME="ardrone2_indi>"#, 1)
+            .replacen(r#"ALT="152">"#, r#"ALT="152"> if alt < 10"#, 1);
+        let configuration = SchemaManager::new().parse_log_configuration(&broken).unwrap();
+
+        assert_eq!(configuration.aircraft.len(), 62);
+        assert!(configuration.airframe_for_sender(13).is_none());
+        assert!(configuration.flight_plan_for_sender(13).is_none());
+        assert_eq!(configuration.airframes.len(), 61);
+        assert_eq!(configuration.flight_plans.len(), 61);
+        assert_eq!(configuration.settings.len(), 62);
+        assert_eq!(configuration.protocol.len(), 365);
+
+        let messages: Vec<_> = configuration.diagnostics.iter().map(|d| (d.kind, d.message.as_str())).collect();
+        assert_eq!(messages.len(), 2);
+        assert!(messages.iter().all(|(kind, _)| *kind == DiagnosticKind::MalformedConfig));
+        assert!(messages[0].1.starts_with("Airframe of aircraft 13 at line 6 of the .log"));
+        assert!(messages[1].1.starts_with("Flight plan of aircraft 13 at line 155 of the .log"));
+    }
+
+    #[test]
+    fn test_invalid_aircraft_and_flight_plan() {
+        let log_content = sample_log();
+        // A non-numeric ac_id, and a well-formed flight plan without its origin
+        let broken = log_content
+            .replacen(r#"<aircraft name="ARDrone2_opticflow" ac_id="12""#, r#"<aircraft name="ARDrone2_opticflow" ac_id="twelve""#, 1)
+            .replacen(r#"LAT0="43 33 50.83""#, "", 1);
+        let configuration = SchemaManager::new().parse_log_configuration(&broken).unwrap();
+
+        // The aircraft is left out instead of failing the whole configuration
+        assert_eq!(configuration.aircraft.len(), 61);
+        assert!(configuration.aircraft_for_sender(12).is_none());
+        assert!(configuration.flight_plan_for_sender(13).is_none());
+        assert!(configuration.airframe_for_sender(13).is_some());
+        assert_eq!(configuration.flight_plans.len(), 60);
+
+        let messages: Vec<_> = configuration.diagnostics.iter().map(|d| (d.kind, d.message.as_str())).collect();
+        assert_eq!(messages.len(), 2);
+        assert!(messages.iter().all(|(kind, _)| *kind == DiagnosticKind::MalformedConfig));
+        assert!(messages[0].1.starts_with("Flight plan of aircraft 13 at line 155 of the .log"));
+        assert!(messages[0].1.ends_with("Flight plan 'Rotorcraft Basic (Enac)' has no LAT0 attribute"));
+        assert!(messages[1].1.starts_with("<aircraft> at line 448 of the .log"));
+        assert!(messages[1].1.ends_with("Invalid ac_id 'twelve'"));
     }
 }
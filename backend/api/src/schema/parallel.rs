@@ -0,0 +1,308 @@
+//! Parallel chunked parsing of .data files
+
+use anyhow::{anyhow, Result};
+use rayon::prelude::*;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::{Receiver, SyncSender, sync_channel};
+use tracing::info;
+
+use super::{DataFormat, DataProgress, Diagnostics, MessageDictionary, TelemetryMessage, TelemetryReader, parse_telemetry_line};
+
+/// Default size of the byte ranges decoded by each worker
+pub const DEFAULT_CHUNK_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Messages of a binary capture sent to the consumer at a time
+const BINARY_BATCH: usize = 4096;
+
+/// Split a file of `len` bytes into ranges of roughly `chunk_bytes`, each ending on a line boundary
+pub fn chunk_boundaries<R: Read + Seek>(reader: &mut R, len: u64, chunk_bytes: u64) -> Result<Vec<(u64, u64)>> {
+    let chunk_bytes = chunk_bytes.max(1);
+    let mut ranges = Vec::new();
+    let mut start = 0;
+
+    while start < len {
+        let target = start + chunk_bytes;
+        if target >= len {
+            ranges.push((start, len));
+            break;
+        }
+
+        // Move the split to just after the next newline, starting from the byte
+        // before the target so a split that already sits on a line start stays there
+        reader.seek(SeekFrom::Start(target - 1))?;
+        let mut skipped = Vec::new();
+        let read = BufReader::new(&mut *reader).read_until(b'\n', &mut skipped)?;
+        let end = (target - 1 + read as u64).min(len);
+
+        ranges.push((start, end));
+        start = end;
+    }
+
+    Ok(ranges)
+}
+
+/// Decode a .data file on the rayon worker pool.
+///
+/// The file is split at line boundaries and every chunk is decoded with the
+/// same `TelemetryReader` as the sequential path. A producer thread decodes one
+/// chunk per worker at a time and hands them over in file order through a
+/// bounded channel, so the messages come out exactly as the sequential reader
+/// yields them, timestamps going back included, and at most two windows of
+/// chunks are held in memory.
+/// Binary captures have no line boundaries to split at and are decoded sequentially
+/// on the producer thread.
+///
+/// The diagnostics of the chunks are merged with their line numbers counted from
+/// the start of the file, and a timestamp going back across a chunk boundary is
+/// reported like one inside a chunk. They are complete once the iterator is exhausted.
+pub fn parse_telemetry_parallel(data_path: &Path, protocol: MessageDictionary, chunk_bytes: u64) -> Result<ParallelTelemetry> {
+    let mut file = File::open(data_path)
+        .map_err(|e| anyhow!("Failed to open data file {:?}: {}", data_path, e))?;
+    let len = file.metadata()?.len();
+    let format = DataFormat::detect_file(&mut file, &protocol)?;
+    let protocol = Arc::new(protocol);
+    let workers = rayon::current_num_threads();
+    let (sender, receiver) = sync_channel(workers);
+
+    let ranges = if format != DataFormat::Text {
+        let producer_protocol = protocol.clone();
+        std::thread::spawn(move || decode_binary(file, format, &producer_protocol, len, sender));
+        Vec::new()
+    } else {
+        let ranges = chunk_boundaries(&mut file, len, chunk_bytes)?;
+        let producer_ranges = ranges.clone();
+        let producer_path = data_path.to_path_buf();
+        let producer_protocol = protocol.clone();
+        std::thread::spawn(move || {
+            for window in producer_ranges.chunks(workers) {
+                let chunks: Vec<_> = window.par_iter()
+                    .map(|&(start, end)| decode_chunk(&producer_path, &producer_protocol, start, end))
+                    .collect();
+                for chunk in chunks {
+                    if sender.send(chunk).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        ranges
+    };
+
+    Ok(ParallelTelemetry {
+        data_path: data_path.to_path_buf(),
+        protocol,
+        format,
+        receiver,
+        ranges,
+        next_range: 0,
+        current: Vec::new().into_iter(),
+        diagnostics: Diagnostics::default(),
+        lines: 0,
+        previous: None,
+        messages: 0,
+        finished: false,
+    })
+}
+
+/// Messages of a .data file in file order, decoded by `parse_telemetry_parallel`
+#[derive(Debug)]
+pub struct ParallelTelemetry {
+    data_path: PathBuf,
+    protocol: Arc<MessageDictionary>,
+    format: DataFormat,
+    receiver: Receiver<Result<DecodedChunk>>,
+    /// Line-aligned byte ranges of a text file, in the order their chunks arrive
+    ranges: Vec<(u64, u64)>,
+    next_range: usize,
+    current: std::vec::IntoIter<TelemetryMessage>,
+    diagnostics: Diagnostics,
+    lines: usize,
+    /// Timestamp of the last message of the previous chunk
+    previous: Option<f64>,
+    messages: usize,
+    finished: bool,
+}
+
+impl ParallelTelemetry {
+    /// Diagnostics of the chunks received so far
+    pub fn diagnostics(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Account for a chunk that arrived: its diagnostics, and a time jump at its start
+    fn receive(&mut self, chunk: DecodedChunk) -> Result<()> {
+        if self.format != DataFormat::Text {
+            if let Some(progress) = chunk.progress {
+                self.diagnostics = progress.diagnostics;
+            }
+        } else {
+            let (start, end) = self.ranges[self.next_range];
+            self.next_range += 1;
+            if let (Some(previous), Some((first, _))) = (self.previous, chunk.first_last)
+                && first < previous
+                && let Some((line, offset, text)) = first_message_line(&self.data_path, start, end, &self.protocol)?
+            {
+                self.diagnostics.record_time_jump(self.lines + line, start + offset, &text, previous, first);
+            }
+            self.previous = chunk.first_last.map(|(_, last)| last).or(self.previous);
+            if let Some(progress) = chunk.progress {
+                self.diagnostics.extend_chunk(progress.diagnostics, self.lines, start);
+                self.lines += progress.lines_read;
+            }
+        }
+        self.current = chunk.messages.into_iter();
+        Ok(())
+    }
+}
+
+impl Iterator for ParallelTelemetry {
+    type Item = Result<TelemetryMessage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(message) = self.current.next() {
+                self.messages += 1;
+                return Some(Ok(message));
+            }
+            if self.finished {
+                return None;
+            }
+
+            let received = match self.receiver.recv() {
+                Ok(chunk) => chunk.and_then(|chunk| self.receive(chunk)),
+                Err(_) => {
+                    self.finished = true;
+                    info!("Parsed {} telemetry messages ({} diagnostics) from {:?} ({:?}, {} chunk(s))",
+                        self.messages, self.diagnostics.total(), self.data_path, self.format, self.ranges.len().max(1));
+                    return None;
+                }
+            };
+            if let Err(e) = received {
+                self.finished = true;
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+/// Messages of a chunk in file order, with the timestamps of its first and last message
+#[derive(Debug)]
+struct DecodedChunk {
+    messages: Vec<TelemetryMessage>,
+    first_last: Option<(f64, f64)>,
+    /// Progress of the reader once the chunk is decoded; only on the last batch of a binary capture
+    progress: Option<DataProgress>,
+}
+
+fn decode_chunk(data_path: &Path, protocol: &MessageDictionary, start: u64, end: u64) -> Result<DecodedChunk> {
+    let mut file = File::open(data_path)?;
+    file.seek(SeekFrom::Start(start))?;
+    let mut reader = TelemetryReader::new(BufReader::new(file.take(end - start)), protocol, Some(end - start));
+    let messages = reader.by_ref().collect::<Result<Vec<_>>>()?;
+    let first_last = messages.first().zip(messages.last())
+        .map(|(first, last)| (first.timestamp, last.timestamp));
+    Ok(DecodedChunk { messages, first_last, progress: Some(reader.progress().clone()) })
+}
+
+/// Decode a binary capture in batches, ending with the reader's progress
+fn decode_binary(file: File, format: DataFormat, protocol: &MessageDictionary, len: u64, sender: SyncSender<Result<DecodedChunk>>) {
+    let Some(mut reader) = format.binary_reader(BufReader::new(file), protocol, Some(len)) else {
+        return;
+    };
+    loop {
+        let batch = reader.by_ref().take(BINARY_BATCH).collect::<Result<Vec<_>>>();
+        let last = matches!(&batch, Ok(messages) if messages.len() < BINARY_BATCH);
+        let chunk = batch.map(|messages| DecodedChunk {
+            messages,
+            first_last: None,
+            progress: last.then(|| reader.progress().clone()),
+        });
+        let failed = chunk.is_err();
+        if sender.send(chunk).is_err() || last || failed {
+            return;
+        }
+    }
+}
+
+/// Line number, byte offset and text of the first decoded message of a chunk, relative to the chunk
+fn first_message_line(data_path: &Path, start: u64, end: u64, protocol: &MessageDictionary) -> Result<Option<(usize, u64, String)>> {
+    let mut file = File::open(data_path)?;
+    file.seek(SeekFrom::Start(start))?;
+    let mut offset = 0;
+    for (index, line) in BufReader::new(file.take(end - start)).split(b'\n').enumerate() {
+        let line = line?;
+        let text = String::from_utf8_lossy(&line);
+        if parse_telemetry_line(&text, protocol).is_ok() {
+            return Ok(Some((index + 1, offset, text.trim().to_string())));
+        }
+        offset += line.len() as u64 + 1;
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::fixtures::{CLOCK_RESET_DATA, sample_protocol};
+    use crate::schema::DiagnosticKind;
+
+    fn parse(chunk_bytes: u64) -> (Vec<TelemetryMessage>, Diagnostics) {
+        let mut telemetry = parse_telemetry_parallel(Path::new(CLOCK_RESET_DATA), sample_protocol(), chunk_bytes).unwrap();
+        let messages = telemetry.by_ref().collect::<Result<Vec<_>>>().unwrap();
+        assert!(telemetry.is_finished());
+        (messages, telemetry.diagnostics().clone())
+    }
+
+    #[test]
+    fn test_parallel_matches_sequential() {
+        let protocol = sample_protocol();
+        let mut reader = TelemetryReader::open(Path::new(CLOCK_RESET_DATA), &protocol).unwrap();
+        let sequential = reader.by_ref().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(sequential.len(), 625);
+
+        // Byte offset of the first line after the ground station restart
+        let data = std::fs::read_to_string(CLOCK_RESET_DATA).unwrap();
+        let reset = data.lines().take(375).map(|line| line.len() as u64 + 1).sum();
+
+        // Small chunks so the split points land in the middle of lines, and one split right at the reset
+        for chunk_bytes in [64, 4093, reset, DEFAULT_CHUNK_BYTES] {
+            let (messages, diagnostics) = parse(chunk_bytes);
+            assert_eq!(messages, sequential, "chunks of {} bytes", chunk_bytes);
+            assert_eq!(diagnostics, reader.progress().diagnostics, "chunks of {} bytes", chunk_bytes);
+        }
+    }
+
+    #[test]
+    fn test_backwards_timestamps_keep_file_order() {
+        let (messages, diagnostics) = parse(4093);
+
+        // The clock starts over at 0.4s and the messages stay in the order they were logged
+        assert_eq!(messages[374].timestamp, 114.913);
+        assert_eq!(messages[375].timestamp, 0.4);
+        assert!(!messages.is_sorted_by(|a, b| a.timestamp <= b.timestamp));
+
+        assert_eq!(diagnostics.entries.len(), 1);
+        assert_eq!(diagnostics.entries[0].kind, DiagnosticKind::NonMonotonicTime);
+        assert_eq!(diagnostics.entries[0].line, 376);
+    }
+
+    #[test]
+    fn test_chunk_boundaries() {
+        let data = b"a\nbb\nccc\n\ndddd\n";
+        let mut cursor = std::io::Cursor::new(&data[..]);
+        let ranges = chunk_boundaries(&mut cursor, data.len() as u64, 3).unwrap();
+
+        assert_eq!(ranges, [(0, 5), (5, 9), (9, 15)]);
+        for (start, end) in ranges {
+            assert!(start == 0 || data[start as usize - 1] == b'\n');
+            assert_eq!(data[end as usize - 1], b'\n');
+        }
+    }
+}
@@ -0,0 +1,708 @@
+//! PaparazziUAV message protocol (`<protocol>` / messages.xml) parsing and field decoding
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use tracing::warn;
+
+/// Message dictionary built from a `<protocol>` section
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MessageDictionary {
+    pub messages: Vec<MessageDefinition>,
+    #[serde(skip)]
+    by_name: HashMap<String, usize>,
+}
+
+/// Message class (`<msg_class NAME>`) owning a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageClass {
+    /// Downlink messages sent by the aircraft
+    Telemetry,
+    /// Uplink messages sent to the aircraft
+    Datalink,
+    /// Ground segment messages exchanged between agents
+    Ground,
+    Alert,
+    Intermcu,
+}
+
+/// A single `<message NAME ID>` definition
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageDefinition {
+    pub name: String,
+    pub id: u8,
+    pub class: MessageClass,
+    pub class_id: u8,
+    pub fields: Vec<FieldDefinition>,
+}
+
+/// A single `<field TYPE NAME UNIT>` definition
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldDefinition {
+    pub name: String,
+    pub field_type: FieldType,
+    pub unit: Option<String>,
+    pub alt_unit: Option<String>,
+    pub alt_unit_coef: Option<f64>,
+}
+
+/// Field type as declared by the `TYPE` attribute of a `<field>`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum FieldType {
+    Uint8,
+    Int8,
+    Uint16,
+    Int16,
+    Uint32,
+    Int32,
+    Uint64,
+    Int64,
+    Float,
+    Double,
+    Char,
+    String,
+    Array {
+        element_type: Box<FieldType>,
+        size: Option<usize>,
+    },
+}
+
+/// Field value with type information
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum FieldValue {
+    Uint8(u8),
+    Int8(i8),
+    Uint16(u16),
+    Int16(i16),
+    Uint32(u32),
+    Int32(i32),
+    Uint64(u64),
+    Int64(i64),
+    Float(f32),
+    Double(f64),
+    String(String),
+    Array(Vec<FieldValue>),
+}
+
+// Raw XML layout of the <protocol> section, converted into the public model above.
+// Logs write upper-case attributes, pprzlink's messages.xml lower-case ones.
+#[derive(Debug, Deserialize)]
+struct XmlProtocol {
+    /// `<class>` in the messages.xml of pprzlink v1 and older paparazzi releases
+    #[serde(rename = "msg_class", alias = "class", default)]
+    classes: Vec<XmlMsgClass>,
+}
+
+#[derive(Debug, Deserialize)]
+struct XmlMsgClass {
+    #[serde(rename = "@NAME", alias = "@name")]
+    name: String,
+    #[serde(rename = "@ID", alias = "@id")]
+    id: u8,
+    #[serde(rename = "message", default)]
+    messages: Vec<XmlMessage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct XmlMessage {
+    #[serde(rename = "@NAME", alias = "@name")]
+    name: String,
+    #[serde(rename = "@ID", alias = "@id")]
+    id: u8,
+    #[serde(rename = "field", default)]
+    fields: Vec<XmlField>,
+}
+
+#[derive(Debug, Deserialize)]
+struct XmlField {
+    #[serde(rename = "@NAME", alias = "@name")]
+    name: String,
+    #[serde(rename = "@TYPE", alias = "@type")]
+    field_type: String,
+    #[serde(rename = "@UNIT", alias = "@unit")]
+    unit: Option<String>,
+    #[serde(rename = "@ALT_UNIT", alias = "@alt_unit")]
+    alt_unit: Option<String>,
+    #[serde(rename = "@ALT_UNIT_COEF", alias = "@alt_unit_coef")]
+    alt_unit_coef: Option<String>,
+}
+
+impl MessageDictionary {
+    /// Parse a `<protocol>` element (or a standalone messages.xml with a `<protocol>` root)
+    pub fn from_xml(xml: &str) -> Result<Self> {
+        let protocol: XmlProtocol = serde_xml_rs::from_str(xml)
+            .map_err(|e| anyhow!("Failed to parse <protocol> section: {}", e))?;
+
+        let mut messages = Vec::new();
+        for class in protocol.classes {
+            // A class this decoder does not know about should not cost the rest of the dictionary
+            let msg_class = match class.name.parse::<MessageClass>() {
+                Ok(msg_class) => msg_class,
+                Err(e) => {
+                    warn!("Skipping {} message(s) of class {}: {}", class.messages.len(), class.id, e);
+                    continue;
+                }
+            };
+            for message in class.messages {
+                let fields = message.fields.into_iter()
+                    .map(|field| {
+                        let field_type = field.field_type.parse::<FieldType>()
+                            .map_err(|e| anyhow!("Message {} field {}: {}", message.name, field.name, e))?;
+                        let alt_unit_coef = field.alt_unit_coef
+                            .map(|coef| coef.trim().parse::<f64>()
+                                .map_err(|_| anyhow!("Message {} field {}: invalid ALT_UNIT_COEF '{}'", message.name, field.name, coef)))
+                            .transpose()?;
+                        Ok(FieldDefinition {
+                            name: field.name,
+                            field_type,
+                            unit: field.unit.filter(|u| !u.is_empty()),
+                            alt_unit: field.alt_unit.filter(|u| !u.is_empty()),
+                            alt_unit_coef,
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+
+                messages.push(MessageDefinition {
+                    name: message.name,
+                    id: message.id,
+                    class: msg_class,
+                    class_id: class.id,
+                    fields,
+                });
+            }
+        }
+
+        Ok(Self::new(messages))
+    }
+
+    /// Extract and parse the `<protocol>` section embedded in a .log file
+    pub fn from_log_content(log_content: &str) -> Result<Self> {
+        let start = log_content.find("<protocol>")
+            .ok_or_else(|| anyhow!("No <protocol> section found in log file"))?;
+        let end_tag = "</protocol>";
+        let end = log_content[start..].find(end_tag)
+            .ok_or_else(|| anyhow!("Malformed <protocol> section"))?;
+
+        Self::from_xml(&log_content[start..start + end + end_tag.len()])
+    }
+
+    /// Build a dictionary from message definitions, indexing them by name
+    pub fn new(messages: Vec<MessageDefinition>) -> Self {
+        let mut by_name = HashMap::new();
+        for (index, message) in messages.iter().enumerate() {
+            // Keep the first definition when a name appears in several classes
+            by_name.entry(message.name.clone()).or_insert(index);
+        }
+
+        Self { messages, by_name }
+    }
+
+    /// Look up a message definition by name
+    pub fn get(&self, name: &str) -> Option<&MessageDefinition> {
+        self.by_name.get(name).map(|&index| &self.messages[index])
+    }
+
+    /// Look up a message definition by class and protocol ID
+    pub fn get_by_id(&self, class: MessageClass, id: u8) -> Option<&MessageDefinition> {
+        self.messages.iter().find(|m| m.class == class && m.id == id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Normalized text of the dictionary: messages ordered by class and id, one line per
+    /// message and per field. Descriptions, attribute order and XML formatting are left out,
+    /// so two protocols that decode identically give the same text.
+    pub fn canonical(&self) -> String {
+        let mut messages: Vec<_> = self.messages.iter().collect();
+        messages.sort_by_key(|m| (m.class_id, m.id));
+
+        let mut canonical = String::new();
+        for message in messages {
+            canonical.push_str(&format!("{} {} {} {}\n", message.class_id, message.class, message.id, message.name));
+            for field in &message.fields {
+                canonical.push_str(&format!("  {} {} unit={} alt_unit={} alt_unit_coef={}\n",
+                    field.name,
+                    field.field_type,
+                    field.unit.as_deref().unwrap_or(""),
+                    field.alt_unit.as_deref().unwrap_or(""),
+                    field.alt_unit_coef.map(|coef| coef.to_string()).unwrap_or_default()));
+            }
+        }
+        canonical
+    }
+
+    /// blake3 hash of the canonical text, identifying the protocol in the schema registry
+    pub fn schema_hash(&self) -> String {
+        blake3::hash(self.canonical().as_bytes()).to_hex().to_string()
+    }
+}
+
+impl MessageClass {
+    /// Whether messages of this class travel from the ground to the aircraft
+    pub fn is_uplink(&self) -> bool {
+        matches!(self, MessageClass::Datalink)
+    }
+}
+
+impl fmt::Display for MessageClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            MessageClass::Telemetry => "telemetry",
+            MessageClass::Datalink => "datalink",
+            MessageClass::Ground => "ground",
+            MessageClass::Alert => "alert",
+            MessageClass::Intermcu => "intermcu",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for MessageClass {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "telemetry" => Ok(MessageClass::Telemetry),
+            "datalink" => Ok(MessageClass::Datalink),
+            "ground" => Ok(MessageClass::Ground),
+            "alert" => Ok(MessageClass::Alert),
+            "intermcu" => Ok(MessageClass::Intermcu),
+            other => Err(anyhow!("Unknown message class '{}'", other)),
+        }
+    }
+}
+
+impl MessageDefinition {
+    /// Decode the raw field tokens of a .data line into named, typed values
+    pub fn decode_fields(&self, values: &[&str]) -> Result<HashMap<String, FieldValue>> {
+        if values.len() != self.fields.len() {
+            return Err(anyhow!(
+                "Field count mismatch for {}: expected {}, got {}",
+                self.name, self.fields.len(), values.len()
+            ));
+        }
+
+        self.fields.iter()
+            .zip(values)
+            .map(|(field, raw)| {
+                let value = field.field_type.parse_value(raw)
+                    .map_err(|e| anyhow!("Invalid value '{}' for {}.{}: {}", raw, self.name, field.name, e))?;
+                Ok((field.name.clone(), value))
+            })
+            .collect()
+    }
+
+    /// Look up a field definition by name
+    pub fn field(&self, name: &str) -> Option<&FieldDefinition> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+
+    /// Scalar channels of a decoded message in declaration order, with
+    /// array fields expanded into indexable `field[i]` sub-channels
+    pub fn channels<'a>(&'a self, fields: &'a HashMap<String, FieldValue>) -> Vec<(String, &'a FieldDefinition, &'a FieldValue)> {
+        let mut channels = Vec::new();
+        for field in &self.fields {
+            match fields.get(&field.name) {
+                Some(FieldValue::Array(elements)) => {
+                    for (index, element) in elements.iter().enumerate() {
+                        channels.push((format!("{}[{}]", field.name, index), field, element));
+                    }
+                }
+                Some(value) => channels.push((field.name.clone(), field, value)),
+                None => {}
+            }
+        }
+        channels
+    }
+}
+
+/// Reference to a single scalar channel, e.g. `GPS_INT.alt` or `IMU_GYRO_RAW.gp[0]`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ChannelRef {
+    pub message: String,
+    pub field: String,
+    pub index: Option<usize>,
+}
+
+impl ChannelRef {
+    /// Name of the channel within its message (`alt`, `gp[0]`)
+    pub fn channel_name(&self) -> String {
+        match self.index {
+            Some(index) => format!("{}[{}]", self.field, index),
+            None => self.field.clone(),
+        }
+    }
+
+    /// Resolve this channel against the decoded fields of a message
+    pub fn resolve<'a>(&self, fields: &'a HashMap<String, FieldValue>) -> Option<&'a FieldValue> {
+        let value = fields.get(&self.field)?;
+        match (self.index, value) {
+            (Some(index), FieldValue::Array(elements)) => elements.get(index),
+            (None, value) => Some(value),
+            (Some(_), _) => None,
+        }
+    }
+}
+
+impl FromStr for ChannelRef {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (message, channel) = s.split_once('.')
+            .ok_or_else(|| anyhow!("Channel '{}' must have the form MESSAGE.field", s))?;
+        let (field, index) = match channel.split_once('[') {
+            Some((field, rest)) => {
+                let index = rest.strip_suffix(']')
+                    .and_then(|i| i.parse::<usize>().ok())
+                    .ok_or_else(|| anyhow!("Invalid channel index in '{}'", s))?;
+                (field, Some(index))
+            }
+            None => (channel, None),
+        };
+
+        Ok(Self {
+            message: message.to_string(),
+            field: field.to_string(),
+            index,
+        })
+    }
+}
+
+impl fmt::Display for ChannelRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.message, self.channel_name())
+    }
+}
+
+impl FieldType {
+    /// Parse a raw text value according to this type
+    pub fn parse_value(&self, raw: &str) -> Result<FieldValue> {
+        match self {
+            FieldType::Uint8 => Ok(FieldValue::Uint8(raw.parse()?)),
+            FieldType::Int8 => Ok(FieldValue::Int8(raw.parse()?)),
+            FieldType::Uint16 => Ok(FieldValue::Uint16(raw.parse()?)),
+            FieldType::Int16 => Ok(FieldValue::Int16(raw.parse()?)),
+            FieldType::Uint32 => Ok(FieldValue::Uint32(raw.parse()?)),
+            FieldType::Int32 => Ok(FieldValue::Int32(raw.parse()?)),
+            FieldType::Uint64 => Ok(FieldValue::Uint64(raw.parse()?)),
+            FieldType::Int64 => Ok(FieldValue::Int64(raw.parse()?)),
+            FieldType::Float => Ok(FieldValue::Float(raw.parse()?)),
+            FieldType::Double => Ok(FieldValue::Double(raw.parse()?)),
+            FieldType::Char | FieldType::String => Ok(FieldValue::String(unquote(raw).to_string())),
+            FieldType::Array { element_type, size } => {
+                // char[] carries a string payload rather than a list of characters
+                if matches!(**element_type, FieldType::Char | FieldType::String) {
+                    return Ok(FieldValue::String(unquote(raw).to_string()));
+                }
+
+                let elements = unquote(raw).split(',')
+                    .map(|element| element_type.parse_value(element.trim()))
+                    .collect::<Result<Vec<_>>>()?;
+                if let Some(size) = size
+                    && elements.len() != *size
+                {
+                    return Err(anyhow!("Expected {} array elements, got {}", size, elements.len()));
+                }
+                Ok(FieldValue::Array(elements))
+            }
+        }
+    }
+}
+
+/// Strip the double quotes around a string payload, if any
+fn unquote(raw: &str) -> &str {
+    raw.strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .unwrap_or(raw)
+}
+
+/// Split the payload of a .data line into raw field tokens.
+///
+/// Tokens are separated by whitespace, except inside double quotes so that
+/// string payloads containing spaces stay a single token.
+pub fn split_fields(content: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    let mut in_quotes = false;
+
+    for (i, c) in content.char_indices() {
+        match (start, c) {
+            (_, '"') => {
+                in_quotes = !in_quotes;
+                start.get_or_insert(i);
+            }
+            (Some(s), c) if c.is_whitespace() && !in_quotes => {
+                tokens.push(&content[s..i]);
+                start = None;
+            }
+            (None, c) if !c.is_whitespace() => start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        tokens.push(&content[s..]);
+    }
+
+    tokens
+}
+
+impl FromStr for FieldType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some((element, rest)) = s.split_once('[') {
+            let size = rest.strip_suffix(']')
+                .ok_or_else(|| anyhow!("Malformed array type '{}'", s))?;
+            let size = if size.is_empty() {
+                None
+            } else {
+                Some(size.parse::<usize>().map_err(|_| anyhow!("Invalid array size in '{}'", s))?)
+            };
+            return Ok(FieldType::Array {
+                element_type: Box::new(element.parse()?),
+                size,
+            });
+        }
+
+        match s {
+            "uint8" => Ok(FieldType::Uint8),
+            "int8" => Ok(FieldType::Int8),
+            "uint16" => Ok(FieldType::Uint16),
+            "int16" => Ok(FieldType::Int16),
+            "uint32" => Ok(FieldType::Uint32),
+            "int32" => Ok(FieldType::Int32),
+            "uint64" => Ok(FieldType::Uint64),
+            "int64" => Ok(FieldType::Int64),
+            "float" => Ok(FieldType::Float),
+            "double" => Ok(FieldType::Double),
+            "char" => Ok(FieldType::Char),
+            "string" => Ok(FieldType::String),
+            other => Err(anyhow!("Unknown field type '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            FieldType::Uint8 => "uint8",
+            FieldType::Int8 => "int8",
+            FieldType::Uint16 => "uint16",
+            FieldType::Int16 => "int16",
+            FieldType::Uint32 => "uint32",
+            FieldType::Int32 => "int32",
+            FieldType::Uint64 => "uint64",
+            FieldType::Int64 => "int64",
+            FieldType::Float => "float",
+            FieldType::Double => "double",
+            FieldType::Char => "char",
+            FieldType::String => "string",
+            FieldType::Array { element_type, size: Some(size) } => return write!(f, "{}[{}]", element_type, size),
+            FieldType::Array { element_type, size: None } => return write!(f, "{}[]", element_type),
+        };
+        f.write_str(name)
+    }
+}
+
+impl FieldValue {
+    /// Numeric view of a scalar value, if it has one
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            FieldValue::Uint8(v) => Some(*v as f64),
+            FieldValue::Int8(v) => Some(*v as f64),
+            FieldValue::Uint16(v) => Some(*v as f64),
+            FieldValue::Int16(v) => Some(*v as f64),
+            FieldValue::Uint32(v) => Some(*v as f64),
+            FieldValue::Int32(v) => Some(*v as f64),
+            FieldValue::Uint64(v) => Some(*v as f64),
+            FieldValue::Int64(v) => Some(*v as f64),
+            FieldValue::Float(v) => Some(*v as f64),
+            FieldValue::Double(v) => Some(*v),
+            FieldValue::String(_) | FieldValue::Array(_) => None,
+        }
+    }
+
+    /// Plain JSON value, a number, string or array; non-finite numbers become null
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            FieldValue::String(v) => serde_json::Value::String(v.clone()),
+            FieldValue::Array(elements) => elements.iter().map(FieldValue::to_json).collect(),
+            // Through its shortest decimal form, so that 0.1f32 stays 0.1
+            FieldValue::Float(v) => v.to_string().parse().ok()
+                .and_then(serde_json::Number::from_f64)
+                .map_or(serde_json::Value::Null, serde_json::Value::Number),
+            FieldValue::Double(v) => serde_json::Number::from_f64(*v)
+                .map_or(serde_json::Value::Null, serde_json::Value::Number),
+            FieldValue::Uint64(v) => (*v).into(),
+            FieldValue::Int64(v) => (*v).into(),
+            integer => integer.as_f64().map_or(serde_json::Value::Null, |v| (v as i64).into()),
+        }
+    }
+}
+
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldValue::Uint8(v) => write!(f, "{}", v),
+            FieldValue::Int8(v) => write!(f, "{}", v),
+            FieldValue::Uint16(v) => write!(f, "{}", v),
+            FieldValue::Int16(v) => write!(f, "{}", v),
+            FieldValue::Uint32(v) => write!(f, "{}", v),
+            FieldValue::Int32(v) => write!(f, "{}", v),
+            FieldValue::Uint64(v) => write!(f, "{}", v),
+            FieldValue::Int64(v) => write!(f, "{}", v),
+            FieldValue::Float(v) => write!(f, "{}", v),
+            FieldValue::Double(v) => write!(f, "{}", v),
+            FieldValue::String(v) => f.write_str(v),
+            FieldValue::Array(elements) => {
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::fixtures::sample_log;
+
+    const PROTOCOL: &str = r#"<protocol>
+    <msg_class NAME="telemetry" ID="1">
+      <message NAME="ALIVE" ID="2">
+        <description>alive/heartbeat message</description>
+        <field TYPE="uint8[]" NAME="md5sum"></field>
+      </message>
+      <message NAME="GPS_INT" ID="155">
+        <field UNIT="1e7deg" TYPE="int32" NAME="lat" ALT_UNIT_COEF="0.0000001" ALT_UNIT="deg"></field>
+        <field UNIT="mm" TYPE="int32" NAME="alt" ALT_UNIT="m">altitude above WGS84 reference ellipsoid</field>
+        <field TYPE="uint8" NAME="fix"></field>
+      </message>
+      <message NAME="IMU_GYRO_RAW" ID="203">
+        <field TYPE="uint8" NAME="id"></field>
+        <field UNIT="adc" TYPE="int32[3]" NAME="gp"></field>
+        <field TYPE="char[]" NAME="desc"></field>
+      </message>
+    </msg_class>
+    <msg_class NAME="datalink" ID="2">
+      <message NAME="SETTING" LINK="forwarded" ID="4">
+        <field TYPE="uint8" NAME="index"></field>
+        <field TYPE="uint8" NAME="ac_id"></field>
+        <field TYPE="float" NAME="value"></field>
+      </message>
+    </msg_class>
+  </protocol>"#;
+
+    #[test]
+    fn test_schema_hash() {
+        let protocol = MessageDictionary::from_xml(PROTOCOL).unwrap();
+        assert_eq!(protocol.schema_hash().len(), 64);
+
+        // Same definitions, reordered, reformatted and without descriptions
+        let reformatted = PROTOCOL
+            .replace("<description>alive/heartbeat message</description>", "")
+            .replace(r#"<field TYPE="uint8" NAME="fix"></field>"#, r#"<field NAME="fix" TYPE="uint8"/>"#);
+        let reordered = MessageDictionary::new(protocol.messages.iter().rev().cloned().collect());
+        assert_eq!(MessageDictionary::from_xml(&reformatted).unwrap().schema_hash(), protocol.schema_hash());
+        assert_eq!(reordered.schema_hash(), protocol.schema_hash());
+
+        let changed = MessageDictionary::from_xml(&PROTOCOL.replace("int32[3]", "int16[3]")).unwrap();
+        assert_ne!(changed.schema_hash(), protocol.schema_hash());
+        assert!(changed.canonical().contains("  gp int16[3] unit=adc"));
+    }
+
+    #[test]
+    fn test_parse_protocol() {
+        let dictionary = MessageDictionary::from_xml(PROTOCOL).unwrap();
+        assert_eq!(dictionary.len(), 4);
+
+        let gps = dictionary.get("GPS_INT").unwrap();
+        assert_eq!(gps.id, 155);
+        assert_eq!(gps.class, MessageClass::Telemetry);
+        assert_eq!(gps.fields[0].name, "lat");
+        assert_eq!(gps.fields[0].field_type, FieldType::Int32);
+        assert_eq!(gps.fields[0].unit.as_deref(), Some("1e7deg"));
+        assert_eq!(gps.fields[0].alt_unit.as_deref(), Some("deg"));
+        assert_eq!(gps.fields[0].alt_unit_coef, Some(0.0000001));
+
+        let setting = dictionary.get_by_id(MessageClass::Datalink, 4).unwrap();
+        assert_eq!(setting.name, "SETTING");
+        assert_eq!(setting.class_id, 2);
+        assert!(setting.class.is_uplink());
+
+        let extended = PROTOCOL.replace("</protocol>",
+            r#"<msg_class NAME="vendor" ID="9"><message NAME="VENDOR" ID="1"/></msg_class></protocol>"#);
+        let dictionary = MessageDictionary::from_xml(&extended).unwrap();
+        assert_eq!(dictionary.len(), 4);
+        assert!(dictionary.get("VENDOR").is_none());
+
+        // pprzlink v1 messages.xml names its classes <class>
+        let v1 = PROTOCOL.replace("<msg_class", "<class").replace("</msg_class>", "</class>");
+        let dictionary = MessageDictionary::from_xml(&v1).unwrap();
+        assert_eq!(dictionary.len(), 4);
+        assert_eq!(dictionary.get("GPS_INT").unwrap().class, MessageClass::Telemetry);
+    }
+
+    #[test]
+    fn test_decode_fields() {
+        let dictionary = MessageDictionary::from_xml(PROTOCOL).unwrap();
+        let gps = dictionary.get("GPS_INT").unwrap();
+
+        let fields = gps.decode_fields(&["435639416", "194120", "3"]).unwrap();
+        assert_eq!(fields["lat"], FieldValue::Int32(435639416));
+        assert_eq!(fields["alt"], FieldValue::Int32(194120));
+        assert_eq!(fields["fix"], FieldValue::Uint8(3));
+
+        assert!(gps.decode_fields(&["1", "2"]).is_err());
+        assert!(gps.decode_fields(&["1", "2", "300"]).is_err());
+    }
+
+    #[test]
+    fn test_decode_arrays_and_strings() {
+        let dictionary = MessageDictionary::from_xml(PROTOCOL).unwrap();
+
+        let alive = dictionary.get("ALIVE").unwrap();
+        let fields = alive.decode_fields(&["1,2,255"]).unwrap();
+        assert_eq!(fields["md5sum"], FieldValue::Array(vec![
+            FieldValue::Uint8(1), FieldValue::Uint8(2), FieldValue::Uint8(255),
+        ]));
+
+        let gyro = dictionary.get("IMU_GYRO_RAW").unwrap();
+        let tokens = split_fields(r#"2 -12,40,7 "gyro board v2""#);
+        let fields = gyro.decode_fields(&tokens).unwrap();
+        assert_eq!(fields["desc"], FieldValue::String("gyro board v2".to_string()));
+
+        let channels = gyro.channels(&fields);
+        let names: Vec<_> = channels.iter().map(|(name, _, _)| name.as_str()).collect();
+        assert_eq!(names, ["id", "gp[0]", "gp[1]", "gp[2]", "desc"]);
+
+        let channel: ChannelRef = "IMU_GYRO_RAW.gp[1]".parse().unwrap();
+        assert_eq!(channel.resolve(&fields), Some(&FieldValue::Int32(40)));
+
+        // Fixed-size arrays must have exactly the declared number of elements
+        assert!(gyro.decode_fields(&["2", "-12,40", "x"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_sample_log_protocol() {
+        let dictionary = MessageDictionary::from_log_content(&sample_log()).unwrap();
+
+        assert_eq!(dictionary.len(), 365);
+        let fp = dictionary.get("ROTORCRAFT_FP").unwrap();
+        assert!(fp.fields.iter().any(|f| f.name == "vup" && f.field_type == FieldType::Int32));
+    }
+}
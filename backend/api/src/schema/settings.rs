@@ -0,0 +1,337 @@
+//! Datalink settings (`<dl_settings>`) embedded in the .log and the changes made to them in flight
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use super::{FieldValue, TelemetryMessage};
+use super::xml::{attr, children};
+
+/// Settings of one aircraft, in the order the firmware indexes them
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SettingsTable {
+    pub settings: Vec<Setting>,
+}
+
+/// A `<dl_setting>`; `index` is the number used by `DL_VALUE`, `SETTING` and `DL_SETTING`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Setting {
+    pub index: u16,
+    /// C variable set by the setting (`ahrs_icq.accel_omega`)
+    pub var: String,
+    pub shortname: Option<String>,
+    pub module: Option<String>,
+    /// Names of the enclosing `<dl_settings>` groups, outermost first
+    pub group: Vec<String>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub step: Option<f64>,
+    pub unit: Option<String>,
+    pub alt_unit: Option<String>,
+    /// Labels of an enumerated setting, for values `min`, `min + 1`, ...
+    pub values: Vec<String>,
+    /// Airframe define the setting is initialised from
+    pub param: Option<String>,
+    pub handler: Option<String>,
+}
+
+impl SettingsTable {
+    /// Index every `<dl_setting>` below a `<settings>` element, depth first in document order
+    pub fn from_node(node: roxmltree::Node) -> Self {
+        let mut table = Self::default();
+        let mut group = Vec::new();
+        for dl_settings in children(node, "dl_settings") {
+            table.collect(dl_settings, &mut group);
+        }
+        table
+    }
+
+    fn collect(&mut self, node: roxmltree::Node, group: &mut Vec<String>) {
+        let name = attr(node, "NAME");
+        if let Some(name) = &name {
+            group.push(name.clone());
+        }
+
+        for element in node.children().filter(|n| n.is_element()) {
+            match element.tag_name().name() {
+                "dl_settings" => self.collect(element, group),
+                "dl_setting" => {
+                    // Past 65535 a setting cannot be addressed by any pprzlink message
+                    let Ok(index) = u16::try_from(self.settings.len()) else {
+                        continue;
+                    };
+                    self.settings.push(Setting {
+                        index,
+                        var: attr(element, "VAR").unwrap_or_default(),
+                        shortname: attr(element, "SHORTNAME"),
+                        module: attr(element, "MODULE"),
+                        group: group.clone(),
+                        min: attr(element, "MIN").and_then(|v| parse_number(&v)),
+                        max: attr(element, "MAX").and_then(|v| parse_number(&v)),
+                        step: attr(element, "STEP").and_then(|v| parse_number(&v)),
+                        unit: attr(element, "UNIT"),
+                        alt_unit: attr(element, "ALT_UNIT"),
+                        values: attr(element, "VALUES")
+                            .map(|values| values.split('|').map(str::to_string).collect())
+                            .unwrap_or_default(),
+                        param: attr(element, "PARAM"),
+                        handler: attr(element, "HANDLER"),
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        if name.is_some() {
+            group.pop();
+        }
+    }
+
+    pub fn get(&self, index: u16) -> Option<&Setting> {
+        self.settings.get(index as usize)
+    }
+
+    pub fn len(&self) -> usize {
+        self.settings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.settings.is_empty()
+    }
+}
+
+impl Setting {
+    /// Display name: the short name when there is one, the variable otherwise
+    pub fn name(&self) -> &str {
+        self.shortname.as_deref().unwrap_or(&self.var)
+    }
+
+    /// Label of an enumerated value (`NAV` for `autopilot_mode_ap = 2`)
+    pub fn label(&self, value: f64) -> Option<&str> {
+        let offset = value - self.min.unwrap_or(0.0);
+        if offset < 0.0 || offset.fract() != 0.0 {
+            return None;
+        }
+        self.values.get(offset as usize).map(String::as_str)
+    }
+}
+
+/// A change of a setting value during the flight
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingChange {
+    pub timestamp: f64,
+    /// Aircraft whose setting changed
+    pub sender_id: u8,
+    pub index: u16,
+    pub setting: Option<String>,
+    pub module: Option<String>,
+    /// Last known value, `None` when the setting had not been reported yet
+    pub old_value: Option<f64>,
+    pub new_value: f64,
+    pub new_label: Option<String>,
+    pub unit: Option<String>,
+    /// Message the change was seen in (`DL_VALUE`, `SETTING`, `DL_SETTING`)
+    pub source: String,
+}
+
+/// Incremental setting change detection over a stream of telemetry messages.
+///
+/// `DL_VALUE` reports the current value of each setting periodically: the first
+/// report of a setting is its initial value, later reports that differ are changes.
+/// `SETTING` and `DL_SETTING` are the commands sent to an aircraft and are always
+/// recorded, so a change is listed even when no `DL_VALUE` confirms it.
+#[derive(Debug)]
+pub struct SettingChangeTracker<'a> {
+    settings: &'a BTreeMap<u8, SettingsTable>,
+    values: HashMap<(u8, u16), f64>,
+    changes: Vec<SettingChange>,
+}
+
+impl<'a> SettingChangeTracker<'a> {
+    pub fn new(settings: &'a BTreeMap<u8, SettingsTable>) -> Self {
+        Self {
+            settings,
+            values: HashMap::new(),
+            changes: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, message: &TelemetryMessage) {
+        let field = |name: &str| message.fields.get(name);
+        let (aircraft, commanded) = match message.message_name.as_str() {
+            "DL_VALUE" => (Some(message.sender_id), false),
+            "SETTING" | "DL_SETTING" => (field("ac_id").and_then(ac_id), true),
+            _ => return,
+        };
+        let (Some(aircraft), Some(index), Some(value)) = (
+            aircraft,
+            field("index").and_then(FieldValue::as_f64).and_then(|i| u16::try_from(i as i64).ok()),
+            field("value").and_then(FieldValue::as_f64),
+        ) else {
+            return;
+        };
+
+        let old_value = self.values.insert((aircraft, index), value);
+        if !commanded && old_value.is_none_or(|old| old == value) {
+            return;
+        }
+
+        let setting = self.settings.get(&aircraft).and_then(|table| table.get(index));
+        self.changes.push(SettingChange {
+            timestamp: message.timestamp,
+            sender_id: aircraft,
+            index,
+            setting: setting.map(|s| s.name().to_string()),
+            module: setting.and_then(|s| s.module.clone()),
+            old_value,
+            new_value: value,
+            new_label: setting.and_then(|s| s.label(value)).map(str::to_string),
+            unit: setting.and_then(|s| s.unit.clone()),
+            source: message.message_name.clone(),
+        });
+    }
+
+    pub fn finish(self) -> Vec<SettingChange> {
+        self.changes
+    }
+}
+
+/// `ac_id` field of an uplink message; `DL_SETTING` sends it as a string
+fn ac_id(value: &FieldValue) -> Option<u8> {
+    match value {
+        FieldValue::String(s) => s.trim().parse().ok(),
+        other => other.as_f64().and_then(|v| u8::try_from(v as i64).ok()),
+    }
+}
+
+fn parse_number(value: &str) -> Option<f64> {
+    value.trim().parse::<f64>().ok().filter(|v| v.is_finite())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::fixtures::sample_protocol;
+    use crate::schema::parse_telemetry_line;
+
+    const SETTINGS: &str = r#"<settings>
+        <dl_settings>
+          <dl_settings name="System">
+            <dl_setting min="0" step="1" max="13" var="telemetry_mode_Main" shortname="Main" values="default|ppm"/>
+            <dl_settings name="Autopilot">
+              <dl_setting min="0" max="6" step="1" var="autopilot_mode_ap" shortname="ap" values="ATTITUDE_DIRECT|ATTITUDE_Z_HOLD|NAV"/>
+            </dl_settings>
+          </dl_settings>
+          <dl_settings NAME="AHRS ICQ">
+            <dl_setting VAR="ahrs_icq.accel_omega" UNIT="rad/s" STEP="0.02" SHORTNAME="acc_omega" MODULE="modules/ahrs/ahrs_int_cmpl_quat" MIN="0.02" MAX="0.2"/>
+          </dl_settings>
+        </dl_settings>
+      </settings>"#;
+
+    #[test]
+    fn test_setting_changes() {
+        let document = roxmltree::Document::parse(SETTINGS).unwrap();
+        let table = SettingsTable::from_node(document.root_element());
+        assert_eq!(table.len(), 3);
+        assert_eq!(table.get(1).unwrap().group, ["System", "Autopilot"]);
+        assert_eq!(table.get(1).unwrap().label(2.0), Some("NAV"));
+        assert_eq!(table.get(2).unwrap().module.as_deref(), Some("modules/ahrs/ahrs_int_cmpl_quat"));
+
+        let protocol = sample_protocol();
+        let settings = BTreeMap::from([(7, table)]);
+        let mut tracker = SettingChangeTracker::new(&settings);
+        for line in [
+            "1.0 7 DL_VALUE 1 0",
+            "2.0 7 DL_VALUE 1 0",
+            "3.0 0 SETTING 2 7 0.1",
+            "4.0 7 DL_VALUE 2 0.1",
+            "5.0 7 DL_VALUE 1 2",
+        ] {
+            tracker.add(&parse_telemetry_line(line, &protocol).unwrap());
+        }
+        let changes = tracker.finish();
+
+        assert_eq!(changes.len(), 2);
+        assert_eq!((changes[0].sender_id, changes[0].setting.as_deref()), (7, Some("acc_omega")));
+        assert_eq!((changes[0].old_value, changes[0].source.as_str()), (None, "SETTING"));
+        assert_eq!((changes[1].old_value, changes[1].new_value), (Some(0.0), 2.0));
+        assert_eq!(changes[1].new_label.as_deref(), Some("NAV"));
+
+        // Large settings files go past the 255 a byte could index
+        let many = format!("<settings><dl_settings>{}</dl_settings></settings>",
+            (0..300).map(|i| format!(r#"<dl_setting var="v{}" min="0" max="1"/>"#, i)).collect::<String>());
+        let document = roxmltree::Document::parse(&many).unwrap();
+        let settings = BTreeMap::from([(7, SettingsTable::from_node(document.root_element()))]);
+        assert_eq!(settings[&7].len(), 300);
+        let mut tracker = SettingChangeTracker::new(&settings);
+        tracker.add(&TelemetryMessage {
+            timestamp: 1.0,
+            sender_id: 0,
+            message_id: 4,
+            msg_class: crate::schema::MessageClass::Datalink,
+            message_name: "SETTING".to_string(),
+            fields: [
+                ("index".to_string(), FieldValue::Uint16(299)),
+                ("ac_id".to_string(), FieldValue::Uint8(7)),
+                ("value".to_string(), FieldValue::Float(1.0)),
+            ].into(),
+        });
+        let changes = tracker.finish();
+        assert_eq!((changes[0].index, changes[0].setting.as_deref()), (299, Some("v299")));
+    }
+
+    fn uplink(name: &str, fields: &[(&str, FieldValue)]) -> TelemetryMessage {
+        TelemetryMessage {
+            timestamp: 1.0,
+            sender_id: 0,
+            message_id: 4,
+            msg_class: crate::schema::MessageClass::Datalink,
+            message_name: name.to_string(),
+            fields: fields.iter().map(|(name, value)| (name.to_string(), value.clone())).collect(),
+        }
+    }
+
+    #[test]
+    fn test_setting_failures() {
+        let document = roxmltree::Document::parse(SETTINGS).unwrap();
+        let table = SettingsTable::from_node(document.root_element());
+
+        // Unparseable or infinite bounds are dropped, not read as 0
+        let document = roxmltree::Document::parse(
+            r#"<settings><dl_settings><dl_setting var="x" min="low" max="inf" step=""/></dl_settings></settings>"#).unwrap();
+        let bounds = SettingsTable::from_node(document.root_element());
+        let x = bounds.get(0).unwrap();
+        assert_eq!((x.min, x.max, x.step), (None, None, None));
+        assert!(bounds.get(1).is_none());
+
+        // Values outside the enumeration, below its minimum or between two labels have no label
+        let ap = table.get(1).unwrap();
+        assert_eq!(ap.label(3.0), None);
+        assert_eq!(ap.label(-1.0), None);
+        assert_eq!(ap.label(1.5), None);
+
+        let settings = BTreeMap::from([(7, table)]);
+        let mut tracker = SettingChangeTracker::new(&settings);
+        for message in [
+            // Not a setting message
+            uplink("PING", &[]),
+            // ac_id that is not a number, or out of the u8 range
+            uplink("DL_SETTING", &[("ac_id", FieldValue::String("seven".to_string())), ("index", FieldValue::Uint8(0)), ("value", FieldValue::Float(1.0))]),
+            uplink("SETTING", &[("ac_id", FieldValue::Int16(300)), ("index", FieldValue::Uint8(0)), ("value", FieldValue::Float(1.0))]),
+            // Negative index, missing value
+            uplink("SETTING", &[("ac_id", FieldValue::Uint8(7)), ("index", FieldValue::Int16(-1)), ("value", FieldValue::Float(1.0))]),
+            uplink("SETTING", &[("ac_id", FieldValue::Uint8(7)), ("index", FieldValue::Uint8(0))]),
+        ] {
+            tracker.add(&message);
+        }
+        assert!(tracker.finish().is_empty());
+
+        // Commands to an unknown aircraft or setting are still recorded, without a name
+        let mut tracker = SettingChangeTracker::new(&settings);
+        tracker.add(&uplink("DL_SETTING", &[("ac_id", FieldValue::String(" 9 ".to_string())), ("index", FieldValue::Uint8(0)), ("value", FieldValue::Float(1.0))]));
+        tracker.add(&uplink("SETTING", &[("ac_id", FieldValue::Uint8(7)), ("index", FieldValue::Uint8(42)), ("value", FieldValue::Float(1.0))]));
+        let changes = tracker.finish();
+        assert_eq!(changes.len(), 2);
+        assert_eq!((changes[0].sender_id, changes[0].setting.as_deref()), (9, None));
+        assert_eq!((changes[1].index, changes[1].setting.as_deref(), changes[1].unit.as_deref()), (42, None, None));
+    }
+}
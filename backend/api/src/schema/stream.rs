@@ -0,0 +1,415 @@
+//! Streaming .data reader decoding telemetry lines incrementally with bounded memory
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::sync::{mpsc, watch};
+use tokio_stream::{Stream, StreamExt};
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::{debug, info};
+
+use super::{BinaryTelemetryReader, DataFormat, Diagnostics, MessageDictionary, TelemetryMessage, split_fields};
+
+/// Decoded messages buffered between the reader thread and a `TelemetryStream` consumer
+const STREAM_BUFFER: usize = 1024;
+
+/// How many lines are read between two progress updates of a `TelemetryStream`
+const PROGRESS_INTERVAL: usize = 4096;
+
+/// Progress of a .data reader, in bytes of the underlying file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DataProgress {
+    pub format: DataFormat,
+    pub bytes_read: u64,
+    pub total_bytes: Option<u64>,
+    /// Lines of a text file, frames with a valid checksum of a binary capture
+    pub lines_read: usize,
+    pub messages_decoded: usize,
+    pub lines_skipped: usize,
+    /// Binary frames dropped because their checksum did not match
+    pub checksum_errors: usize,
+    /// Times the binary decoder skipped bytes to find the start of the next frame
+    pub resyncs: usize,
+    /// Lines the message dictionary could not account for
+    pub mismatches: DictionaryMismatches,
+    /// Every line or frame that was skipped or looked wrong, with its position
+    pub diagnostics: Diagnostics,
+    pub finished: bool,
+}
+
+/// Messages of a .data file that do not match the dictionary used to decode it
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DictionaryMismatches {
+    /// Lines per message name missing from the dictionary
+    pub unknown_messages: BTreeMap<String, usize>,
+    /// Messages whose lines do not have the number of fields the dictionary declares
+    pub field_counts: BTreeMap<String, FieldCountMismatch>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FieldCountMismatch {
+    pub expected: usize,
+    pub found: BTreeSet<usize>,
+    pub lines: usize,
+}
+
+impl DictionaryMismatches {
+    pub fn is_empty(&self) -> bool {
+        self.unknown_messages.is_empty() && self.field_counts.is_empty()
+    }
+
+    /// Record why a line failed to decode, if it is a dictionary mismatch
+    fn record(&mut self, line: &str, protocol: &MessageDictionary) {
+        let parts = split_fields(line.trim_start_matches('(').trim_end_matches(')'));
+        let Some(&name) = parts.get(2) else {
+            return;
+        };
+        match protocol.get(name) {
+            None => *self.unknown_messages.entry(name.to_string()).or_insert(0) += 1,
+            Some(definition) if definition.fields.len() != parts.len() - 3 => {
+                let mismatch = self.field_counts.entry(name.to_string()).or_default();
+                mismatch.expected = definition.fields.len();
+                mismatch.found.insert(parts.len() - 3);
+                mismatch.lines += 1;
+            }
+            Some(_) => {}
+        }
+    }
+}
+
+impl DataProgress {
+    /// Fraction of the file read so far, when its size is known
+    pub fn fraction(&self) -> Option<f64> {
+        match self.total_bytes {
+            Some(0) => Some(1.0),
+            Some(total) => Some((self.bytes_read as f64 / total as f64).min(1.0)),
+            None => None,
+        }
+    }
+}
+
+/// Iterator over the decoded messages of a .data file.
+///
+/// Lines are read one at a time into a reused buffer, so memory stays bounded
+/// by the longest line regardless of the file size. Lines that fail to decode
+/// are skipped and counted in the progress; I/O errors end the iteration.
+pub struct TelemetryReader<'a, R> {
+    reader: R,
+    protocol: &'a MessageDictionary,
+    buffer: Vec<u8>,
+    last_timestamp: Option<f64>,
+    progress: DataProgress,
+}
+
+impl<'a> TelemetryReader<'a, BufReader<File>> {
+    /// Open a .data file for streaming
+    pub fn open(data_path: &Path, protocol: &'a MessageDictionary) -> Result<Self> {
+        let file = File::open(data_path)
+            .map_err(|e| anyhow!("Failed to open data file {:?}: {}", data_path, e))?;
+        let total_bytes = file.metadata().ok().map(|m| m.len());
+
+        Ok(Self::new(BufReader::new(file), protocol, total_bytes))
+    }
+}
+
+impl<'a, R: BufRead> TelemetryReader<'a, R> {
+    pub fn new(reader: R, protocol: &'a MessageDictionary, total_bytes: Option<u64>) -> Self {
+        Self {
+            reader,
+            protocol,
+            buffer: Vec::new(),
+            last_timestamp: None,
+            progress: DataProgress {
+                total_bytes,
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn progress(&self) -> &DataProgress {
+        &self.progress
+    }
+}
+
+impl<R: BufRead> Iterator for TelemetryReader<'_, R> {
+    type Item = Result<TelemetryMessage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.buffer.clear();
+            let read = match self.reader.read_until(b'\n', &mut self.buffer) {
+                Ok(read) => read,
+                Err(e) => {
+                    self.progress.finished = true;
+                    return Some(Err(anyhow!("Failed to read data file: {}", e)));
+                }
+            };
+            if read == 0 {
+                self.progress.finished = true;
+                return None;
+            }
+
+            let byte_offset = self.progress.bytes_read;
+            self.progress.bytes_read += read as u64;
+            self.progress.lines_read += 1;
+            let line_number = self.progress.lines_read;
+
+            let line = String::from_utf8_lossy(&self.buffer);
+            let complete = line.ends_with('\n');
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            match parse_telemetry_line(line, self.protocol) {
+                Ok(message) => {
+                    if let Some(previous) = self.last_timestamp
+                        && message.timestamp < previous
+                    {
+                        self.progress.diagnostics.record_time_jump(line_number, byte_offset, line, previous, message.timestamp);
+                    }
+                    self.last_timestamp = Some(message.timestamp);
+                    self.progress.messages_decoded += 1;
+                    return Some(Ok(message));
+                }
+                Err(e) => {
+                    // Log and continue with the next line
+                    self.progress.lines_skipped += 1;
+                    self.progress.mismatches.record(line, self.protocol);
+                    self.progress.diagnostics.record_line(line_number, byte_offset, line, complete, self.protocol);
+                    debug!("Failed to parse line {}: {} ({})", line_number, e, line);
+                }
+            }
+        }
+    }
+}
+
+/// Decoders producing telemetry messages out of a capture, text or binary
+pub trait MessageReader: Iterator<Item = Result<TelemetryMessage>> {
+    fn progress(&self) -> &DataProgress;
+}
+
+impl<R: BufRead> MessageReader for TelemetryReader<'_, R> {
+    fn progress(&self) -> &DataProgress {
+        &self.progress
+    }
+}
+
+impl<R: std::io::Read> MessageReader for BinaryTelemetryReader<'_, R> {
+    fn progress(&self) -> &DataProgress {
+        self.progress()
+    }
+}
+
+/// Async stream of decoded messages read from a .data file on a blocking thread.
+///
+/// The file may be a text .data file or a binary pprzlink capture, told apart by
+/// its first bytes. The channel between the reader and the consumer is bounded,
+/// so a slow consumer pauses the reader instead of buffering the whole file.
+pub struct TelemetryStream {
+    messages: ReceiverStream<Result<TelemetryMessage>>,
+    progress: watch::Receiver<DataProgress>,
+}
+
+impl TelemetryStream {
+    /// Start streaming a .data file, decoding it with the given dictionary
+    pub fn open(data_path: &Path, protocol: MessageDictionary) -> Result<Self> {
+        let file = File::open(data_path)
+            .map_err(|e| anyhow!("Failed to open data file {:?}: {}", data_path, e))?;
+        let total_bytes = file.metadata().ok().map(|m| m.len());
+        let data_path = data_path.to_path_buf();
+
+        let (sender, receiver) = mpsc::channel(STREAM_BUFFER);
+        let (progress_sender, progress) = watch::channel(DataProgress {
+            total_bytes,
+            ..Default::default()
+        });
+
+        tokio::task::spawn_blocking(move || {
+            let mut file = file;
+            let format = match DataFormat::detect_file(&mut file, &protocol) {
+                Ok(format) => format,
+                Err(e) => {
+                    let _ = sender.blocking_send(Err(anyhow!("Failed to read data file {:?}: {}", data_path, e)));
+                    return;
+                }
+            };
+            let file = BufReader::new(file);
+
+            if format == DataFormat::Text {
+                forward(TelemetryReader::new(file, &protocol, total_bytes), &sender, &progress_sender);
+            } else if let Some(reader) = format.binary_reader(file, &protocol, total_bytes) {
+                forward(reader, &sender, &progress_sender);
+            }
+            let done = progress_sender.borrow().clone();
+            info!("Streamed {} telemetry messages ({} skipped, {:?}) from {:?}",
+                done.messages_decoded, done.lines_skipped, done.format, data_path);
+        });
+
+        Ok(Self {
+            messages: ReceiverStream::new(receiver),
+            progress,
+        })
+    }
+
+    /// Latest progress reported by the reader
+    pub fn progress(&self) -> DataProgress {
+        self.progress.borrow().clone()
+    }
+
+    /// Watch handle for observing progress from another task
+    pub fn subscribe_progress(&self) -> watch::Receiver<DataProgress> {
+        self.progress.clone()
+    }
+
+    /// Restrict the stream to the messages of a single aircraft; read errors are kept
+    pub fn from_sender(self, sender_id: u8) -> impl Stream<Item = Result<TelemetryMessage>> {
+        self.filter(move |message| match message {
+            Ok(message) => message.sender_id == sender_id,
+            Err(_) => true,
+        })
+    }
+}
+
+impl Stream for TelemetryStream {
+    type Item = Result<TelemetryMessage>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.messages).poll_next(cx)
+    }
+}
+
+/// Send the messages of a reader to a `TelemetryStream`, publishing progress along the way
+fn forward<R: MessageReader>(
+    mut reader: R,
+    sender: &mpsc::Sender<Result<TelemetryMessage>>,
+    progress_sender: &watch::Sender<DataProgress>,
+) {
+    let mut last_update = 0;
+    while let Some(item) = reader.next() {
+        if sender.blocking_send(item).is_err() {
+            // Consumer went away
+            return;
+        }
+        if reader.progress().lines_read - last_update >= PROGRESS_INTERVAL {
+            last_update = reader.progress().lines_read;
+            progress_sender.send_replace(reader.progress().clone());
+        }
+    }
+    progress_sender.send_replace(reader.progress().clone());
+}
+
+/// Parse a single telemetry message line, decoding its fields with the message dictionary
+pub fn parse_telemetry_line(line: &str, protocol: &MessageDictionary) -> Result<TelemetryMessage> {
+    // PaparazziUAV telemetry format: (timestamp sender_id MESSAGE_NAME field1 field2 ...)
+    let trimmed = line.trim();
+
+    // Remove parentheses if present
+    let content = if trimmed.starts_with('(') && trimmed.ends_with(')') {
+        &trimmed[1..trimmed.len()-1]
+    } else {
+        trimmed
+    };
+
+    let parts = split_fields(content);
+
+    if parts.len() < 3 {
+        return Err(anyhow!("Invalid telemetry line format: too few fields"));
+    }
+
+    let timestamp = parts[0].parse::<f64>()
+        .map_err(|_| anyhow!("Invalid timestamp format"))?;
+
+    let sender_id = parts[1].parse::<u8>()
+        .map_err(|_| anyhow!("Invalid sender_id format"))?;
+
+    // Message name is the third field (string, not numeric ID)
+    let message_name = parts[2].to_string();
+
+    // Resolve the protocol ID and class from the message dictionary
+    let definition = protocol.get(&message_name)
+        .ok_or_else(|| anyhow!("Unknown message: {}", message_name))?;
+    let fields = definition.decode_fields(&parts[3..])?;
+
+    Ok(TelemetryMessage {
+        timestamp,
+        sender_id,
+        message_id: definition.id,
+        msg_class: definition.class,
+        message_name,
+        fields,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::FieldValue;
+    use crate::schema::fixtures::sample_protocol;
+    use std::io::Cursor;
+    use tokio_stream::StreamExt;
+
+    const DATA: &str = "\
+1.000 38 ROTORCRAFT_FP 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15
+1.250 38 NOT_A_MESSAGE 1 2
+
+2.500 38 ALIVE 1,2,3
+# comment
+3.000 38 ROTORCRAFT_FP 1 2 3 4 5 6 7 8 9 10 11 12 13 14 16
+";
+
+    #[test]
+    fn test_reader_streams_lines() {
+        let protocol = sample_protocol();
+        let mut reader = TelemetryReader::new(Cursor::new(DATA), &protocol, Some(DATA.len() as u64));
+
+        let first = reader.next().unwrap().unwrap();
+        assert_eq!(first.message_name, "ROTORCRAFT_FP");
+        assert_eq!(reader.progress().lines_read, 1);
+        assert!(reader.progress().fraction().unwrap() < 0.5);
+
+        let rest: Vec<_> = reader.by_ref().collect::<Result<_>>().unwrap();
+        assert_eq!(rest.len(), 2);
+        assert_eq!(rest[0].fields["md5sum"], FieldValue::Array(vec![
+            FieldValue::Uint8(1), FieldValue::Uint8(2), FieldValue::Uint8(3),
+        ]));
+
+        let progress = reader.progress();
+        assert!(progress.finished);
+        assert_eq!(progress.bytes_read, DATA.len() as u64);
+        assert_eq!(progress.messages_decoded, 3);
+        assert_eq!(progress.lines_skipped, 1);
+        assert_eq!(progress.mismatches.unknown_messages["NOT_A_MESSAGE"], 1);
+        assert!(progress.mismatches.field_counts.is_empty());
+        assert_eq!(progress.fraction(), Some(1.0));
+    }
+
+    #[tokio::test]
+    async fn test_stream_matches_reader() {
+        let data_path = std::env::temp_dir().join(format!("ppz-stream-{}.data", uuid::Uuid::new_v4()));
+        std::fs::write(&data_path, DATA).unwrap();
+
+        let protocol = sample_protocol();
+        let expected: Vec<_> = TelemetryReader::open(&data_path, &protocol).unwrap()
+            .collect::<Result<_>>().unwrap();
+
+        let mut stream = TelemetryStream::open(&data_path, protocol).unwrap();
+        let mut streamed = Vec::new();
+        while let Some(message) = stream.next().await {
+            streamed.push(message.unwrap());
+        }
+        std::fs::remove_file(&data_path).unwrap();
+
+        assert_eq!(streamed.len(), expected.len());
+        for (a, b) in streamed.iter().zip(&expected) {
+            assert_eq!(a.timestamp, b.timestamp);
+            assert_eq!(a.fields, b.fields);
+        }
+        assert_eq!(stream.progress().bytes_read, DATA.len() as u64);
+    }
+}
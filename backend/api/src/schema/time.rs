@@ -0,0 +1,341 @@
+//! Absolute UTC time of telemetry messages, anchored on the log start time or GPS time
+
+use anyhow::Result;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio_stream::StreamExt;
+use tracing::warn;
+
+use super::{FieldValue, LogConfiguration, TelemetryMessage, TelemetryStream};
+
+/// Unix time of the GPS epoch, 1980-01-06 00:00:00 UTC
+const GPS_EPOCH_UNIX: f64 = 315_964_800.0;
+
+const SECONDS_PER_WEEK: f64 = 604_800.0;
+
+/// GPS anchors further than this from `time_of_day` are considered bogus (wrong week, bad fix)
+const MAX_GPS_OFFSET: f64 = 86_400.0;
+
+/// UTC dates (unix time) from which GPS time is ahead of UTC by one more leap second
+const LEAP_SECONDS: [i64; 18] = [
+    362_793_600,   // 1981-07-01
+    394_329_600,   // 1982-07-01
+    425_865_600,   // 1983-07-01
+    489_024_000,   // 1985-07-01
+    567_993_600,   // 1988-01-01
+    631_152_000,   // 1990-01-01
+    662_688_000,   // 1991-01-01
+    709_948_800,   // 1992-07-01
+    741_484_800,   // 1993-07-01
+    773_020_800,   // 1994-07-01
+    820_454_400,   // 1996-01-01
+    867_715_200,   // 1997-07-01
+    915_148_800,   // 1999-01-01
+    1_136_073_600, // 2006-01-01
+    1_230_768_000, // 2009-01-01
+    1_341_100_800, // 2012-07-01
+    1_435_708_800, // 2015-07-01
+    1_483_228_800, // 2017-01-01
+];
+
+/// Where the UTC time of the start of a log comes from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeSource {
+    /// Ground station clock when the log was started (`time_of_day`)
+    #[default]
+    TimeOfDay,
+    /// Ground station local time in the file name, UTC offset unknown (no .log)
+    LocalFilename,
+    /// GPS time of week reported by the aircraft
+    Gps,
+}
+
+impl TimeSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TimeSource::TimeOfDay => "time_of_day",
+            TimeSource::LocalFilename => "local_filename",
+            TimeSource::Gps => "gps",
+        }
+    }
+}
+
+impl std::str::FromStr for TimeSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "time_of_day" => Ok(TimeSource::TimeOfDay),
+            "local_filename" => Ok(TimeSource::LocalFilename),
+            "gps" => Ok(TimeSource::Gps),
+            other => Err(anyhow::anyhow!("Unknown time source: {}", other)),
+        }
+    }
+}
+
+/// UTC time of `timestamp = 0` in a log, used to turn message timestamps into wall-clock time
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TimeAnchor {
+    pub source: TimeSource,
+    /// Unix time of the start of the log, unknown when only the local time of the file name is
+    pub start: Option<f64>,
+    /// Ground station local time of the start of the log from the file name, UTC offset unknown
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_start: Option<NaiveDateTime>,
+    /// `start - time_of_day`: how far the ground station clock was off
+    pub offset: f64,
+    /// Number of GPS fixes the anchor was estimated from
+    pub samples: usize,
+}
+
+impl TimeAnchor {
+    /// Anchor on the log's `time_of_day` alone
+    pub fn from_time_of_day(time_of_day: f64) -> Self {
+        Self {
+            source: TimeSource::TimeOfDay,
+            start: Some(time_of_day),
+            local_start: None,
+            offset: 0.0,
+            samples: 0,
+        }
+    }
+
+    /// Anchor on the local time of a file name alone, which gives no UTC time
+    pub fn from_local_start(local_start: NaiveDateTime) -> Self {
+        Self {
+            source: TimeSource::LocalFilename,
+            start: None,
+            local_start: Some(local_start),
+            offset: 0.0,
+            samples: 0,
+        }
+    }
+
+    /// Anchor of a configuration before its .data file is read
+    pub fn from_configuration(configuration: &LogConfiguration) -> Self {
+        match configuration.local_start {
+            Some(local_start) => Self::from_local_start(local_start),
+            None => Self::from_time_of_day(configuration.time_of_day),
+        }
+    }
+
+    /// Estimate the anchor over a whole telemetry stream
+    pub async fn from_stream(stream: &mut TelemetryStream, time_of_day: f64, source: TimeSource) -> Result<Self> {
+        let mut builder = TimeAnchorBuilder::new(time_of_day, source);
+        while let Some(message) = stream.next().await {
+            builder.add(&message?);
+        }
+        Ok(builder.finish())
+    }
+
+    /// Unix time of a message timestamp, if the UTC start of the log is known
+    pub fn unix(&self, timestamp: f64) -> Option<f64> {
+        self.start.map(|start| start + timestamp)
+    }
+
+    /// UTC time of a message timestamp, if the UTC start of the log is known
+    pub fn utc(&self, timestamp: f64) -> Option<DateTime<Utc>> {
+        // An f64 unix time is only accurate to a fraction of a microsecond
+        let micros = (self.unix(timestamp)? * 1e6).round() as i64;
+        DateTime::from_timestamp_micros(micros)
+    }
+}
+
+/// Leap seconds between GPS time and UTC at a GPS time expressed on the unix scale
+pub fn leap_seconds(gps_unix: f64) -> f64 {
+    LEAP_SECONDS.iter()
+        .enumerate()
+        .take_while(|&(i, &date)| gps_unix >= (date + i as i64 + 1) as f64)
+        .count() as f64
+}
+
+/// Unix (UTC) time of a GPS week and time of week in seconds
+pub fn gps_to_unix(week: u32, tow: f64) -> f64 {
+    let gps_unix = GPS_EPOCH_UNIX + week as f64 * SECONDS_PER_WEEK + tow;
+    gps_unix - leap_seconds(gps_unix)
+}
+
+/// Incremental estimation of the time anchor of a log over a stream of telemetry messages.
+///
+/// Every GPS fix gives the UTC time of the log start (GPS time minus the message
+/// timestamp); the median of these is used, which ignores the occasional fix delayed
+/// by the link. `GPS_INT` has no week number: the last week seen in a `GPS` message
+/// of the same aircraft is used, or the week closest to `time_of_day`.
+#[derive(Debug)]
+pub struct TimeAnchorBuilder {
+    time_of_day: f64,
+    source: TimeSource,
+    local_start: Option<NaiveDateTime>,
+    weeks: HashMap<u8, u32>,
+    starts: Vec<f64>,
+}
+
+impl TimeAnchorBuilder {
+    pub fn new(time_of_day: f64, source: TimeSource) -> Self {
+        Self {
+            time_of_day,
+            source,
+            local_start: None,
+            weeks: HashMap::new(),
+            starts: Vec::new(),
+        }
+    }
+
+    /// Builder for a log known only by the local time of its file name.
+    ///
+    /// Read as if it were UTC, that time is off by the ground station time zone, which
+    /// stays well within the week and the GPS plausibility window: it is only used to
+    /// pick the GPS week and bound the anchor, and is never reported as UTC.
+    pub fn from_local_start(local_start: NaiveDateTime) -> Self {
+        Self {
+            local_start: Some(local_start),
+            ..Self::new(local_start.and_utc().timestamp() as f64, TimeSource::LocalFilename)
+        }
+    }
+
+    pub fn for_configuration(configuration: &LogConfiguration) -> Self {
+        match configuration.local_start {
+            Some(local_start) => Self::from_local_start(local_start),
+            None => Self::new(configuration.time_of_day, configuration.time_source),
+        }
+    }
+
+    /// Anchor when no GPS fix can be used
+    fn estimate(&self) -> TimeAnchor {
+        match self.local_start {
+            Some(local_start) => TimeAnchor::from_local_start(local_start),
+            None => TimeAnchor { source: self.source, ..TimeAnchor::from_time_of_day(self.time_of_day) },
+        }
+    }
+
+    pub fn add(&mut self, message: &TelemetryMessage) {
+        let field = |name: &str| message.fields.get(name).and_then(FieldValue::as_f64);
+        let fix = match message.message_name.as_str() {
+            "GPS" => {
+                let (Some(mode), Some(week), Some(itow)) = (field("mode"), field("week"), field("itow")) else {
+                    return;
+                };
+                if mode < 3.0 || week <= 0.0 {
+                    return;
+                }
+                self.weeks.insert(message.sender_id, week as u32);
+                Some((week as u32, itow / 1000.0))
+            }
+            "GPS_INT" => {
+                let (Some(fix), Some(tow)) = (field("fix"), field("tow")) else {
+                    return;
+                };
+                if fix < 3.0 {
+                    return;
+                }
+                let tow = tow / 1000.0;
+                let week = self.weeks.get(&message.sender_id).copied()
+                    .unwrap_or_else(|| self.nearest_week(message.timestamp, tow));
+                Some((week, tow))
+            }
+            _ => None,
+        };
+
+        if let Some((week, tow)) = fix
+            && tow > 0.0
+        {
+            self.starts.push(gps_to_unix(week, tow) - message.timestamp);
+        }
+    }
+
+    /// Week number that puts a time of week closest to the `time_of_day` estimate
+    fn nearest_week(&self, timestamp: f64, tow: f64) -> u32 {
+        let estimate = self.time_of_day + timestamp;
+        let gps_seconds = estimate + leap_seconds(estimate) - GPS_EPOCH_UNIX;
+        ((gps_seconds - tow) / SECONDS_PER_WEEK).round().max(0.0) as u32
+    }
+
+    pub fn finish(mut self) -> TimeAnchor {
+        if self.starts.is_empty() {
+            return self.estimate();
+        }
+
+        self.starts.sort_by(f64::total_cmp);
+        let middle = self.starts.len() / 2;
+        let start = if self.starts.len().is_multiple_of(2) {
+            (self.starts[middle - 1] + self.starts[middle]) / 2.0
+        } else {
+            self.starts[middle]
+        };
+
+        let offset = start - self.time_of_day;
+        if offset.abs() > MAX_GPS_OFFSET {
+            warn!("Ignoring GPS time anchor {:.0}s away from time_of_day", offset);
+            return self.estimate();
+        }
+
+        TimeAnchor {
+            source: TimeSource::Gps,
+            start: Some(start),
+            local_start: self.local_start,
+            offset,
+            samples: self.starts.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::fixtures::sample_protocol;
+    use crate::schema::parse_telemetry_line;
+
+    #[test]
+    fn test_gps_to_unix() {
+        // Start of GPS week 2374 is 2025-07-06 00:00:00 GPS, 18 leap seconds ahead of UTC
+        assert_eq!(gps_to_unix(2374, 0.0), 1_751_760_000.0 - 18.0);
+        assert_eq!(leap_seconds(GPS_EPOCH_UNIX), 0.0);
+        assert_eq!(leap_seconds(1_483_228_800.0 + 18.0), 18.0);
+        assert_eq!(leap_seconds(1_483_228_800.0 + 17.0), 17.0);
+    }
+
+    #[test]
+    fn test_gps_anchor() {
+        let protocol = sample_protocol();
+
+        // The log started at 1752068333.37 UTC, time_of_day is 1.5s late
+        let time_of_day = 1_752_068_334.87;
+        let gps_int = |timestamp: f64, tow_ms: u32| format!("{} 13 GPS_INT 0 0 0 0 0 0 0 0 0 0 0 0 {} 0 8 3 1", timestamp, tow_ms);
+
+        let lines = [
+            gps_int(10.0, 308_361_370),
+            gps_int(11.0, 308_362_370),
+            gps_int(12.0, 308_363_770), // delayed by the link
+            "13 13 GPS_INT 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 1 1".to_string(), // no fix
+        ];
+        let anchor_from = |mut builder: TimeAnchorBuilder| {
+            for line in &lines {
+                builder.add(&parse_telemetry_line(line, &protocol).unwrap());
+            }
+            builder.finish()
+        };
+        let anchor = anchor_from(TimeAnchorBuilder::new(time_of_day, TimeSource::TimeOfDay));
+
+        assert_eq!((anchor.source, anchor.samples), (TimeSource::Gps, 3));
+        assert!((anchor.start.unwrap() - 1_752_068_333.37).abs() < 1e-3);
+        assert!((anchor.offset + 1.5).abs() < 1e-3);
+        assert_eq!(anchor.utc(1.0).unwrap().to_rfc3339(), "2025-07-09T13:38:54.370+00:00");
+
+        let fallback = TimeAnchorBuilder::new(time_of_day, TimeSource::TimeOfDay).finish();
+        assert_eq!(fallback, TimeAnchor::from_time_of_day(time_of_day));
+
+        // Without the .log, the file name 25_07_09__15_38_54 gives the local (CEST) start time
+        let local_start = NaiveDateTime::parse_from_str("2025-07-09 15:38:54", "%Y-%m-%d %H:%M:%S").unwrap();
+        let anchor = anchor_from(TimeAnchorBuilder::from_local_start(local_start));
+        assert_eq!(anchor.source, TimeSource::Gps);
+        assert!((anchor.start.unwrap() - 1_752_068_333.37).abs() < 1e-3);
+
+        // Without a GPS fix the local time is kept as it is, it is not a UTC time
+        let fallback = TimeAnchorBuilder::from_local_start(local_start).finish();
+        assert_eq!(fallback.source, TimeSource::LocalFilename);
+        assert_eq!(fallback.local_start, Some(local_start));
+        assert_eq!((fallback.start, fallback.utc(1.0)), (None, None));
+    }
+}
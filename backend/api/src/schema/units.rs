@@ -0,0 +1,297 @@
+//! Unit handling for protocol fields (`UNIT`, `ALT_UNIT`, `ALT_UNIT_COEF`)
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use std::fmt;
+use std::str::FromStr;
+
+use super::protocol::FieldDefinition;
+
+/// Fractional bits of the paparazzi INT32 fixed-point (BFP) math types
+pub const INT32_POS_FRAC: i32 = 8;
+pub const INT32_SPEED_FRAC: i32 = 19;
+pub const INT32_ACCEL_FRAC: i32 = 10;
+pub const INT32_ANGLE_FRAC: i32 = 12;
+pub const INT32_RATE_FRAC: i32 = 12;
+pub const INT32_MAG_FRAC: i32 = 11;
+pub const INT32_TRIG_FRAC: i32 = 14;
+pub const INT32_QUAT_FRAC: i32 = 15;
+
+// BFP types by the SI unit of their value
+const BFP_SCALES: &[(&str, i32)] = &[
+    ("m", INT32_POS_FRAC),
+    ("m/s", INT32_SPEED_FRAC),
+    ("m/s2", INT32_ACCEL_FRAC),
+    ("rad", INT32_ANGLE_FRAC),
+    ("rad/s", INT32_RATE_FRAC),
+];
+
+/// Unit system values are returned in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UnitSystem {
+    /// Values exactly as logged, in the field's `UNIT`
+    #[default]
+    Raw,
+    /// Values in the field's `ALT_UNIT`, falling back to raw
+    Alt,
+    /// Values in SI base units, falling back to raw
+    Si,
+}
+
+/// Linear conversion of a field value into a target unit
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UnitConversion {
+    pub unit: Option<String>,
+    pub scale: f64,
+}
+
+// Coefficients from a named unit to its SI unit
+const SI_UNITS: &[(&str, &str, f64)] = &[
+    ("m", "m", 1.0),
+    ("cm", "m", 0.01),
+    ("mm", "m", 0.001),
+    ("ft", "m", 0.3048),
+    ("m/s", "m/s", 1.0),
+    ("cm/s", "m/s", 0.01),
+    ("mm/s", "m/s", 0.001),
+    ("m/s2", "m/s2", 1.0),
+    ("m/s^2", "m/s2", 1.0),
+    ("rad", "rad", 1.0),
+    ("deg", "rad", PI / 180.0),
+    ("decideg", "rad", 0.1 * PI / 180.0),
+    ("centideg", "rad", 0.01 * PI / 180.0),
+    ("rad/s", "rad/s", 1.0),
+    ("rad/sec", "rad/s", 1.0),
+    ("deg/s", "rad/s", PI / 180.0),
+    ("rad/s2", "rad/s2", 1.0),
+    ("deg/s2", "rad/s2", PI / 180.0),
+    ("deg/s^2", "rad/s2", PI / 180.0),
+    ("s", "s", 1.0),
+    ("sec", "s", 1.0),
+    ("decisec", "s", 0.1),
+    ("ms", "s", 0.001),
+    ("msec", "s", 0.001),
+    ("usec", "s", 0.000001),
+    ("mus", "s", 0.000001),
+    ("Pa", "Pa", 1.0),
+    ("pascal", "Pa", 1.0),
+    ("hPa", "Pa", 100.0),
+    ("mBar", "Pa", 100.0),
+    ("Bar", "Pa", 100000.0),
+    ("V", "V", 1.0),
+    ("volts", "V", 1.0),
+    ("deciV", "V", 0.1),
+    ("mV", "V", 0.001),
+    ("A", "A", 1.0),
+    ("mA", "A", 0.001),
+    ("W", "W", 1.0),
+    ("Hz", "Hz", 1.0),
+    ("hz", "Hz", 1.0),
+];
+
+/// Resolve a unit to its SI unit and scale.
+///
+/// Besides the named units above this understands the scaled forms used in
+/// messages.xml: `1e7deg`, `1e2_deg`, `1e4rad` and the BFP forms `2^8m`, `2^12rad`.
+pub fn si_scale(unit: &str) -> Option<(&'static str, f64)> {
+    if let Some(&(_, si, scale)) = SI_UNITS.iter().find(|(name, _, _)| *name == unit) {
+        return Some((si, scale));
+    }
+
+    let (base, exponent, factor) = if let Some(rest) = unit.strip_prefix("1e") {
+        let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+        (&rest[digits..], rest[..digits].parse::<i32>().ok()?, 10f64)
+    } else if let Some(rest) = unit.strip_prefix("2^") {
+        let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+        (&rest[digits..], rest[..digits].parse::<i32>().ok()?, 2f64)
+    } else {
+        return None;
+    };
+
+    let (si, scale) = si_scale(base.trim_start_matches('_'))?;
+    Some((si, scale * factor.powi(-exponent)))
+}
+
+/// Scale of a BFP fixed-point value with the given number of fractional bits
+pub fn bfp_scale(frac: i32) -> f64 {
+    2f64.powi(-frac)
+}
+
+/// Exact coefficient for a BFP field whose messages.xml `ALT_UNIT_COEF` is
+/// the fixed-point scale rounded to 7 decimals (`0.0000019` for 2^-19 m/s).
+fn exact_bfp_coef(coef: f64, alt_unit: &str) -> Option<f64> {
+    let (si, alt_scale) = si_scale(alt_unit)?;
+    let (_, frac) = BFP_SCALES.iter().find(|(unit, _)| *unit == si)?;
+    let exact = bfp_scale(*frac) / alt_scale;
+    ((exact * 1e7).round() / 1e7 == coef).then_some(exact)
+}
+
+impl FieldDefinition {
+    /// Conversion from the logged value to the requested unit system
+    pub fn conversion(&self, units: UnitSystem) -> UnitConversion {
+        let raw = UnitConversion {
+            unit: self.unit.clone(),
+            scale: 1.0,
+        };
+
+        match units {
+            UnitSystem::Raw => raw,
+            UnitSystem::Alt => self.alt_conversion().unwrap_or(raw),
+            UnitSystem::Si => self.si_conversion().unwrap_or(raw),
+        }
+    }
+
+    fn alt_conversion(&self) -> Option<UnitConversion> {
+        let alt_unit = self.alt_unit.as_ref()?;
+
+        // An explicit coefficient wins; otherwise derive it from the built-in table
+        let scale = match self.alt_unit_coef {
+            Some(coef) => exact_bfp_coef(coef, alt_unit).unwrap_or(coef),
+            None => {
+                let (raw_si, raw_scale) = si_scale(self.unit.as_deref()?)?;
+                let (alt_si, alt_scale) = si_scale(alt_unit)?;
+                if raw_si != alt_si {
+                    return None;
+                }
+                raw_scale / alt_scale
+            }
+        };
+
+        Some(UnitConversion {
+            unit: Some(alt_unit.clone()),
+            scale,
+        })
+    }
+
+    fn si_conversion(&self) -> Option<UnitConversion> {
+        if let Some((si, scale)) = self.unit.as_deref().and_then(si_scale) {
+            return Some(UnitConversion {
+                unit: Some(si.to_string()),
+                scale,
+            });
+        }
+
+        // Fixed-point fields often only declare the alt unit and its coefficient
+        let alt = self.alt_conversion()?;
+        let (si, scale) = si_scale(alt.unit.as_deref()?)?;
+        Some(UnitConversion {
+            unit: Some(si.to_string()),
+            scale: alt.scale * scale,
+        })
+    }
+}
+
+impl UnitConversion {
+    pub fn apply(&self, value: f64) -> f64 {
+        value * self.scale
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.scale == 1.0
+    }
+}
+
+impl fmt::Display for UnitSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            UnitSystem::Raw => "raw",
+            UnitSystem::Alt => "alt",
+            UnitSystem::Si => "si",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for UnitSystem {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "raw" => Ok(UnitSystem::Raw),
+            "alt" => Ok(UnitSystem::Alt),
+            "si" => Ok(UnitSystem::Si),
+            other => Err(anyhow!("Unknown unit system '{}', expected raw, alt or si", other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{default_dictionary, FieldType};
+
+    fn field(unit: Option<&str>, alt_unit: Option<&str>, alt_unit_coef: Option<f64>) -> FieldDefinition {
+        FieldDefinition {
+            name: "value".to_string(),
+            field_type: FieldType::Int32,
+            unit: unit.map(str::to_string),
+            alt_unit: alt_unit.map(str::to_string),
+            alt_unit_coef,
+        }
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9 * b.abs().max(1.0), "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_alt_conversion() {
+        let lat = field(Some("1e7deg"), Some("deg"), Some(0.0000001));
+        let conversion = lat.conversion(UnitSystem::Alt);
+        assert_eq!(conversion.unit.as_deref(), Some("deg"));
+        assert_close(conversion.apply(435639416.0), 43.5639416);
+
+        // No coefficient: derived from the built-in table
+        let alt = field(Some("mm"), Some("m"), None);
+        assert_close(alt.conversion(UnitSystem::Alt).apply(194120.0), 194.12);
+
+        let phi = field(Some("rad"), Some("deg"), None);
+        assert_close(phi.conversion(UnitSystem::Alt).apply(PI), 180.0);
+    }
+
+    #[test]
+    fn test_si_conversion() {
+        let lat = field(Some("1e7deg"), Some("deg"), Some(0.0000001));
+        let conversion = lat.conversion(UnitSystem::Si);
+        assert_eq!(conversion.unit.as_deref(), Some("rad"));
+        assert_close(conversion.apply(1800000000.0), PI);
+
+        // BFP position with only an alt unit (ROTORCRAFT_FP.east)
+        let east = field(None, Some("m"), Some(0.0039063));
+        let conversion = east.conversion(UnitSystem::Si);
+        assert_eq!(conversion.unit.as_deref(), Some("m"));
+        assert_close(conversion.apply(256.0), 1.0);
+
+        assert_close(si_scale("2^12rad").unwrap().1, bfp_scale(INT32_ANGLE_FRAC));
+
+        // Unknown units fall back to the raw value
+        let count = field(Some("events"), None, None);
+        assert!(count.conversion(UnitSystem::Si).is_identity());
+    }
+
+    #[test]
+    fn test_bfp_fields() {
+        let dictionary = default_dictionary().load().unwrap();
+        let fp = dictionary.get("ROTORCRAFT_FP").unwrap();
+
+        // The rounded 0.0000019 would be 0.4% off INT32_SPEED_FRAC
+        let vup = fp.field("vup").unwrap().conversion(UnitSystem::Si);
+        assert_eq!(vup.unit.as_deref(), Some("m/s"));
+        assert_eq!(vup.scale, bfp_scale(INT32_SPEED_FRAC));
+
+        let east = fp.field("east").unwrap().conversion(UnitSystem::Si);
+        assert_eq!(east.scale, bfp_scale(INT32_POS_FRAC));
+
+        let psi = fp.field("psi").unwrap().conversion(UnitSystem::Alt);
+        assert_eq!(psi.unit.as_deref(), Some("deg"));
+        assert_close(psi.apply(4096.0 * PI), 180.0);
+        assert_close(fp.field("psi").unwrap().conversion(UnitSystem::Si).scale, bfp_scale(INT32_ANGLE_FRAC));
+
+        // 1e7deg coefficients are not mistaken for BFP scales
+        let wp = dictionary.get("WP_MOVED_LLA").unwrap();
+        let lat = wp.field("lat").unwrap().conversion(UnitSystem::Alt);
+        assert_eq!(lat.scale, 0.0000001);
+    }
+}
@@ -0,0 +1,152 @@
+//! Paparazzi versions of the `paparazzi_version` comments at the top of a .log file
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// A `git describe` version such as `v7.0_unstable-133-g0bba9528bd-dirty`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PaparazziVersion {
+    pub raw: String,
+    /// Tag the build descends from, e.g. `v7.0_unstable`
+    pub base: String,
+    /// Commits between the tag and the build, 0 for a tagged build
+    pub commits_since_tag: u32,
+    /// Abbreviated commit hash, without the `g` prefix
+    pub git_hash: Option<String>,
+    /// Built from a tree with uncommitted changes
+    pub dirty: bool,
+}
+
+impl FromStr for PaparazziVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let raw = s.trim();
+        if raw.is_empty() || raw.contains(char::is_whitespace) {
+            return Err(anyhow!("Invalid paparazzi version '{}'", s));
+        }
+
+        let (described, dirty) = match raw.strip_suffix("-dirty") {
+            Some(described) => (described, true),
+            None => (raw, false),
+        };
+        let mut parts = described.rsplitn(3, '-');
+        let (last, middle, first) = (parts.next(), parts.next(), parts.next());
+        let hash = last
+            .and_then(|last| last.strip_prefix('g'))
+            .filter(|hash| hash.len() >= 4 && hash.chars().all(|c| c.is_ascii_hexdigit()));
+
+        let (base, commits_since_tag, git_hash) = match (first, middle.and_then(|m| m.parse::<u32>().ok()), hash) {
+            (Some(base), Some(commits), Some(hash)) => (base.to_string(), commits, Some(hash.to_string())),
+            _ => (described.to_string(), 0, None),
+        };
+        Ok(PaparazziVersion { raw: raw.to_string(), base, commits_since_tag, git_hash, dirty })
+    }
+}
+
+impl fmt::Display for PaparazziVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+/// Paparazzi versions of the ground station that logged a flight and of the firmware it flew
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BuildProvenance {
+    pub runtime: Option<PaparazziVersion>,
+    pub build: Option<PaparazziVersion>,
+}
+
+impl BuildProvenance {
+    /// Read the `logged with runtime/build paparazzi_version` comments before `<configuration>`.
+    ///
+    /// A comment that names neither is taken as the runtime version, as older loggers wrote only that one.
+    pub fn from_log_header(log_content: &str) -> Self {
+        let mut provenance = BuildProvenance::default();
+        let header = log_content.split("<configuration").next().unwrap_or_default();
+        for comment in header.split("<!--").skip(1) {
+            let comment = comment.split("-->").next().unwrap_or_default();
+            let Some((before, version)) = comment.split_once("paparazzi_version") else {
+                continue;
+            };
+            let Ok(version) = version.parse::<PaparazziVersion>() else {
+                continue;
+            };
+            if before.contains("build") {
+                provenance.build = Some(version);
+            } else {
+                provenance.runtime = Some(version);
+            }
+        }
+        provenance
+    }
+
+    /// Firmware built from another commit (or tree state) than the ground station that logged it
+    pub fn mismatch(&self) -> bool {
+        matches!((&self.runtime, &self.build), (Some(runtime), Some(build)) if runtime.raw != build.raw)
+    }
+
+    pub fn dirty(&self) -> bool {
+        self.build.as_ref().is_some_and(|build| build.dirty)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_provenance() {
+        let header = "<!-- logged with runtime paparazzi_version v7.0_unstable-133-g0bba9528bd-dirty -->\n\
+            <!-- logged with build paparazzi_version v7.0_unstable-131-ga20609ad49 -->\n\
+            <configuration time_of_day=\"1752068334.87\" data_file=\"25_07_09__15_38_54.data\">";
+        let provenance = BuildProvenance::from_log_header(header);
+
+        let runtime = provenance.runtime.as_ref().unwrap();
+        assert_eq!((runtime.base.as_str(), runtime.commits_since_tag, runtime.git_hash.as_deref(), runtime.dirty),
+            ("v7.0_unstable", 133, Some("0bba9528bd"), true));
+        let build = provenance.build.as_ref().unwrap();
+        assert_eq!((build.base.as_str(), build.commits_since_tag, build.git_hash.as_deref(), build.dirty),
+            ("v7.0_unstable", 131, Some("a20609ad49"), false));
+        assert!(provenance.mismatch());
+        assert!(!provenance.dirty());
+
+        let tagged: PaparazziVersion = "v6.3".parse().unwrap();
+        assert_eq!((tagged.base.as_str(), tagged.commits_since_tag, tagged.git_hash), ("v6.3", 0, None));
+        let legacy = BuildProvenance::from_log_header("<!-- paparazzi_version v5.18-dirty -->\n<configuration>");
+        assert_eq!(legacy.runtime.map(|v| (v.base, v.dirty)), Some(("v5.18".to_string(), true)));
+        assert!(legacy.build.is_none());
+    }
+
+    #[test]
+    fn test_invalid_versions() {
+        for invalid in ["", "   ", "v7.0 unstable", "v7.0\tdirty"] {
+            assert!(invalid.parse::<PaparazziVersion>().is_err(), "{:?}", invalid);
+        }
+
+        // Not a git describe suffix: the whole version is the base
+        for (raw, base) in [
+            ("v7.0-133-gnothex", "v7.0-133-gnothex"),
+            ("v7.0-133-g0bb", "v7.0-133-g0bb"),
+            ("v7.0-many-g0bba9528bd", "v7.0-many-g0bba9528bd"),
+            ("0bba9528bd-dirty", "0bba9528bd"),
+        ] {
+            let version: PaparazziVersion = raw.parse().unwrap();
+            assert_eq!((version.base.as_str(), version.commits_since_tag, version.git_hash), (base, 0, None), "{}", raw);
+        }
+
+        // Unparseable versions, comments without one and comments past <configuration> are ignored
+        let provenance = BuildProvenance::from_log_header("<!-- logged with build paparazzi_version -->\n\
+            <!-- logged by the ground station -->\n\
+            <configuration>\n<!-- paparazzi_version v6.3 -->");
+        assert_eq!(provenance, BuildProvenance::default());
+        assert!(!provenance.mismatch());
+
+        // Unterminated comment
+        let provenance = BuildProvenance::from_log_header("<!-- logged with build paparazzi_version v6.3-dirty");
+        assert!(provenance.dirty());
+        assert!(!provenance.mismatch());
+    }
+}
@@ -0,0 +1,148 @@
+//! Helpers for reading the XML configuration embedded in .log files
+
+use anyhow::{anyhow, Result};
+use std::borrow::Cow;
+
+/// Escape ampersands that do not start an entity reference.
+///
+/// The logger copies flight plan and autopilot attributes verbatim, so
+/// conditions such as `cond="bat_low&&(flag==false)"` end up as raw `&`,
+/// which a conforming XML parser rejects.
+pub fn escape_stray_ampersands(xml: &str) -> Cow<'_, str> {
+    if !xml.contains('&') {
+        return Cow::Borrowed(xml);
+    }
+
+    let mut escaped = String::with_capacity(xml.len() + 64);
+    let mut rest = xml;
+    while let Some(index) = rest.find('&') {
+        escaped.push_str(&rest[..index]);
+        rest = &rest[index..];
+        if starts_with_entity(rest) {
+            escaped.push('&');
+        } else {
+            escaped.push_str("&amp;");
+        }
+        rest = &rest[1..];
+    }
+    escaped.push_str(rest);
+
+    Cow::Owned(escaped)
+}
+
+fn starts_with_entity(text: &str) -> bool {
+    let Some(end) = text[1..].find(';').filter(|&end| end <= 10) else {
+        return false;
+    };
+    let name = &text[1..end + 1];
+
+    match name.strip_prefix('#') {
+        Some(hex) if hex.starts_with(['x', 'X']) => hex.len() > 1 && hex[1..].chars().all(|c| c.is_ascii_hexdigit()),
+        Some(decimal) => !decimal.is_empty() && decimal.chars().all(|c| c.is_ascii_digit()),
+        None => matches!(name, "amp" | "lt" | "gt" | "quot" | "apos"),
+    }
+}
+
+/// Parse an XML document; logger output should go through `escape_stray_ampersands` first
+pub fn parse_document(xml: &str) -> Result<roxmltree::Document<'_>> {
+    roxmltree::Document::parse(xml)
+        .map_err(|e| anyhow!("Failed to parse XML: {}", e))
+}
+
+/// Top-level `<tag>` elements of a piece of markup, with their byte offset in it.
+///
+/// Elements are cut out of the text without parsing what surrounds them, so that
+/// malformed markup in one section of a .log does not prevent reading the others.
+/// An element that is never closed runs to the end of the text.
+pub fn find_elements<'a>(text: &'a str, tag: &str) -> Vec<(usize, &'a str)> {
+    let open = format!("<{}", tag);
+    let close = format!("</{}>", tag);
+    let mut elements = Vec::new();
+    let mut position = 0;
+
+    while let Some(start) = find_start_tag(text, &open, position) {
+        let Some(tag_end) = text[start..].find('>').map(|end| start + end + 1) else {
+            break;
+        };
+        let end = if text[..tag_end].ends_with("/>") {
+            tag_end
+        } else {
+            text[tag_end..].find(&close).map_or(text.len(), |end| tag_end + end + close.len())
+        };
+        elements.push((start, &text[start..end]));
+        position = end;
+    }
+    elements
+}
+
+/// Offset of the next `<tag` that is a start tag of exactly that name (`<conf` does not match `<configuration`)
+fn find_start_tag(text: &str, open: &str, from: usize) -> Option<usize> {
+    let mut from = from;
+    while let Some(index) = text[from..].find(open) {
+        let start = from + index;
+        match text[start + open.len()..].chars().next() {
+            Some(c) if c.is_whitespace() || c == '>' || c == '/' => return Some(start),
+            _ => from = start + open.len(),
+        }
+    }
+    None
+}
+
+/// Start tag of an element rewritten as an empty element, to read its attributes
+/// without the content: `<aircraft name="x">…` becomes `<aircraft name="x"/>`
+pub fn start_tag(element: &str) -> Option<String> {
+    let end = element.find('>')?;
+    Some(format!("{}/>", element[..end].trim_end_matches('/')))
+}
+
+/// Owned value of an attribute; paparazzi files mix `NAME` and `name`, so the lookup ignores case
+pub fn attr(node: roxmltree::Node, name: &str) -> Option<String> {
+    node.attribute(name)
+        .or_else(|| node.attributes().find(|a| a.name().eq_ignore_ascii_case(name)).map(|a| a.value()))
+        .map(str::to_string)
+}
+
+/// Child elements with the given tag name
+pub fn children<'a, 'input>(node: roxmltree::Node<'a, 'input>, tag: &'static str) -> impl Iterator<Item = roxmltree::Node<'a, 'input>> {
+    node.children().filter(move |child| child.has_tag_name(tag))
+}
+
+/// First child element with the given tag name
+pub fn child<'a, 'input>(node: roxmltree::Node<'a, 'input>, tag: &'static str) -> Option<roxmltree::Node<'a, 'input>> {
+    children(node, tag).next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_stray_ampersands() {
+        let xml = r#"<exception cond="(bat_low&&(flag==false))" text="a &amp; b &#38; &lt;"/>"#;
+        let escaped = escape_stray_ampersands(xml);
+        assert_eq!(escaped, r#"<exception cond="(bat_low&amp;&amp;(flag==false))" text="a &amp; b &#38; &lt;"/>"#);
+
+        let document = parse_document(&escaped).unwrap();
+        assert_eq!(document.root_element().attribute("cond"), Some("(bat_low&&(flag==false))"));
+        assert!(matches!(escape_stray_ampersands("<a/>"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_find_elements() {
+        let text = r#"<configuration><conf><aircraft ac_id="1"><airframe NAME="a"><settings/></airframe></aircraft>
+            <aircraft ac_id="2"/><aircraft ac_id="3"><airframe NAME="b""#;
+        let conf = find_elements(text, "conf");
+        assert_eq!(conf.len(), 1);
+        assert_eq!(conf[0].0, "<configuration>".len());
+
+        let aircraft = find_elements(text, "aircraft");
+        assert_eq!(aircraft.len(), 3);
+        assert!(aircraft[0].1.ends_with("</airframe></aircraft>"));
+        assert_eq!(aircraft[1].1, r#"<aircraft ac_id="2"/>"#);
+        // Never closed: runs to the end
+        assert!(aircraft[2].1.ends_with(r#"NAME="b""#));
+
+        assert_eq!(start_tag(aircraft[0].1).as_deref(), Some(r#"<aircraft ac_id="1"/>"#));
+        assert_eq!(start_tag(aircraft[1].1).as_deref(), Some(r#"<aircraft ac_id="2"/>"#));
+    }
+}
@@ -3,6 +3,8 @@
 use anyhow::{Result, anyhow};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
 use std::path::Path;
 use tokio::fs;
 use tracing::{debug, info, warn};
@@ -87,37 +89,55 @@ pub async fn parse_log_configuration<P: AsRef<Path>>(log_file_path: P) -> Result
     Ok(config)
 }
 
-/// Parse telemetry data from .data file
-pub async fn parse_telemetry_data<P: AsRef<Path>>(data_file_path: P) -> Result<Vec<TelemetryRecord>> {
-    let content = fs::read_to_string(&data_file_path).await
-        .map_err(|e| anyhow!("Failed to read data file: {}", e))?;
+/// Iterator over the raw records of a .data file, read one line at a time
+pub struct TelemetryRecords<R> {
+    lines: std::io::Lines<R>,
+    line_num: usize,
+}
+
+/// Stream telemetry data from .data file without loading it in memory
+pub fn parse_telemetry_data<P: AsRef<Path>>(data_file_path: P) -> Result<TelemetryRecords<BufReader<File>>> {
+    let file = File::open(&data_file_path)
+        .map_err(|e| anyhow!("Failed to open data file: {}", e))?;
     
     info!("Parsing telemetry data from: {}", data_file_path.as_ref().display());
     
-    let mut records = Vec::new();
-    
-    for (line_num, line) in content.lines().enumerate() {
-        if line.trim().is_empty() {
-            continue;
-        }
-        
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() < 3 {
-            warn!("Skipping invalid line {}: insufficient parts", line_num + 1);
-            continue;
-        }
-        
-        match parse_telemetry_line(parts) {
-            Ok(record) => records.push(record),
-            Err(e) => {
-                debug!("Failed to parse line {}: {}", line_num + 1, e);
+    Ok(TelemetryRecords {
+        lines: BufReader::new(file).lines(),
+        line_num: 0,
+    })
+}
+
+impl<R: BufRead> Iterator for TelemetryRecords<R> {
+    type Item = Result<TelemetryRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for line in self.lines.by_ref() {
+            self.line_num += 1;
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => return Some(Err(anyhow!("Failed to read data file: {}", e))),
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() < 3 {
+                warn!("Skipping invalid line {}: insufficient parts", self.line_num);
                 continue;
             }
+            
+            match parse_telemetry_line(parts) {
+                Ok(record) => return Some(Ok(record)),
+                Err(e) => {
+                    debug!("Failed to parse line {}: {}", self.line_num, e);
+                    continue;
+                }
+            }
         }
+        None
     }
-    
-    info!("Parsed {} telemetry records", records.len());
-    Ok(records)
 }
 
 fn parse_telemetry_line(parts: Vec<&str>) -> Result<TelemetryRecord> {
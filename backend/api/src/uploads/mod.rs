@@ -0,0 +1,329 @@
+//! Expansion of compressed uploads and archive bundles into the log files they contain
+//!
+//! Flight PCs archive `var/logs` as tarballs or gzip each `.data` on its own. An upload
+//! is recognised by its magic bytes, not its name, and expanded in a single streaming
+//! pass: gzip and zstd are decompressed, tar and zip members are walked, and nested
+//! containers (a tarball of gzipped `.data` files) are expanded in turn. Only the
+//! telemetry files are kept; everything else in a bundle is listed as skipped.
+
+use flate2::read::GzDecoder;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufWriter, Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use tracing::{debug, info};
+use uuid::Uuid;
+
+/// Extensions of the files kept from an archive
+pub const LOG_EXTENSIONS: [&str; 3] = ["log", "data", "tlm"];
+
+/// Extensions of the telemetry half of a file pair, text .data first
+pub const DATA_EXTENSIONS: [&str; 2] = ["data", "tlm"];
+
+/// Upper bound on the bytes expanded from one upload, guarding against decompression bombs
+pub const MAX_EXPANDED_BYTES: u64 = 16 * 1024 * 1024 * 1024;
+
+/// Containers nested deeper than this are not expanded
+const MAX_DEPTH: usize = 4;
+
+/// Bytes needed to recognise every container, up to the tar magic at offset 257
+const SNIFF_BYTES: usize = 512;
+
+#[derive(Debug, thiserror::Error)]
+pub enum UploadError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid zip archive: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("Archive expands to more than {} bytes", MAX_EXPANDED_BYTES)]
+    TooLarge,
+}
+
+/// Container format recognised from the first bytes of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Container {
+    Gzip,
+    Zstd,
+    Zip,
+    Tar,
+}
+
+impl Container {
+    pub fn detect(head: &[u8]) -> Option<Self> {
+        match head {
+            [0x1f, 0x8b, ..] => Some(Container::Gzip),
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Some(Container::Zstd),
+            [b'P', b'K', 0x03, 0x04, ..] | [b'P', b'K', 0x05, 0x06, ..] => Some(Container::Zip),
+            _ if head.get(257..262) == Some(b"ustar") => Some(Container::Tar),
+            _ => None,
+        }
+    }
+}
+
+/// A telemetry file written out of an archive
+#[derive(Debug, Clone)]
+pub struct ExtractedFile {
+    /// Id of the file, also its name in the storage directory
+    pub id: Uuid,
+    /// File name, without the directories of the archive
+    pub filename: String,
+    /// Path inside the archive, through every nesting level (`logs.tar/var/logs/a.data.gz/a.data`)
+    pub member_path: String,
+    /// Directories of the member inside the archives (`var/logs`), empty at their root.
+    /// Files of an archive only pair with files of the same directory.
+    pub directory: String,
+    pub storage_path: PathBuf,
+    pub file_size: i64,
+    /// blake3 hash of the expanded content
+    pub file_hash: String,
+}
+
+/// Files expanded out of an upload
+#[derive(Debug, Default)]
+pub struct ExpandedUpload {
+    pub container: Option<Container>,
+    pub files: Vec<ExtractedFile>,
+    /// Members that are not telemetry files or could not be expanded
+    pub skipped: Vec<String>,
+}
+
+/// Expand an uploaded file into `storage_dir`, returning `None` when it is neither
+/// compressed nor an archive. The upload itself is left in place.
+pub fn expand_upload(upload_path: &Path, filename: &str, storage_dir: &Path) -> Result<Option<ExpandedUpload>, UploadError> {
+    let mut file = File::open(upload_path)?;
+    let mut head = Vec::with_capacity(SNIFF_BYTES);
+    (&mut file).take(SNIFF_BYTES as u64).read_to_end(&mut head)?;
+    let Some(container) = Container::detect(&head) else {
+        return Ok(None);
+    };
+
+    let mut expander = Expander {
+        storage_dir,
+        expanded_bytes: 0,
+        upload: ExpandedUpload {
+            container: Some(container),
+            ..Default::default()
+        },
+    };
+    let result = match container {
+        // Zip needs to seek to its central directory, read it from the file itself
+        Container::Zip => expander.expand_zip(File::open(upload_path)?, filename, "", 0),
+        _ => expander.expand(&mut Cursor::new(head).chain(file), filename, filename, "", 0),
+    };
+
+    if let Err(e) = result {
+        for extracted in &expander.upload.files {
+            let _ = std::fs::remove_file(&extracted.storage_path);
+        }
+        return Err(e);
+    }
+
+    let upload = expander.upload;
+    info!("Expanded {} ({:?}): {} log file(s), {} member(s) skipped",
+        filename, container, upload.files.len(), upload.skipped.len());
+    Ok(Some(upload))
+}
+
+struct Expander<'a> {
+    storage_dir: &'a Path,
+    expanded_bytes: u64,
+    upload: ExpandedUpload,
+}
+
+impl Expander<'_> {
+    /// Expand a stream named `name`, found at `path` in the upload and in `directory` of its archives
+    fn expand(&mut self, reader: &mut dyn Read, name: &str, path: &str, directory: &str, depth: usize) -> Result<(), UploadError> {
+        let mut head = Vec::with_capacity(SNIFF_BYTES);
+        reader.take(SNIFF_BYTES as u64).read_to_end(&mut head)?;
+        let mut reader = Cursor::new(head.as_slice()).chain(reader);
+
+        let container = Container::detect(&head);
+        if container.is_some() && depth >= MAX_DEPTH {
+            debug!("Not expanding {}: nested too deep", path);
+            self.upload.skipped.push(path.to_string());
+            return Ok(());
+        }
+
+        match container {
+            Some(Container::Gzip) => {
+                let inner = decompressed_name(name, &["gz", "gzip"], "tgz");
+                self.expand(&mut GzDecoder::new(reader), &inner, &format!("{}/{}", path, inner), directory, depth + 1)
+            }
+            Some(Container::Zstd) => {
+                let inner = decompressed_name(name, &["zst", "zstd"], "tzst");
+                let mut decoder = zstd::stream::read::Decoder::new(reader)?;
+                self.expand(&mut decoder, &inner, &format!("{}/{}", path, inner), directory, depth + 1)
+            }
+            Some(Container::Tar) => {
+                let mut archive = tar::Archive::new(reader);
+                for entry in archive.entries()? {
+                    let mut entry = entry?;
+                    if !entry.header().entry_type().is_file() {
+                        continue;
+                    }
+                    let member = entry.path()?.to_string_lossy().to_string();
+                    let member_name = file_name(&member);
+                    let member_directory = member_directory(directory, &member);
+                    self.expand(&mut entry, &member_name, &format!("{}/{}", path, member), &member_directory, depth + 1)?;
+                }
+                Ok(())
+            }
+            Some(Container::Zip) => {
+                // A zip inside another container has no seekable file to read its directory from
+                debug!("Not expanding nested zip {}", path);
+                self.upload.skipped.push(path.to_string());
+                Ok(())
+            }
+            None => self.write(&mut reader, name, path, directory),
+        }
+    }
+
+    fn expand_zip(&mut self, file: File, path: &str, directory: &str, depth: usize) -> Result<(), UploadError> {
+        let mut archive = zip::ZipArchive::new(file)?;
+        for index in 0..archive.len() {
+            let mut entry = archive.by_index(index)?;
+            if !entry.is_file() {
+                continue;
+            }
+            let member = entry.name().to_string();
+            let member_name = file_name(&member);
+            let member_directory = member_directory(directory, &member);
+            self.expand(&mut entry, &member_name, &format!("{}/{}", path, member), &member_directory, depth + 1)?;
+        }
+        Ok(())
+    }
+
+    /// Write a telemetry file to storage, hashing it on the way
+    fn write(&mut self, reader: &mut dyn Read, name: &str, path: &str, directory: &str) -> Result<(), UploadError> {
+        let extension = Path::new(name).extension().and_then(|e| e.to_str()).unwrap_or("");
+        if !LOG_EXTENSIONS.contains(&extension) {
+            self.upload.skipped.push(path.to_string());
+            return Ok(());
+        }
+
+        let id = Uuid::new_v4();
+        let storage_path = self.storage_dir.join(id.to_string());
+        let mut file = BufWriter::new(File::create(&storage_path)?);
+        let mut hasher = blake3::Hasher::new();
+        let mut buffer = vec![0u8; 64 * 1024];
+        let mut file_size = 0u64;
+
+        let copied = loop {
+            let read = match reader.read(&mut buffer) {
+                Ok(0) => break file.flush().map_err(UploadError::from),
+                Ok(read) => read,
+                Err(e) => break Err(e.into()),
+            };
+            file_size += read as u64;
+            self.expanded_bytes += read as u64;
+            if self.expanded_bytes > MAX_EXPANDED_BYTES {
+                break Err(UploadError::TooLarge);
+            }
+            hasher.update(&buffer[..read]);
+            if let Err(e) = file.write_all(&buffer[..read]) {
+                break Err(e.into());
+            }
+        };
+        if let Err(e) = copied {
+            drop(file);
+            let _ = std::fs::remove_file(&storage_path);
+            return Err(e);
+        }
+
+        self.upload.files.push(ExtractedFile {
+            id,
+            filename: name.to_string(),
+            member_path: path.to_string(),
+            directory: directory.to_string(),
+            storage_path,
+            file_size: file_size as i64,
+            file_hash: hasher.finalize().to_hex().to_string(),
+        });
+        Ok(())
+    }
+}
+
+/// Last component of an archive member path
+fn file_name(member: &str) -> String {
+    member.rsplit(['/', '\\']).next().unwrap_or(member).to_string()
+}
+
+/// Directory of an archive member, under the directory of the archive itself
+fn member_directory(directory: &str, member: &str) -> String {
+    let parent = member.rsplit_once(['/', '\\']).map_or("", |(parent, _)| parent);
+    [directory, parent].into_iter().filter(|part| !part.is_empty()).collect::<Vec<_>>().join("/")
+}
+
+/// Name of a decompressed file: `a.data.gz` gives `a.data`, `logs.tgz` gives `logs.tar`
+fn decompressed_name(name: &str, extensions: &[&str], tar_extension: &str) -> String {
+    match name.rsplit_once('.') {
+        Some((stem, extension)) if extensions.contains(&extension.to_ascii_lowercase().as_str()) => stem.to_string(),
+        Some((stem, extension)) if extension.eq_ignore_ascii_case(tar_extension) => format!("{}.tar", stem),
+        _ => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use std::io::Seek;
+
+    fn gzip(content: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(content).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_expand_archives() {
+        let dir = std::env::temp_dir().join(format!("ppz-uploads-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // Tarball of var/logs with a gzipped .data, compressed with zstd
+        let mut tarball = tar::Builder::new(Vec::new());
+        for (path, content) in [
+            ("var/logs/25_07_09__13_38_54.log", b"<configuration/>".to_vec()),
+            ("var/logs/25_07_09__13_38_54.data.gz", gzip(b"1.0 13 ALIVE 1,2,3\n")),
+            ("var/logs/README", b"not a log".to_vec()),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            tarball.append_data(&mut header, path, content.as_slice()).unwrap();
+        }
+        let tar_zst = zstd::encode_all(tarball.into_inner().unwrap().as_slice(), 0).unwrap();
+        let upload_path = dir.join("upload");
+        std::fs::write(&upload_path, &tar_zst).unwrap();
+
+        let upload = expand_upload(&upload_path, "logs.tar.zst", &dir).unwrap().unwrap();
+        assert_eq!(upload.container, Some(Container::Zstd));
+        let names: Vec<_> = upload.files.iter().map(|f| f.filename.as_str()).collect();
+        assert_eq!(names, ["25_07_09__13_38_54.log", "25_07_09__13_38_54.data"]);
+        assert_eq!(upload.files[1].member_path,
+            "logs.tar.zst/logs.tar/var/logs/25_07_09__13_38_54.data.gz/25_07_09__13_38_54.data");
+        assert_eq!(upload.files[1].directory, "var/logs");
+        assert_eq!(std::fs::read(&upload.files[1].storage_path).unwrap(), b"1.0 13 ALIVE 1,2,3\n");
+        assert_eq!(upload.files[1].file_hash, blake3::hash(b"1.0 13 ALIVE 1,2,3\n").to_hex().to_string());
+        assert_eq!(upload.skipped, ["logs.tar.zst/logs.tar/var/logs/README"]);
+
+        // Zip bundle
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("flight.data", zip::write::SimpleFileOptions::default()).unwrap();
+        zip.write_all(b"1.0 13 ALIVE 1,2,3\n").unwrap();
+        let mut zip = zip.finish().unwrap();
+        zip.rewind().unwrap();
+        std::fs::write(&upload_path, zip.into_inner()).unwrap();
+
+        let upload = expand_upload(&upload_path, "bundle.zip", &dir).unwrap().unwrap();
+        assert_eq!(upload.files.len(), 1);
+        assert_eq!(upload.files[0].member_path, "bundle.zip/flight.data");
+        assert_eq!(upload.files[0].directory, "");
+
+        std::fs::write(&upload_path, b"1.0 13 ALIVE 1,2,3\n").unwrap();
+        assert!(expand_upload(&upload_path, "flight.data", &dir).unwrap().is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}